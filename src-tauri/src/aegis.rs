@@ -0,0 +1,169 @@
+use base64::{Engine as _, engine::general_purpose};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use zeroize::Zeroizing;
+
+use crate::crypto::{decrypt_with_nonce, derive_scrypt_key};
+use crate::types::{AppError, Result};
+
+// Key slot types defined by Aegis; only password slots can be opened here
+const SLOT_TYPE_PASSWORD: u32 = 1;
+
+/// Top level of an Aegis export (`aegis-export-*.json`). `db` is the plain
+/// database object for unencrypted exports and a base64 string otherwise.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AegisVault {
+    pub version: u32,
+    pub header: AegisHeader,
+    pub db: Value,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AegisHeader {
+    pub slots: Option<Vec<AegisSlot>>,
+    pub params: Option<AegisCipherParams>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AegisSlot {
+    #[serde(rename = "type")]
+    pub slot_type: u32,
+    pub key: String,
+    pub key_params: AegisCipherParams,
+    pub n: Option<u64>,
+    pub r: Option<u32>,
+    pub p: Option<u32>,
+    pub salt: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AegisCipherParams {
+    pub nonce: String,
+    pub tag: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AegisDb {
+    pub version: u32,
+    // Kept as raw values so one malformed entry cannot fail the whole import
+    pub entries: Vec<Value>,
+    #[serde(default)]
+    pub groups: Vec<AegisGroup>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AegisGroup {
+    pub uuid: String,
+    pub name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AegisEntry {
+    #[serde(rename = "type")]
+    pub entry_type: String,
+    #[serde(default)]
+    pub uuid: String,
+    pub name: String,
+    #[serde(default)]
+    pub issuer: String,
+    #[serde(default)]
+    pub note: String,
+    pub info: AegisEntryInfo,
+    // Database version 3 references groups by uuid, version 2 stored the name
+    #[serde(default)]
+    pub groups: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AegisEntryInfo {
+    pub secret: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub algo: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub digits: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub period: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub counter: Option<u64>,
+}
+
+impl AegisDb {
+    pub fn group_name(&self, entry: &AegisEntry) -> Option<String> {
+        entry.groups
+            .iter()
+            .find_map(|uuid| self.groups.iter().find(|g| &g.uuid == uuid))
+            .map(|g| g.name.clone())
+            .or_else(|| entry.group.clone())
+            .filter(|name| !name.is_empty())
+    }
+}
+
+pub fn is_encrypted(vault: &AegisVault) -> bool {
+    vault.db.is_string()
+}
+
+/// Parses an Aegis export, decrypting it with `password` when it is encrypted
+pub fn read_vault(content: &str, password: Option<&str>) -> Result<AegisDb> {
+    let vault: AegisVault = serde_json::from_str(content)
+        .map_err(|e| AppError::Serialization(format!("Invalid Aegis file format: {}", e)))?;
+
+    let db = if is_encrypted(&vault) {
+        let password = password
+            .filter(|p| !p.is_empty())
+            .ok_or_else(|| AppError::Encryption("This Aegis vault is encrypted, a password is required".to_string()))?;
+        decrypt_db(&vault, password)?
+    } else {
+        vault.db
+    };
+
+    serde_json::from_value(db)
+        .map_err(|e| AppError::Serialization(format!("Invalid Aegis database: {}", e)))
+}
+
+fn decrypt_db(vault: &AegisVault, password: &str) -> Result<Value> {
+    let master_key = unlock_master_key(&vault.header, password)?;
+
+    let params = vault.header.params.as_ref()
+        .ok_or_else(|| AppError::Encryption("Aegis vault is missing its cipher parameters".to_string()))?;
+
+    let mut ciphertext = general_purpose::STANDARD
+        .decode(vault.db.as_str().unwrap_or_default())
+        .map_err(|_| AppError::Encryption("Invalid base64 data in Aegis vault".to_string()))?;
+    ciphertext.extend_from_slice(&decode_hex(&params.tag)?);
+
+    let plaintext = Zeroizing::new(decrypt_with_nonce(&master_key, &decode_hex(&params.nonce)?, &ciphertext)
+        .map_err(|_| AppError::Encryption("Aegis vault is corrupted".to_string()))?);
+
+    Ok(serde_json::from_slice(&plaintext)?)
+}
+
+fn unlock_master_key(header: &AegisHeader, password: &str) -> Result<Zeroizing<[u8; 32]>> {
+    let slots = header.slots.as_deref().unwrap_or_default();
+
+    for slot in slots.iter().filter(|s| s.slot_type == SLOT_TYPE_PASSWORD) {
+        let (Some(n), Some(r), Some(p), Some(salt)) = (slot.n, slot.r, slot.p, slot.salt.as_deref()) else {
+            continue;
+        };
+
+        let slot_key = derive_scrypt_key(password.as_bytes(), &decode_hex(salt)?, n, r, p)?;
+
+        let mut wrapped = decode_hex(&slot.key)?;
+        wrapped.extend_from_slice(&decode_hex(&slot.key_params.tag)?);
+
+        // A slot that does not open with this password just means "try the next one"
+        if let Ok(master_key) = decrypt_with_nonce(&slot_key, &decode_hex(&slot.key_params.nonce)?, &wrapped) {
+            let master_key = Zeroizing::new(master_key);
+            return <[u8; 32]>::try_from(master_key.as_slice())
+                .map(Zeroizing::new)
+                .map_err(|_| AppError::Encryption("Invalid master key in Aegis vault".to_string()));
+        }
+    }
+
+    Err(AppError::Encryption("Invalid password for this Aegis vault".to_string()))
+}
+
+fn decode_hex(value: &str) -> Result<Vec<u8>> {
+    hex::decode(value).map_err(|_| AppError::Encryption("Invalid hex data in Aegis vault".to_string()))
+}
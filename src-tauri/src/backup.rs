@@ -0,0 +1,120 @@
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+use crate::crypto::{decrypt_data, derive_scrypt_key, encrypt_data};
+use crate::types::{AppError, OtpApp, Result};
+
+const BACKUP_FORMAT: &str = "plaxo-otp-backup";
+const BACKUP_VERSION: u32 = 1;
+
+// scrypt cost for new backups; the parameters are stored in the file so
+// they can be raised later without breaking old backups
+const SCRYPT_N: u64 = 1 << 15;
+const SCRYPT_R: u32 = 8;
+const SCRYPT_P: u32 = 1;
+const SALT_SIZE: usize = 32;
+
+/// Self-contained encrypted backup, protected by its own passphrase so it
+/// can be restored on a fresh install without the master password
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EncryptedBackup {
+    pub format: String,
+    pub version: u32,
+    pub kdf: BackupKdf,
+    /// Base64 of nonce || AES-256-GCM ciphertext of the entries as JSON
+    pub data: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BackupKdf {
+    pub n: u64,
+    pub r: u32,
+    pub p: u32,
+    pub salt: String,
+}
+
+pub fn encrypt_backup(apps: &[OtpApp], passphrase: &str) -> Result<String> {
+    encrypt_backup_with(apps, passphrase, SCRYPT_N)
+}
+
+fn encrypt_backup_with(apps: &[OtpApp], passphrase: &str, n: u64) -> Result<String> {
+    if passphrase.is_empty() {
+        return Err(AppError::Encryption("A passphrase is required for encrypted backups".to_string()));
+    }
+
+    let salt: [u8; SALT_SIZE] = rand::random();
+    let key = derive_scrypt_key(passphrase.as_bytes(), &salt, n, SCRYPT_R, SCRYPT_P)?;
+
+    let backup = EncryptedBackup {
+        format: BACKUP_FORMAT.to_string(),
+        version: BACKUP_VERSION,
+        kdf: BackupKdf {
+            n,
+            r: SCRYPT_R,
+            p: SCRYPT_P,
+            salt: hex::encode(salt),
+        },
+        data: encrypt_data(&Zeroizing::new(serde_json::to_string(apps)?), &key)?,
+    };
+
+    Ok(serde_json::to_string_pretty(&backup)?)
+}
+
+pub fn decrypt_backup(content: &str, passphrase: &str) -> Result<Vec<OtpApp>> {
+    let backup: EncryptedBackup = serde_json::from_str(content)
+        .map_err(|e| AppError::Serialization(format!("Invalid backup file format: {}", e)))?;
+
+    if backup.format != BACKUP_FORMAT {
+        return Err(AppError::Serialization("Not a Plaxo OTP backup file".to_string()));
+    }
+
+    if backup.version > BACKUP_VERSION {
+        return Err(AppError::Serialization(format!("Unsupported backup version: {}", backup.version)));
+    }
+
+    let salt = hex::decode(&backup.kdf.salt)
+        .map_err(|_| AppError::Encryption("Invalid salt in backup file".to_string()))?;
+    // Rejects parameters a crafted file could use to exhaust memory
+    let key = derive_scrypt_key(passphrase.as_bytes(), &salt, backup.kdf.n, backup.kdf.r, backup.kdf.p)?;
+
+    let json = decrypt_data(&backup.data, &key)
+        .map(Zeroizing::new)
+        .map_err(|_| AppError::Encryption("Invalid passphrase for this backup".to_string()))?;
+
+    Ok(serde_json::from_str(&json)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backup_round_trip() {
+        let mut app = OtpApp::new("GitHub".to_string(), "JBSWY3DPEHPK3PXP".to_string());
+        app.group = Some("Work".to_string());
+
+        let content = encrypt_backup_with(std::slice::from_ref(&app), "correct horse", 1024).unwrap();
+        assert!(!content.contains("JBSWY3DPEHPK3PXP"));
+
+        assert!(decrypt_backup(&content, "wrong").is_err());
+
+        let restored = decrypt_backup(&content, "correct horse").unwrap();
+        assert_eq!(restored.len(), 1);
+        assert_eq!(restored[0].id, app.id);
+        assert_eq!(restored[0].secret, app.secret);
+        assert_eq!(restored[0].group, app.group);
+    }
+
+    #[test]
+    fn test_excessive_scrypt_parameters_are_rejected() {
+        let content = encrypt_backup_with(&[], "pw", 1024).unwrap();
+        let mut backup: serde_json::Value = serde_json::from_str(&content).unwrap();
+
+        backup["kdf"]["n"] = (1u64 << 40).into();
+        assert!(decrypt_backup(&backup.to_string(), "pw").is_err());
+
+        backup["kdf"]["n"] = 1024.into();
+        backup["kdf"]["r"] = (1u32 << 24).into();
+        assert!(decrypt_backup(&backup.to_string(), "pw").is_err());
+    }
+}
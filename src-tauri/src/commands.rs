@@ -1,71 +1,439 @@
 use std::io::prelude::*;
 use std::net::TcpListener;
+use std::thread;
+use std::time::Duration;
 
 use auto_launch::AutoLaunchBuilder;
-use tauri::{AppHandle, ClipboardManager, Runtime};
+use tauri::{AppHandle, ClipboardManager, Manager, Runtime};
 
-use crate::crypto::derive_key;
+use zeroize::Zeroizing;
+
+use crate::crypto::{generate_recovery_key, normalize_recovery_key, Kdf, Keyfile, SlotKind, VaultKey};
+use crate::export;
 use crate::google_drive::GoogleDriveClient;
+use crate::import::{self, ParsedImport};
+use crate::lock;
 use crate::otp::OtpGenerator;
-use crate::qr::QrCodeReader;
+use crate::migration::encode_migration_uris;
+use crate::otpauth::build_otpauth_uri;
+use crate::qr::{QrCodeReader, QrCodeWriter};
+use crate::rotation::{self, BackupPolicy};
+use crate::settings::{Settings, SETTINGS_CHANGED_EVENT};
 use crate::state::AppState;
 use crate::storage::Storage;
 use crate::sync::SyncManager;
-use crate::types::{OtpApp, AppError, Result};
+use crate::throttle::{self, AttemptLog};
+use crate::tray::update_tray_menu;
+use crate::types::{OtpApp, OtpParams, OtpType, AppError, BackupInfo, DuplicatePolicy, ExportFormat, ImportPreview, GoogleAuthStatus, ImportReport, IntegrityReport, QrCodeImage, QrData, QrImageFormat, Result};
 
 #[tauri::command]
-pub fn has_master_password(state: tauri::State<AppState>) -> bool {
-    if state.has_master_password() {
+pub fn has_master_password(state: tauri::State<AppState>, storage: tauri::State<Storage>) -> bool {
+    if state.is_unlocked() {
         return true;
     }
     
     // Check if encrypted data file exists
-    let storage = Storage::new();
     storage.has_apps_file()
 }
 
+/// Where the vault files live, as chosen at startup
 #[tauri::command]
-pub fn verify_master_password(password: String, state: tauri::State<AppState>) -> bool {
-    let storage = Storage::new();
+pub fn get_data_dir(storage: tauri::State<Storage>) -> String {
+    storage.location()
+}
+
+/// Unlocks the vault, or sets the master password on first run. Failed
+/// attempts are counted on disk and, past `throttle::FREE_ATTEMPTS`, answered
+/// with `TooManyAttempts` until the backoff has elapsed.
+#[tauri::command]
+pub fn verify_master_password(
+    password: String,
+    keyfile: Option<Vec<u8>>,
+    state: tauri::State<AppState>,
+    storage: tauri::State<Storage>,
+) -> Result<bool> {
+    // Only the derived key is kept, the password is wiped when this returns
+    let password = Zeroizing::new(password);
+    let keyfile = read_keyfile(keyfile)?;
     let file_exists = storage.has_apps_file();
     
-    if !state.has_master_password() && !file_exists {
+    if !state.is_unlocked() && !file_exists {
         // First time - set password
-        state.set_master_password(password.clone());
-        let key = derive_key(&password);
+        let key = match VaultKey::generate(&password, keyfile.as_ref()) {
+            Ok(key) => key,
+            Err(e) => {
+                tracing::error!("Failed to derive key: {}", e);
+                return Ok(false);
+            }
+        };
         state.set_encryption_key(key);
+        state.touch();
         tracing::info!("First time - password set");
-        true
+        Ok(true)
     } else if file_exists {
-        // File exists - verify password and load data
-        let key = derive_key(&password);
+        let (mut attempts, now) = begin_attempt(&storage)?;
         
-        match storage.load_apps(&key) {
-            Ok(loaded_apps) => {
-                state.set_master_password(password);
+        // File exists - verify password and load data
+        match storage.load_apps(&password, keyfile.as_ref()) {
+            Ok((loaded_apps, mut key)) => {
+                succeed_attempt(&storage, &mut attempts);
+                
+                if key.needs_upgrade() {
+                    // Move vaults from older versions to key slots and the current KDF cost,
+                    // without binding a keyfile the vault did not ask for
+                    let keyfile = keyfile.as_ref().filter(|_| key.requires_keyfile());
+                    match rekey_password(&storage, &key, &password, keyfile) {
+                        Ok(new_key) => key = new_key,
+                        Err(e) => tracing::warn!("Could not upgrade vault key: {}", e),
+                    }
+                }
+                
                 state.set_encryption_key(key);
                 state.set_apps(loaded_apps);
+                state.touch();
                 tracing::info!("Data loaded: {} apps", state.get_apps().len());
-                true
+                Ok(true)
             }
+            Err(AppError::InvalidMasterPassword) => {
+                tracing::warn!("Invalid master password");
+                fail_attempt(&storage, &state, &mut attempts, now)
+            }
+            Err(AppError::KeyfileRequired) => Err(AppError::KeyfileRequired),
             Err(e) => {
                 tracing::error!("Failed to load data: {}", e);
-                false
+                Ok(false)
             }
         }
     } else {
         // Verify password in current session
-        false // This case shouldn't happen in the current flow
+        Ok(false) // This case shouldn't happen in the current flow
+    }
+}
+
+/// Unlocks the vault with its recovery key and sets a new master password.
+/// The new password slot only needs a keyfile if `keyfile` is given, so a
+/// lost keyfile is recovered from too. Throttled together with
+/// `verify_master_password`.
+#[tauri::command]
+pub fn unlock_with_recovery_key(
+    recovery_key: String,
+    new_password: String,
+    keyfile: Option<Vec<u8>>,
+    state: tauri::State<AppState>,
+    storage: tauri::State<Storage>,
+) -> Result<bool> {
+    let recovery_key = Zeroizing::new(recovery_key);
+    let new_password = Zeroizing::new(new_password);
+    let keyfile = read_keyfile(keyfile)?;
+    
+    if new_password.is_empty() {
+        return Err(AppError::Encryption("New password cannot be empty".to_string()));
+    }
+    
+    if !storage.has_apps_file() {
+        return Err(AppError::NoMasterPassword);
+    }
+    
+    let (mut attempts, now) = begin_attempt(&storage)?;
+    
+    match storage.load_apps_with_recovery_key(&recovery_key) {
+        Ok((loaded_apps, key)) => {
+            succeed_attempt(&storage, &mut attempts);
+            
+            let new_key = rekey_password(&storage, &key, &new_password, keyfile.as_ref())?;
+            
+            state.set_encryption_key(new_key);
+            state.set_apps(loaded_apps);
+            state.touch();
+            tracing::info!("Vault recovered, master password replaced");
+            Ok(true)
+        }
+        Err(AppError::InvalidRecoveryKey) => {
+            tracing::warn!("Invalid recovery key");
+            fail_attempt(&storage, &state, &mut attempts, now)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Creates a recovery key for the unlocked vault, replacing any previous
+/// one. The key is only ever returned here, it cannot be shown again.
+#[tauri::command]
+pub fn create_recovery_key(state: tauri::State<AppState>, storage: tauri::State<Storage>) -> Result<String> {
+    let key = state.get_encryption_key()
+        .ok_or(AppError::NoMasterPassword)?;
+    
+    let recovery_key = generate_recovery_key();
+    let new_key = key.with_slot(SlotKind::Recovery, &normalize_recovery_key(&recovery_key), None, Kdf::generate())?;
+    
+    if storage.has_apps_file() {
+        storage.rekey(&key, &new_key)?;
+    } else {
+        storage.save_apps(&state.get_apps(), &new_key)?;
+    }
+    
+    state.set_encryption_key(new_key);
+    tracing::info!("Recovery key created");
+    Ok(recovery_key.to_string())
+}
+
+#[tauri::command]
+pub fn has_recovery_key(state: tauri::State<AppState>) -> bool {
+    state.get_encryption_key()
+        .is_some_and(|key| key.slot(SlotKind::Recovery).is_some())
+}
+
+/// Adds, replaces or removes (`new_keyfile` of `None`) the keyfile the
+/// master password needs. The current keyfile is required to change it;
+/// a lost one is handled by `unlock_with_recovery_key`.
+#[tauri::command]
+pub fn set_keyfile(
+    current_password: String,
+    current_keyfile: Option<Vec<u8>>,
+    new_keyfile: Option<Vec<u8>>,
+    state: tauri::State<AppState>,
+    storage: tauri::State<Storage>,
+) -> Result<()> {
+    let current_password = Zeroizing::new(current_password);
+    let current_keyfile = read_keyfile(current_keyfile)?;
+    let new_keyfile = read_keyfile(new_keyfile)?;
+    
+    let key = state.get_encryption_key()
+        .ok_or(AppError::NoMasterPassword)?;
+    
+    if !key.verify_password(&current_password, current_keyfile.as_ref()) {
+        return Err(if key.requires_keyfile() && current_keyfile.is_none() {
+            AppError::KeyfileRequired
+        } else {
+            AppError::InvalidMasterPassword
+        });
+    }
+    
+    let new_key = rekey_password(&storage, &key, &current_password, new_keyfile.as_ref())?;
+    state.set_encryption_key(new_key);
+    
+    tracing::info!("Keyfile {}", if new_keyfile.is_some() { "set" } else { "removed" });
+    Ok(())
+}
+
+/// Whether the vault needs a keyfile besides the master password, known
+/// from the vault header before unlocking
+#[tauri::command]
+pub fn is_keyfile_required(state: tauri::State<AppState>, storage: tauri::State<Storage>) -> bool {
+    match state.get_encryption_key() {
+        Some(key) => key.requires_keyfile(),
+        None => storage.keyfile_required(),
+    }
+}
+
+/// Replaces the password slot of `key` and rewrites the vault files under it
+fn rekey_password(storage: &Storage, key: &VaultKey, password: &str, keyfile: Option<&Keyfile>) -> Result<VaultKey> {
+    let new_key = key.with_slot(SlotKind::Password, password, keyfile, Kdf::generate())?;
+    storage.rekey(key, &new_key)?;
+    Ok(new_key)
+}
+
+/// Hashes keyfile contents sent by the UI, wiping the copy received
+fn read_keyfile(contents: Option<Vec<u8>>) -> Result<Option<Keyfile>> {
+    contents
+        .map(|contents| Keyfile::from_contents(&Zeroizing::new(contents)))
+        .transpose()
+}
+
+/// Refuses the attempt while the backoff from earlier failures runs
+fn begin_attempt(storage: &Storage) -> Result<(AttemptLog, i64)> {
+    let attempts = storage.load_attempt_log();
+    let now = chrono::Utc::now().timestamp();
+    
+    match attempts.retry_after(now) {
+        0 => Ok((attempts, now)),
+        wait => Err(AppError::TooManyAttempts { retry_after_secs: wait }),
+    }
+}
+
+fn succeed_attempt(storage: &Storage, attempts: &mut AttemptLog) {
+    if attempts.failed_attempts > 0 {
+        attempts.record_success();
+        if let Err(e) = storage.save_attempt_log(attempts) {
+            tracing::warn!("Could not reset the attempt log: {}", e);
+        }
+    }
+}
+
+/// Counts a failed attempt and erases the vault once the opt-in limit is
+/// reached. Answers `Ok(false)` or the wait now required.
+fn fail_attempt(storage: &Storage, state: &AppState, attempts: &mut AttemptLog, now: i64) -> Result<bool> {
+    attempts.record_failure(now);
+    tracing::warn!("{} consecutive failed unlock attempt(s)", attempts.failed_attempts);
+    
+    if attempts.should_wipe() {
+        tracing::warn!("Erasing the vault after {} failed attempts", attempts.failed_attempts);
+        state.clear_all();
+        storage.reset_all_data()?;
+        return Err(AppError::VaultWiped);
+    }
+    
+    storage.save_attempt_log(attempts)?;
+    
+    match attempts.retry_after(now) {
+        0 => Ok(false),
+        wait => Err(AppError::TooManyAttempts { retry_after_secs: wait }),
+    }
+}
+
+/// Opt-in: erase the vault after `attempts` consecutive wrong passwords.
+/// `None` turns it off.
+#[tauri::command]
+pub fn set_wipe_after_failures(attempts: Option<u32>, state: tauri::State<AppState>, storage: tauri::State<Storage>) -> Result<()> {
+    if !state.is_unlocked() {
+        return Err(AppError::NoMasterPassword);
+    }
+    
+    if attempts.is_some_and(|n| n < throttle::MIN_WIPE_AFTER) {
+        return Err(AppError::InvalidInput(format!("Wipe threshold must be at least {} attempts", throttle::MIN_WIPE_AFTER)));
+    }
+    
+    let mut log = storage.load_attempt_log();
+    log.wipe_after = attempts;
+    storage.save_attempt_log(&log)
+}
+
+#[tauri::command]
+pub fn get_wipe_after_failures(storage: tauri::State<Storage>) -> Option<u32> {
+    storage.load_attempt_log().wipe_after
+}
+
+/// Local backups of the vault, newest first
+#[tauri::command]
+pub fn list_backups(state: tauri::State<AppState>, storage: tauri::State<Storage>) -> Result<Vec<BackupInfo>> {
+    let key = state.get_encryption_key()
+        .ok_or(AppError::NoMasterPassword)?;
+    
+    storage.list_backups(&key)
+}
+
+/// Replaces the vault entries with those of backup `id`. The entries being
+/// replaced become a backup themselves, so a restore can be undone.
+#[tauri::command]
+pub fn restore_backup(id: String, state: tauri::State<AppState>, storage: tauri::State<Storage>) -> Result<usize> {
+    let key = state.get_encryption_key()
+        .ok_or(AppError::NoMasterPassword)?;
+    
+    let apps = storage.load_backup(&id, &key)?;
+    let count = apps.len();
+    
+    storage.save_apps(&apps, &key)?;
+    state.set_apps(apps);
+    
+    tracing::info!("Restored {} app(s) from backup {}", count, id);
+    Ok(count)
+}
+
+/// Reports which of the vault file, an interrupted save and the backups
+/// can be read with the current key
+#[tauri::command]
+pub fn check_vault_integrity(state: tauri::State<AppState>, storage: tauri::State<Storage>) -> Result<IntegrityReport> {
+    let key = state.get_encryption_key()
+        .ok_or(AppError::NoMasterPassword)?;
+    
+    storage.check_integrity(&key)
+}
+
+/// Rewrites the vault file from `id`, normally the `repair_from` of
+/// `check_vault_integrity`
+#[tauri::command]
+pub fn repair_vault(id: String, state: tauri::State<AppState>, storage: tauri::State<Storage>) -> Result<usize> {
+    let key = state.get_encryption_key()
+        .ok_or(AppError::NoMasterPassword)?;
+    
+    let apps = storage.repair_from(&id, &key)?;
+    let count = apps.len();
+    state.set_apps(apps);
+    
+    Ok(count)
+}
+
+#[tauri::command]
+pub fn get_backup_policy(storage: tauri::State<Storage>) -> BackupPolicy {
+    storage.load_backup_policy()
+}
+
+#[tauri::command]
+pub fn set_backup_policy(policy: BackupPolicy, state: tauri::State<AppState>, storage: tauri::State<Storage>) -> Result<()> {
+    if !state.is_unlocked() {
+        return Err(AppError::NoMasterPassword);
+    }
+    
+    if !(1..=rotation::MAX_KEEP).contains(&policy.keep) {
+        return Err(AppError::InvalidInput(format!("Backups kept must be between 1 and {}", rotation::MAX_KEEP)));
+    }
+    
+    storage.save_backup_policy(&policy)
+}
+
+#[tauri::command]
+pub fn lock_vault<R: Runtime>(app: AppHandle<R>) -> Result<()> {
+    lock::lock_and_notify(&app);
+    Ok(())
+}
+
+/// Called by the frontend on user interaction to restart the idle timer
+#[tauri::command]
+pub fn record_activity(state: tauri::State<AppState>) {
+    state.touch();
+}
+
+#[tauri::command]
+pub fn get_settings(state: tauri::State<AppState>) -> Settings {
+    state.get_settings()
+}
+
+/// Saves and applies `settings`, then updates the tray and tells the
+/// frontend with `SETTINGS_CHANGED_EVENT`. Only while the vault is unlocked,
+/// so nobody at a locked screen can turn auto-lock off.
+#[tauri::command]
+pub fn set_settings<R: Runtime>(
+    settings: Settings,
+    app: AppHandle<R>,
+    state: tauri::State<AppState>,
+    storage: tauri::State<Storage>,
+) -> Result<()> {
+    if !state.is_unlocked() {
+        return Err(AppError::NoMasterPassword);
+    }
+    
+    settings.validate()?;
+    storage.save_settings(&settings)?;
+    
+    state.set_settings(settings.clone());
+    // A shorter auto-lock timeout counts from now, not from the last activity
+    state.touch();
+    
+    update_tray_menu(&app, get_autostart_status().unwrap_or(false));
+    if let Err(e) = app.emit_all(SETTINGS_CHANGED_EVENT, settings) {
+        tracing::error!("Failed to notify frontend of new settings: {}", e);
     }
+    
+    tracing::info!("Settings saved");
+    Ok(())
 }
 
+/// The entries in the order chosen in the settings
 #[tauri::command]
 pub fn get_apps(state: tauri::State<AppState>) -> Vec<OtpApp> {
-    state.get_apps()
+    let mut apps = state.get_apps();
+    state.get_settings().sort_order.sort(&mut apps);
+    apps
 }
 
 #[tauri::command]
-pub fn add_app(name: String, secret: String, state: tauri::State<AppState>) -> Result<()> {
+pub fn add_app(
+    name: String,
+    secret: String,
+    params: Option<OtpParams>,
+    state: tauri::State<AppState>,
+    storage: tauri::State<Storage>,
+) -> Result<()> {
     tracing::info!("Adding app: {}", name);
     
     if name.trim().is_empty() {
@@ -76,21 +444,20 @@ pub fn add_app(name: String, secret: String, state: tauri::State<AppState>) -> R
         return Err(AppError::InvalidSecret("Secret cannot be empty".to_string()));
     }
     
+    let params = params.unwrap_or_default();
+    
     // Validate secret first
     let otp_generator = OtpGenerator::new();
     otp_generator.validate_secret(&secret)?;
+    otp_generator.validate_entry_params(&params)?;
+    
+    let app = OtpApp::with_params(name.trim().to_string(), secret.trim().to_uppercase(), params);
+    let id = app.id.clone();
     
     // Test code generation
-    let test_code = otp_generator.generate_code(&secret)?;
+    let test_code = otp_generator.generate_for_app(&app)?;
     tracing::info!("Test code generated: {}", test_code);
     
-    let id = uuid::Uuid::new_v4().to_string();
-    let app = OtpApp { 
-        id: id.clone(), 
-        name: name.trim().to_string(), 
-        secret: secret.trim().to_uppercase() 
-    };
-    
     state.add_app(app);
     tracing::info!("App added with ID: {}, Total apps: {}", id, state.get_apps().len());
     
@@ -98,7 +465,6 @@ pub fn add_app(name: String, secret: String, state: tauri::State<AppState>) -> R
     let key = state.get_encryption_key()
         .ok_or(AppError::NoMasterPassword)?;
     
-    let storage = Storage::new();
     storage.save_apps(&state.get_apps(), &key)?;
     tracing::info!("Apps saved to disk successfully");
     
@@ -106,7 +472,7 @@ pub fn add_app(name: String, secret: String, state: tauri::State<AppState>) -> R
 }
 
 #[tauri::command]
-pub fn edit_app_name(id: String, new_name: String, state: tauri::State<AppState>) -> Result<()> {
+pub fn edit_app_name(id: String, new_name: String, state: tauri::State<AppState>, storage: tauri::State<Storage>) -> Result<()> {
     if !state.update_app_name(&id, new_name) {
         return Err(AppError::AppNotFound);
     }
@@ -115,14 +481,13 @@ pub fn edit_app_name(id: String, new_name: String, state: tauri::State<AppState>
     let key = state.get_encryption_key()
         .ok_or(AppError::NoMasterPassword)?;
     
-    let storage = Storage::new();
     storage.save_apps(&state.get_apps(), &key)?;
     
     Ok(())
 }
 
 #[tauri::command]
-pub fn delete_app(app_id: String, state: tauri::State<AppState>) -> Result<()> {
+pub fn delete_app(app_id: String, state: tauri::State<AppState>, storage: tauri::State<Storage>) -> Result<()> {
     tracing::info!("Deleting app with ID: {}", app_id);
     
     if !state.remove_app(&app_id) {
@@ -135,7 +500,6 @@ pub fn delete_app(app_id: String, state: tauri::State<AppState>) -> Result<()> {
     let key = state.get_encryption_key()
         .ok_or(AppError::NoMasterPassword)?;
     
-    let storage = Storage::new();
     storage.save_apps(&state.get_apps(), &key)?;
     
     Ok(())
@@ -147,68 +511,242 @@ pub fn generate_otp(app_id: String, state: tauri::State<AppState>) -> Result<Str
         .ok_or(AppError::AppNotFound)?;
     
     let otp_generator = OtpGenerator::new();
-    otp_generator.generate_code(&app.secret)
+    otp_generator.generate_for_app(&app)
 }
 
 #[tauri::command]
-pub fn copy_to_clipboard<R: Runtime>(app: AppHandle<R>, text: String) -> Result<()> {
-    app.clipboard_manager()
-        .write_text(text)
-        .map_err(|e| AppError::Io(e.to_string()))
+pub fn next_hotp_code(app_id: String, state: tauri::State<AppState>, storage: tauri::State<Storage>) -> Result<String> {
+    let app = state.get_app_by_id(&app_id)
+        .ok_or(AppError::AppNotFound)?;
+    
+    if app.otp_type != OtpType::Hotp {
+        return Err(AppError::InvalidSecret("App is not counter-based (HOTP)".to_string()));
+    }
+    
+    let key = state.get_encryption_key()
+        .ok_or(AppError::NoMasterPassword)?;
+    
+    let counter = state.advance_counter(&app_id, |apps| storage.save_apps(apps, &key))?;
+    tracing::info!("HOTP code for {} generated at counter {}", app_id, counter);
+    
+    let otp_generator = OtpGenerator::new();
+    otp_generator.generate_hotp(&app.secret, app.algorithm, app.digits, counter)
 }
 
 #[tauri::command]
-pub fn import_2fas_file(file_content: String, state: tauri::State<AppState>) -> Result<usize> {
-    use serde_json::Value;
+pub fn resync_hotp_counter(app_id: String, code: String, state: tauri::State<AppState>, storage: tauri::State<Storage>) -> Result<u64> {
+    let app = state.get_app_by_id(&app_id)
+        .ok_or(AppError::AppNotFound)?;
     
-    tracing::info!("Starting 2FAS import...");
+    if app.otp_type != OtpType::Hotp {
+        return Err(AppError::InvalidSecret("App is not counter-based (HOTP)".to_string()));
+    }
+    
+    let otp_generator = OtpGenerator::new();
+    let matched = otp_generator
+        .find_hotp_counter(&app.secret, app.algorithm, app.digits, app.counter, &code)?
+        .ok_or_else(|| AppError::InvalidSecret("Code does not match any upcoming counter value".to_string()))?;
     
-    let json: Value = serde_json::from_str(&file_content)?;
+    // The server has seen `matched`, so the next code to show is the one after it
+    let counter = matched + 1;
+    if !state.set_counter(&app_id, counter) {
+        return Err(AppError::AppNotFound);
+    }
     
-    let services = json.get("services")
-        .and_then(|s| s.as_array())
-        .ok_or_else(|| AppError::Serialization("Invalid 2FAS file format".to_string()))?;
+    let key = state.get_encryption_key()
+        .ok_or(AppError::NoMasterPassword)?;
     
-    let mut imported_count = 0;
-    let otp_generator = OtpGenerator::new();
+    storage.save_apps(&state.get_apps(), &key)?;
+    tracing::info!("HOTP counter for {} resynchronised to {}", app_id, counter);
     
-    for service in services {
-        if let (Some(name), Some(secret)) = (
-            service.get("name").and_then(|n| n.as_str()),
-            service.get("secret").and_then(|s| s.as_str())
-        ) {
-            // Validate secret
-            if otp_generator.validate_secret(secret).is_ok() {
-                let app = OtpApp {
-                    id: uuid::Uuid::new_v4().to_string(),
-                    name: name.to_string(),
-                    secret: secret.to_string(),
-                };
-                state.add_app(app);
-                imported_count += 1;
-                tracing::info!("Imported: {}", name);
-            } else {
-                tracing::warn!("Skipped invalid secret for: {}", name);
+    Ok(counter)
+}
+
+/// Copies `text` and clears it again after the delay set in the settings
+#[tauri::command]
+pub fn copy_to_clipboard<R: Runtime>(app: AppHandle<R>, text: String, state: tauri::State<AppState>) -> Result<()> {
+    app.clipboard_manager()
+        .write_text(text.clone())
+        .map_err(|e| AppError::Io(e.to_string()))?;
+    
+    let delay = state.get_settings().clipboard_clear_seconds;
+    if delay > 0 {
+        thread::spawn(move || {
+            thread::sleep(Duration::from_secs(delay.into()));
+            
+            // Whatever was copied since is left alone
+            let mut clipboard = app.clipboard_manager();
+            if clipboard.read_text().ok().flatten().as_deref() == Some(text.as_str()) {
+                if let Err(e) = clipboard.write_text(String::new()) {
+                    tracing::warn!("Failed to clear the clipboard: {}", e);
+                }
             }
-        } else {
-            tracing::warn!("Skipped service - missing required fields: {:?}", service);
-        }
+        });
     }
     
-    tracing::info!("Total imported: {}, Total in memory: {}", imported_count, state.get_apps().len());
+    Ok(())
+}
+
+#[tauri::command]
+pub fn import_2fas_file(
+    file_content: String,
+    password: Option<String>,
+    policy: Option<DuplicatePolicy>,
+    state: tauri::State<AppState>,
+    storage: tauri::State<Storage>,
+) -> Result<ImportReport> {
+    tracing::info!("Starting 2FAS import...");
+    
+    let parsed = import::parse_2fas(&file_content, password.as_deref())?;
+    save_imported_apps(parsed, policy.unwrap_or_default(), &state, &storage)
+}
+
+#[tauri::command]
+pub fn import_aegis_file(
+    file_content: String,
+    password: Option<String>,
+    policy: Option<DuplicatePolicy>,
+    state: tauri::State<AppState>,
+    storage: tauri::State<Storage>,
+) -> Result<ImportReport> {
+    tracing::info!("Starting Aegis import...");
+    
+    let parsed = import::parse_aegis(&file_content, password.as_deref())?;
+    save_imported_apps(parsed, policy.unwrap_or_default(), &state, &storage)
+}
+
+#[tauri::command]
+pub fn import_google_migration(
+    uris: Vec<String>,
+    policy: Option<DuplicatePolicy>,
+    state: tauri::State<AppState>,
+    storage: tauri::State<Storage>,
+) -> Result<ImportReport> {
+    tracing::info!("Starting Google Authenticator import from {} QR code(s)...", uris.len());
+    
+    let parsed = import::parse_google_migration(&uris)?;
+    save_imported_apps(parsed, policy.unwrap_or_default(), &state, &storage)
+}
+
+#[tauri::command]
+pub fn import_google_migration_images(
+    images: Vec<Vec<u8>>,
+    policy: Option<DuplicatePolicy>,
+    state: tauri::State<AppState>,
+    storage: tauri::State<Storage>,
+) -> Result<ImportReport> {
+    tracing::info!("Starting Google Authenticator import from {} image(s)...", images.len());
+    
+    let parsed = import::parse_google_migration(&read_qr_images(&images)?)?;
+    save_imported_apps(parsed, policy.unwrap_or_default(), &state, &storage)
+}
+
+#[tauri::command]
+pub fn import_backup_file(
+    file_content: String,
+    passphrase: String,
+    policy: Option<DuplicatePolicy>,
+    state: tauri::State<AppState>,
+    storage: tauri::State<Storage>,
+) -> Result<ImportReport> {
+    tracing::info!("Starting encrypted backup import...");
+    
+    let parsed = import::parse_backup(&file_content, &passphrase)?;
+    save_imported_apps(parsed, policy.unwrap_or_default(), &state, &storage)
+}
+
+#[tauri::command]
+pub fn preview_2fas_file(
+    file_content: String,
+    password: Option<String>,
+    state: tauri::State<AppState>,
+) -> Result<ImportPreview> {
+    let parsed = import::parse_2fas(&file_content, password.as_deref())?;
+    Ok(import::preview(parsed, &state.get_apps()))
+}
+
+#[tauri::command]
+pub fn preview_aegis_file(
+    file_content: String,
+    password: Option<String>,
+    state: tauri::State<AppState>,
+) -> Result<ImportPreview> {
+    let parsed = import::parse_aegis(&file_content, password.as_deref())?;
+    Ok(import::preview(parsed, &state.get_apps()))
+}
+
+#[tauri::command]
+pub fn preview_google_migration(uris: Vec<String>, state: tauri::State<AppState>) -> Result<ImportPreview> {
+    let parsed = import::parse_google_migration(&uris)?;
+    Ok(import::preview(parsed, &state.get_apps()))
+}
+
+/// Previews an export from screenshots of its QR codes, in any order
+#[tauri::command]
+pub fn preview_google_migration_images(images: Vec<Vec<u8>>, state: tauri::State<AppState>) -> Result<ImportPreview> {
+    let parsed = import::parse_google_migration(&read_qr_images(&images)?)?;
+    Ok(import::preview(parsed, &state.get_apps()))
+}
+
+fn read_qr_images(images: &[Vec<u8>]) -> Result<Vec<String>> {
+    let qr_reader = QrCodeReader::new();
+    images
+        .iter()
+        .map(|image| qr_reader.decode_content_from_image(image))
+        .collect()
+}
+
+#[tauri::command]
+pub fn preview_backup_file(
+    file_content: String,
+    passphrase: String,
+    state: tauri::State<AppState>,
+) -> Result<ImportPreview> {
+    let parsed = import::parse_backup(&file_content, &passphrase)?;
+    Ok(import::preview(parsed, &state.get_apps()))
+}
+
+/// Stores the entries the user picked from a preview. They are validated
+/// again here, the preview is only advisory.
+#[tauri::command]
+pub fn commit_import(
+    apps: Vec<OtpApp>,
+    policy: Option<DuplicatePolicy>,
+    state: tauri::State<AppState>,
+    storage: tauri::State<Storage>,
+) -> Result<ImportReport> {
+    tracing::info!("Committing {} previewed app(s)...", apps.len());
+    
+    let parsed = ParsedImport { apps, skipped: Vec::new() };
+    save_imported_apps(parsed, policy.unwrap_or_default(), &state, &storage)
+}
+
+fn save_imported_apps(parsed: ParsedImport, policy: DuplicatePolicy, state: &AppState, storage: &Storage) -> Result<ImportReport> {
+    let mut apps = state.get_apps();
+    let report = import::merge_apps(&mut apps, parsed, policy);
+    
+    tracing::info!(
+        "Added: {}, updated: {}, duplicates: {}, invalid: {}, Total in memory: {}",
+        report.added.len(),
+        report.updated.len(),
+        report.duplicates.len(),
+        report.invalid.len(),
+        apps.len()
+    );
+    
+    state.set_apps(apps);
     
     // Save encrypted data
     let key = state.get_encryption_key()
         .ok_or(AppError::NoMasterPassword)?;
     
-    let storage = Storage::new();
     storage.save_apps(&state.get_apps(), &key)?;
     
-    Ok(imported_count)
+    Ok(report)
 }
 
 #[tauri::command]
-pub fn decode_qr_from_image(image_data: Vec<u8>) -> Result<crate::types::QrData> {
+pub fn decode_qr_from_image(image_data: Vec<u8>) -> Result<QrData> {
     tracing::info!("Decoding QR from image, size: {} bytes", image_data.len());
     
     if image_data.is_empty() {
@@ -218,12 +756,12 @@ pub fn decode_qr_from_image(image_data: Vec<u8>) -> Result<crate::types::QrData>
     let qr_reader = QrCodeReader::new();
     let result = qr_reader.decode_from_image(&image_data)?;
     
-    tracing::info!("QR decoded successfully: {} - {}", result.name, result.secret);
+    tracing::info!("QR decoded successfully: {}", result.name);
     Ok(result)
 }
 
 #[tauri::command]
-pub fn decode_qr_from_clipboard() -> Result<crate::types::QrData> {
+pub fn decode_qr_from_clipboard() -> Result<QrData> {
     use arboard::Clipboard;
     
     tracing::info!("Reading image from clipboard...");
@@ -248,10 +786,96 @@ pub fn decode_qr_from_clipboard() -> Result<crate::types::QrData> {
     let qr_reader = QrCodeReader::new();
     let result = qr_reader.decode_from_image(&png_bytes)?;
     
-    tracing::info!("QR decoded successfully from clipboard: {} - {}", result.name, result.secret);
+    tracing::info!("QR decoded successfully from clipboard: {}", result.name);
     Ok(result)
 }
 
+/// Writes every entry to `path` in `format`. All formats except the
+/// encrypted backup contain the secrets in plaintext.
+#[tauri::command]
+pub fn export_vault(
+    format: ExportFormat,
+    path: String,
+    passphrase: Option<String>,
+    state: tauri::State<AppState>,
+) -> Result<usize> {
+    if !state.is_unlocked() {
+        return Err(AppError::NoMasterPassword);
+    }
+    
+    let apps = state.get_apps();
+    tracing::info!("Exporting {} apps as {:?}", apps.len(), format);
+    
+    let content = Zeroizing::new(export::export_apps(&apps, format, passphrase.as_deref())?);
+    write_private(&path, content.as_bytes())?;
+    
+    Ok(apps.len())
+}
+
+/// Writes a file only its owner can read, since exports may hold secrets
+/// in plaintext
+fn write_private(path: &str, content: &[u8]) -> Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    
+    let mut file = options.open(path)?;
+    
+    // The mode only applies to new files, an existing one is narrowed too
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(std::fs::Permissions::from_mode(0o600))?;
+    }
+    
+    file.write_all(content)?;
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_otpauth_uri(app_id: String, state: tauri::State<AppState>) -> Result<String> {
+    let app = state.get_app_by_id(&app_id)
+        .ok_or(AppError::AppNotFound)?;
+    
+    Ok(build_otpauth_uri(&app))
+}
+
+#[tauri::command]
+pub fn generate_qr_code(app_id: String, format: QrImageFormat, state: tauri::State<AppState>) -> Result<QrCodeImage> {
+    let app = state.get_app_by_id(&app_id)
+        .ok_or(AppError::AppNotFound)?;
+    
+    tracing::info!("Generating QR code for {}", app.name);
+    
+    let qr_writer = QrCodeWriter::new();
+    qr_writer.render(&build_otpauth_uri(&app), format)
+}
+
+#[tauri::command]
+pub fn export_migration_qr_codes(
+    app_ids: Vec<String>,
+    format: QrImageFormat,
+    state: tauri::State<AppState>,
+) -> Result<Vec<QrCodeImage>> {
+    let apps = app_ids
+        .iter()
+        .map(|id| state.get_app_by_id(id).ok_or(AppError::AppNotFound))
+        .collect::<Result<Vec<OtpApp>>>()?;
+    
+    let uris = encode_migration_uris(&apps)?;
+    tracing::info!("Exporting {} apps as {} migration QR code(s)", apps.len(), uris.len());
+    
+    let qr_writer = QrCodeWriter::new();
+    uris.iter()
+        .map(|uri| qr_writer.render(uri, format))
+        .collect()
+}
+
 #[tauri::command]
 pub fn set_autostart(enabled: bool) -> Result<()> {
     let exe_path = std::env::current_exe()
@@ -296,14 +920,48 @@ pub fn get_autostart_status() -> Result<bool> {
 }
 
 #[tauri::command]
-pub fn reset_master_password(state: tauri::State<AppState>) -> Result<()> {
+pub fn change_master_password(
+    current_password: String,
+    new_password: String,
+    keyfile: Option<Vec<u8>>,
+    state: tauri::State<AppState>,
+    storage: tauri::State<Storage>,
+) -> Result<()> {
+    let current_password = Zeroizing::new(current_password);
+    let new_password = Zeroizing::new(new_password);
+    let keyfile = read_keyfile(keyfile)?;
+    let current_key = state.get_encryption_key()
+        .ok_or(AppError::NoMasterPassword)?;
+    
+    if !current_key.verify_password(&current_password, keyfile.as_ref()) {
+        return Err(AppError::InvalidMasterPassword);
+    }
+    
+    if new_password.is_empty() {
+        return Err(AppError::Encryption("New password cannot be empty".to_string()));
+    }
+    
+    tracing::info!("Changing master password...");
+    
+    // The data key, recovery slot and keyfile requirement stay, only the
+    // password changes
+    let keyfile = keyfile.as_ref().filter(|_| current_key.requires_keyfile());
+    let new_key = rekey_password(&storage, &current_key, &new_password, keyfile)?;
+    
+    state.set_encryption_key(new_key);
+    
+    tracing::info!("Master password changed");
+    Ok(())
+}
+
+#[tauri::command]
+pub fn reset_master_password(state: tauri::State<AppState>, storage: tauri::State<Storage>) -> Result<()> {
     tracing::info!("Resetting master password and data...");
     
     // Clear state in memory
     state.clear_all();
     
     // Remove files from disk
-    let storage = Storage::new();
     storage.reset_all_data()?;
     
     tracing::info!("Reset completed successfully");
@@ -318,7 +976,7 @@ pub async fn google_drive_auth_url() -> String {
 }
 
 #[tauri::command]
-pub async fn google_drive_auth_flow(state: tauri::State<'_, AppState>) -> Result<()> {
+pub async fn google_drive_auth_flow(state: tauri::State<'_, AppState>, storage: tauri::State<'_, Storage>) -> Result<()> {
     let client = GoogleDriveClient::new();
     let auth_url = client.get_auth_url();
     
@@ -380,7 +1038,7 @@ pub async fn google_drive_auth_flow(state: tauri::State<'_, AppState>) -> Result
                             let key = state.get_encryption_key()
                                 .ok_or(AppError::NoMasterPassword)?;
                             
-                            let sync_manager = SyncManager::new();
+                            let sync_manager = SyncManager::new(storage.inner().clone());
                             sync_manager.save_google_auth(&auth, &key).await?;
                             
                             state.set_google_auth(Some(auth));
@@ -402,76 +1060,100 @@ pub async fn google_drive_auth_flow(state: tauri::State<'_, AppState>) -> Result
 }
 
 #[tauri::command]
-pub async fn sync_with_google_drive(state: tauri::State<'_, AppState>) -> Result<()> {
+pub async fn sync_with_google_drive(state: tauri::State<'_, AppState>, storage: tauri::State<'_, Storage>) -> Result<()> {
     let apps = state.get_apps();
     let key = state.get_encryption_key()
         .ok_or(AppError::NoMasterPassword)?;
     let auth = state.get_google_auth()
         .ok_or_else(|| AppError::GoogleDrive("Google Drive not authenticated".to_string()))?;
     
-    let sync_manager = SyncManager::new();
+    let sync_manager = SyncManager::new(storage.inner().clone());
     sync_manager.sync_to_google_drive(&apps, &key, &auth).await?;
     Ok(())
 }
 
+/// `password`, and `keyfile` if that vault needs one, are only used when the
+/// cloud copy was written by another install with its own data key.
 #[tauri::command]
-pub async fn restore_from_google_drive(state: tauri::State<'_, AppState>) -> Result<usize> {
+pub async fn restore_from_google_drive(
+    password: Option<String>,
+    keyfile: Option<Vec<u8>>,
+    state: tauri::State<'_, AppState>,
+    storage: tauri::State<'_, Storage>,
+) -> Result<usize> {
+    let password = password.map(Zeroizing::new);
+    let keyfile = read_keyfile(keyfile)?;
     let key = state.get_encryption_key()
         .ok_or(AppError::NoMasterPassword)?;
     let auth = state.get_google_auth()
         .ok_or_else(|| AppError::GoogleDrive("Google Drive not authenticated".to_string()))?;
     
-    let sync_manager = SyncManager::new();
-    let cloud_apps = sync_manager.sync_from_google_drive(&key, &auth).await?;
+    let sync_manager = SyncManager::new(storage.inner().clone());
+    let cloud_apps = sync_manager.sync_from_google_drive(&key, password.as_deref().map(String::as_str), keyfile.as_ref(), &auth).await?;
     let count = cloud_apps.len();
     
     state.set_apps(cloud_apps);
     
     // Save locally too
-    let storage = Storage::new();
     storage.save_apps(&state.get_apps(), &key)?;
     
     Ok(count)
 }
 
+/// Connects to Google Drive with the saved token and merges the cloud copy.
+/// A copy written by another install needs its `password`, and `keyfile`
+/// if that vault has one; without them `PasswordRequired` is returned and
+/// the frontend asks for them and calls again.
 #[tauri::command]
-pub async fn check_google_auth(state: tauri::State<'_, AppState>) -> Result<bool> {
+pub async fn check_google_auth(
+    password: Option<String>,
+    keyfile: Option<Vec<u8>>,
+    state: tauri::State<'_, AppState>,
+    storage: tauri::State<'_, Storage>,
+) -> Result<GoogleAuthStatus> {
     // Check if already syncing
     if state.is_syncing() {
         tracing::info!("Sync already in progress, skipping...");
-        return Ok(false);
+        return Ok(GoogleAuthStatus::Disconnected);
     }
     
+    let password = password.map(Zeroizing::new);
+    let keyfile = read_keyfile(keyfile)?;
+    
     state.set_syncing(true);
     
     let key = state.get_encryption_key()
         .ok_or(AppError::NoMasterPassword)?;
     
     let result = {
-        let sync_manager = SyncManager::new();
+        let sync_manager = SyncManager::new(storage.inner().clone());
         match sync_manager.load_google_auth(&key).await {
             Ok(auth) => {
                 state.set_google_auth(Some(auth.clone()));
                 
                 // Initial sync - check if there's data in the cloud
                 tracing::info!("Checking cloud data...");
-                match sync_manager.sync_from_google_drive(&key, &auth).await {
+                let cloud = sync_manager
+                    .sync_from_google_drive(&key, password.as_deref().map(String::as_str), keyfile.as_ref(), &auth)
+                    .await;
+                let status = match cloud {
                     Ok(cloud_apps) => {
                         if !cloud_apps.is_empty() {
                             tracing::info!("Found {} apps in cloud, syncing...", cloud_apps.len());
                             let mut current_apps = state.get_apps();
                             
                             // Merge: keep local apps and add new ones from cloud
-                            for cloud_app in cloud_apps {
-                                if !current_apps.iter().any(|local_app| local_app.id == cloud_app.id) {
-                                    current_apps.push(cloud_app);
-                                }
-                            }
+                            let cloud_apps = ParsedImport { apps: cloud_apps, skipped: Vec::new() };
+                            let report = import::merge_apps(&mut current_apps, cloud_apps, DuplicatePolicy::Skip);
+                            tracing::info!(
+                                "Added {} apps from cloud, {} already present",
+                                report.added.len(),
+                                report.duplicates.len()
+                            );
                             
                             state.set_apps(current_apps);
                             
                             // Save locally
-                            let storage = Storage::new();
                             storage.save_apps(&state.get_apps(), &key)?;
                             tracing::info!("Initial sync completed!");
                         } else {
@@ -481,16 +1163,27 @@ pub async fn check_google_auth(state: tauri::State<'_, AppState>) -> Result<bool
                                 sync_manager.sync_to_google_drive(&apps, &key, &auth).await?;
                             }
                         }
+                        GoogleAuthStatus::Connected
+                    }
+                    Err(AppError::CloudPasswordRequired) => {
+                        tracing::info!("Cloud copy comes from another install, asking for its password");
+                        GoogleAuthStatus::PasswordRequired
+                    }
+                    // The password given for it is wrong, let the user try again
+                    Err(e @ (AppError::InvalidMasterPassword | AppError::KeyfileRequired)) => {
+                        state.set_syncing(false);
+                        return Err(e);
                     }
                     Err(e) => {
                         tracing::warn!("Error checking cloud: {}", e);
                         // Don't fail if can't access cloud
+                        GoogleAuthStatus::Connected
                     }
-                }
+                };
                 
-                Ok(true)
+                Ok(status)
             }
-            Err(_) => Ok(false)
+            Err(_) => Ok(GoogleAuthStatus::Disconnected)
         }
     };
     
@@ -501,14 +1194,179 @@ pub async fn check_google_auth(state: tauri::State<'_, AppState>) -> Result<bool
 }
 
 #[tauri::command]
-pub async fn clear_google_auth(state: tauri::State<'_, AppState>) -> Result<()> {
+pub async fn clear_google_auth(state: tauri::State<'_, AppState>, storage: tauri::State<'_, Storage>) -> Result<()> {
     // Clear from memory
     state.set_google_auth(None);
     
     // Remove file from disk
-    let storage = Storage::new();
     storage.clear_google_auth()?;
     
     tracing::info!("Google Drive authentication removed");
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use tauri::test::{mock_app, MockRuntime};
+    use tauri::{App, Manager};
+
+    use super::*;
+    use crate::otpauth::parse_otpauth_uri;
+    use crate::settings::SortOrder;
+    use crate::store::MemoryStore;
+
+    const SECRET: &str = "JBSWY3DPEHPK3PXP";
+
+    fn test_app() -> App<MockRuntime> {
+        let app = mock_app();
+        app.manage(AppState::new());
+        app.manage(Storage::new(Arc::new(MemoryStore::default())));
+        app
+    }
+
+    /// Sets `password` on a fresh vault and saves it with one entry
+    fn create_vault(app: &App<MockRuntime>, password: &str) {
+        assert!(verify_master_password(password.to_string(), None, app.state(), app.state()).unwrap());
+        add_app("GitHub".to_string(), SECRET.to_string(), None, app.state(), app.state()).unwrap();
+    }
+
+    fn unlock(app: &App<MockRuntime>, password: &str) -> Result<bool> {
+        app.state::<AppState>().lock();
+        verify_master_password(password.to_string(), None, app.state(), app.state())
+    }
+
+    #[test]
+    fn test_entries_persist_across_unlocks() {
+        let app = test_app();
+        assert!(!has_master_password(app.state(), app.state()));
+
+        create_vault(&app, "pw");
+        let id = get_apps(app.state())[0].id.clone();
+        edit_app_name(id.clone(), "GitLab".to_string(), app.state(), app.state()).unwrap();
+
+        assert!(unlock(&app, "pw").unwrap());
+        let apps = get_apps(app.state());
+        assert_eq!(apps.len(), 1);
+        assert_eq!(apps[0].name, "GitLab");
+
+        delete_app(id, app.state(), app.state()).unwrap();
+        assert!(unlock(&app, "pw").unwrap());
+        assert!(get_apps(app.state()).is_empty());
+    }
+
+    #[test]
+    fn test_wrong_password_is_throttled() {
+        let app = test_app();
+        create_vault(&app, "pw");
+
+        for _ in 1..throttle::FREE_ATTEMPTS {
+            assert!(!unlock(&app, "wrong").unwrap());
+        }
+        assert!(matches!(unlock(&app, "wrong"), Err(AppError::TooManyAttempts { .. })));
+        assert!(matches!(unlock(&app, "pw"), Err(AppError::TooManyAttempts { .. })));
+    }
+
+    #[test]
+    fn test_export_needs_unlock_and_is_private() {
+        let app = test_app();
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("export.txt").to_string_lossy().into_owned();
+
+        let locked = export_vault(ExportFormat::OtpauthUris, path.clone(), None, app.state());
+        assert!(matches!(locked, Err(AppError::NoMasterPassword)));
+
+        create_vault(&app, "pw");
+        assert_eq!(export_vault(ExportFormat::OtpauthUris, path.clone(), None, app.state()).unwrap(), 1);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+    }
+
+    #[test]
+    fn test_low_wipe_threshold_is_invalid_input() {
+        let app = test_app();
+        create_vault(&app, "pw");
+
+        let attempts = Some(throttle::MIN_WIPE_AFTER - 1);
+        assert!(matches!(set_wipe_after_failures(attempts, app.state(), app.state()), Err(AppError::InvalidInput(_))));
+        set_wipe_after_failures(Some(throttle::MIN_WIPE_AFTER), app.state(), app.state()).unwrap();
+    }
+
+    #[test]
+    fn test_change_password_and_recover() {
+        let app = test_app();
+        create_vault(&app, "pw");
+
+        change_master_password("pw".to_string(), "new".to_string(), None, app.state(), app.state()).unwrap();
+        assert!(!unlock(&app, "pw").unwrap());
+        assert!(unlock(&app, "new").unwrap());
+
+        let recovery_key = create_recovery_key(app.state(), app.state()).unwrap();
+        app.state::<AppState>().lock();
+        assert!(unlock_with_recovery_key(recovery_key, "recovered".to_string(), None, app.state(), app.state()).unwrap());
+        assert_eq!(get_apps(app.state()).len(), 1);
+        assert!(unlock(&app, "recovered").unwrap());
+    }
+
+    #[test]
+    fn test_restore_backup_brings_back_deleted_entry() {
+        let app = test_app();
+        create_vault(&app, "pw");
+        let id = get_apps(app.state())[0].id.clone();
+        delete_app(id, app.state(), app.state()).unwrap();
+
+        let backups = list_backups(app.state(), app.state()).unwrap();
+        assert_eq!(backups[0].entry_count, Some(1));
+        assert_eq!(restore_backup(backups[0].id.clone(), app.state(), app.state()).unwrap(), 1);
+
+        assert!(unlock(&app, "pw").unwrap());
+        assert_eq!(get_apps(app.state()).len(), 1);
+    }
+
+    #[test]
+    fn test_get_apps_follows_sort_order() {
+        let app = test_app();
+        create_vault(&app, "pw");
+        add_app("Amazon".to_string(), SECRET.to_string(), None, app.state(), app.state()).unwrap();
+
+        let names = |app: &App<MockRuntime>| get_apps(app.state()).into_iter().map(|entry| entry.name).collect::<Vec<_>>();
+        assert_eq!(names(&app), ["GitHub", "Amazon"]);
+
+        let settings = Settings { sort_order: SortOrder::Name, ..Settings::default() };
+        app.state::<AppState>().set_settings(settings);
+        assert_eq!(names(&app), ["Amazon", "GitHub"]);
+    }
+
+    #[test]
+    fn test_hotp_starts_at_imported_counter() {
+        let app = test_app();
+        create_vault(&app, "pw");
+
+        // RFC 4226 appendix D secret
+        let uri = "otpauth://hotp/Example:alice?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ&counter=0";
+        let qr = parse_otpauth_uri(uri).unwrap();
+        add_app(qr.name, qr.secret, Some(qr.params), app.state(), app.state()).unwrap();
+        let id = get_apps(app.state())[1].id.clone();
+
+        assert_eq!(next_hotp_code(id.clone(), app.state(), app.state()).unwrap(), "755224");
+        assert_eq!(next_hotp_code(id.clone(), app.state(), app.state()).unwrap(), "287082");
+        assert_eq!(app.state::<AppState>().get_app_by_id(&id).unwrap().counter, 2);
+    }
+
+    #[test]
+    fn test_reset_removes_the_vault() {
+        let app = test_app();
+        create_vault(&app, "pw");
+
+        reset_master_password(app.state(), app.state()).unwrap();
+        assert!(!has_master_password(app.state(), app.state()));
+        assert!(get_apps(app.state()).is_empty());
+        assert_eq!(get_data_dir(app.state()), "memory");
+    }
+}
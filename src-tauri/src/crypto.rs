@@ -1,13 +1,349 @@
-use aes_gcm::{Aes256Gcm, Key, Nonce, aead::{Aead, KeyInit}};
+use aes_gcm::{Aes256Gcm, Key, Nonce, aead::{Aead, KeyInit, Payload}};
 use base64::{Engine as _, engine::general_purpose};
 use rand::RngCore;
+use serde::{Deserialize, Serialize};
 use sha2::{Sha256, Digest};
+use zeroize::Zeroizing;
 
+use crate::secret::{SecretKey, KEY_SIZE};
 use crate::types::{AppError, Result};
 
 const SALT: &[u8] = b"plaxo-otp-salt-2024";
-const NONCE_SIZE: usize = 12;
+pub const NONCE_SIZE: usize = 12;
 
+// Argon2id cost for new vaults (memory in KiB). Vaults with lower costs are
+// re-keyed on the next unlock, so these can be raised over time.
+#[cfg(not(test))]
+const ARGON2_M_COST: u32 = 64 * 1024;
+#[cfg(not(test))]
+const ARGON2_T_COST: u32 = 3;
+// Tests run the commands end to end, so new vaults there use the cheapest cost
+#[cfg(test)]
+const ARGON2_M_COST: u32 = 1024;
+#[cfg(test)]
+const ARGON2_T_COST: u32 = 1;
+const ARGON2_P_COST: u32 = 1;
+// Costs read from vault files are attacker-controlled; above these a file
+// could make the derivation exhaust memory or run for hours
+const MAX_ARGON2_M_COST: u32 = 1024 * 1024;
+const MAX_ARGON2_T_COST: u32 = 64;
+const MAX_ARGON2_P_COST: u32 = 16;
+// The same for scrypt parameters in imported files. Memory use is 128·r·n bytes.
+const MAX_SCRYPT_N: u64 = 1 << 20;
+const MAX_SCRYPT_R: u32 = 32;
+const MAX_SCRYPT_P: u32 = 16;
+const MAX_SCRYPT_MEMORY: u64 = 1 << 30;
+const KDF_SALT_SIZE: usize = 16;
+const RECOVERY_KEY_BYTES: usize = 20;
+
+/// How the vault key is derived from the master password. Stored in clear
+/// next to the ciphertext, it is all that is needed besides the password.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "algorithm", rename_all = "lowercase")]
+pub enum Kdf {
+    /// Single SHA-256 over the password and a salt shared by every install.
+    /// Only read, files using it are migrated on unlock.
+    #[serde(rename = "sha256-static-salt")]
+    LegacySha256,
+    Argon2id {
+        m_cost: u32,
+        t_cost: u32,
+        p_cost: u32,
+        salt: String,
+    },
+}
+
+impl Kdf {
+    /// Argon2id with the current default cost and a fresh random salt
+    pub fn generate() -> Self {
+        Self::argon2id(ARGON2_M_COST, ARGON2_T_COST, ARGON2_P_COST)
+    }
+
+    pub fn argon2id(m_cost: u32, t_cost: u32, p_cost: u32) -> Self {
+        let salt: [u8; KDF_SALT_SIZE] = rand::random();
+
+        Self::Argon2id {
+            m_cost,
+            t_cost,
+            p_cost,
+            salt: hex::encode(salt),
+        }
+    }
+
+    pub fn derive(&self, password: &str) -> Result<SecretKey> {
+        self.derive_with_keyfile(password, None)
+    }
+
+    /// Rejects costs too high to derive safely, for KDFs read from a file
+    pub fn check_cost(&self) -> Result<()> {
+        match self {
+            Self::LegacySha256 => Ok(()),
+            Self::Argon2id { m_cost, t_cost, p_cost, .. } => {
+                if *m_cost > MAX_ARGON2_M_COST || *t_cost > MAX_ARGON2_T_COST || *p_cost > MAX_ARGON2_P_COST {
+                    return Err(AppError::Encryption(format!(
+                        "Key derivation cost is too high (m={}, t={}, p={})",
+                        m_cost, t_cost, p_cost
+                    )));
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Derives with the keyfile hash as Argon2's secret input, so neither
+    /// the password nor the keyfile alone is enough
+    pub fn derive_with_keyfile(&self, password: &str, keyfile: Option<&Keyfile>) -> Result<SecretKey> {
+        match self {
+            Self::LegacySha256 if keyfile.is_some() => {
+                Err(AppError::Encryption("Keyfiles require an Argon2 key".to_string()))
+            }
+            Self::LegacySha256 => Ok(SecretKey::from_bytes(&mut derive_key(password))),
+            Self::Argon2id { m_cost, t_cost, p_cost, salt } => {
+                let salt = hex::decode(salt)
+                    .map_err(|_| AppError::Encryption("Invalid key derivation salt".to_string()))?;
+                let params = argon2::Params::new(*m_cost, *t_cost, *p_cost, Some(32))
+                    .map_err(|e| AppError::Encryption(format!("Invalid Argon2 parameters: {}", e)))?;
+
+                let argon2 = match keyfile {
+                    Some(keyfile) => argon2::Argon2::new_with_secret(
+                        keyfile.0.expose(),
+                        argon2::Algorithm::Argon2id,
+                        argon2::Version::V0x13,
+                        params,
+                    )
+                    .map_err(|e| AppError::Encryption(format!("Invalid keyfile: {}", e)))?,
+                    None => argon2::Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, params),
+                };
+
+                let mut key = SecretKey::zeroed();
+                argon2
+                    .hash_password_into(password.as_bytes(), &salt, key.expose_mut())
+                    .map_err(|e| AppError::Encryption(format!("Failed to derive key: {}", e)))?;
+
+                Ok(key)
+            }
+        }
+    }
+
+    /// True when data under this KDF should be re-encrypted with `Kdf::generate`
+    pub fn needs_upgrade(&self) -> bool {
+        match self {
+            Self::LegacySha256 => true,
+            Self::Argon2id { m_cost, t_cost, p_cost, .. } => {
+                *m_cost < ARGON2_M_COST || *t_cost < ARGON2_T_COST || *p_cost < ARGON2_P_COST
+            }
+        }
+    }
+}
+
+/// What a key slot is opened with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SlotKind {
+    Password,
+    Recovery,
+}
+
+impl SlotKind {
+    // Binds the wrapped key to its slot kind, so a slot cannot be relabeled
+    fn aad(self) -> &'static [u8] {
+        match self {
+            Self::Password => b"plaxo-otp-slot-password",
+            Self::Recovery => b"plaxo-otp-slot-recovery",
+        }
+    }
+}
+
+/// SHA-256 of a keyfile's contents. Only this hash is used and the file
+/// itself is never stored, slots just record that one is needed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Keyfile(SecretKey);
+
+impl Keyfile {
+    pub fn from_contents(contents: &[u8]) -> Result<Self> {
+        if contents.is_empty() {
+            return Err(AppError::Encryption("The keyfile is empty".to_string()));
+        }
+
+        let mut hash: [u8; KEY_SIZE] = Sha256::digest(contents).into();
+        Ok(Self(SecretKey::from_bytes(&mut hash)))
+    }
+}
+
+/// The vault's data key, wrapped under a key derived from a password or a
+/// recovery key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeySlot {
+    pub kind: SlotKind,
+    pub kdf: Kdf,
+    /// Whether a keyfile was mixed into `kdf` along with the secret
+    pub keyfile: bool,
+    pub nonce: [u8; NONCE_SIZE],
+    /// The data key encrypted with AES-256-GCM, tag included
+    pub wrapped_key: Vec<u8>,
+}
+
+impl KeySlot {
+    pub fn wrap(kind: SlotKind, kdf: Kdf, secret: &str, keyfile: Option<&Keyfile>, data_key: &SecretKey) -> Result<Self> {
+        let wrapping_key = kdf.derive_with_keyfile(secret, keyfile)?;
+        let (nonce, wrapped_key) = encrypt_with_aad(wrapping_key.expose(), data_key.expose(), kind.aad())?;
+
+        Ok(Self { kind, kdf, keyfile: keyfile.is_some(), nonce, wrapped_key })
+    }
+
+    /// `keyfile` is ignored by slots that do not need one
+    pub fn unwrap(&self, secret: &str, keyfile: Option<&Keyfile>) -> Result<SecretKey> {
+        let keyfile = match (self.keyfile, keyfile) {
+            (true, None) => return Err(AppError::KeyfileRequired),
+            (true, keyfile) => keyfile,
+            (false, _) => None,
+        };
+
+        let wrapping_key = self.kdf.derive_with_keyfile(secret, keyfile)?;
+        let data_key = Zeroizing::new(decrypt_with_aad(wrapping_key.expose(), &self.nonce, &self.wrapped_key, self.kind.aad())?);
+
+        let mut bytes: [u8; KEY_SIZE] = data_key.as_slice()
+            .try_into()
+            .map_err(|_| AppError::Encryption("Invalid key slot".to_string()))?;
+        Ok(SecretKey::from_bytes(&mut bytes))
+    }
+}
+
+/// How the key that encrypts the vault data is obtained
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeyProtection {
+    /// Vaults from before key slots: the password-derived key encrypts the
+    /// data directly. Only read, these are moved to slots on unlock.
+    Direct(Kdf),
+    /// A random data key, wrapped once per way to unlock the vault
+    Slots(Vec<KeySlot>),
+}
+
+/// The key the vault data is encrypted with, and how it is unlocked
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VaultKey {
+    pub key: SecretKey,
+    pub protection: KeyProtection,
+}
+
+impl VaultKey {
+    /// Derives a key the legacy way, straight from the password
+    pub fn derive(password: &str, kdf: Kdf) -> Result<Self> {
+        Ok(Self { key: kdf.derive(password)?, protection: KeyProtection::Direct(kdf) })
+    }
+
+    /// A random data key for a new vault, with a password slot that also
+    /// needs `keyfile` when one is given
+    pub fn generate(password: &str, keyfile: Option<&Keyfile>) -> Result<Self> {
+        Self::generate_with(password, keyfile, Kdf::generate())
+    }
+
+    pub fn generate_with(password: &str, keyfile: Option<&Keyfile>, kdf: Kdf) -> Result<Self> {
+        Self::random().with_slot(SlotKind::Password, password, keyfile, kdf)
+    }
+
+    fn random() -> Self {
+        let mut bytes: [u8; KEY_SIZE] = rand::random();
+        Self { key: SecretKey::from_bytes(&mut bytes), protection: KeyProtection::Slots(Vec::new()) }
+    }
+
+    /// Opens the first slot of `kind` that `secret` unwraps
+    pub fn unlock(slots: &[KeySlot], kind: SlotKind, secret: &str, keyfile: Option<&Keyfile>) -> Result<Self> {
+        let mut missing_keyfile = false;
+
+        for slot in slots.iter().filter(|s| s.kind == kind) {
+            match slot.unwrap(secret, keyfile) {
+                Ok(key) => return Ok(Self { key, protection: KeyProtection::Slots(slots.to_vec()) }),
+                Err(AppError::KeyfileRequired) => missing_keyfile = true,
+                Err(_) => {}
+            }
+        }
+
+        // Nothing was tried with the password yet, so this is not a failed attempt
+        if missing_keyfile {
+            return Err(AppError::KeyfileRequired);
+        }
+
+        Err(match kind {
+            SlotKind::Password => AppError::InvalidMasterPassword,
+            SlotKind::Recovery => AppError::InvalidRecoveryKey,
+        })
+    }
+
+    pub fn slot(&self, kind: SlotKind) -> Option<&KeySlot> {
+        match &self.protection {
+            KeyProtection::Direct(_) => None,
+            KeyProtection::Slots(slots) => slots.iter().find(|s| s.kind == kind),
+        }
+    }
+
+    /// Returns a copy with the slot of `kind` replaced by one for `secret`.
+    /// The data key and other slots are kept; a legacy key is first swapped
+    /// for a random data key, since the old one is only the password hashed.
+    pub fn with_slot(&self, kind: SlotKind, secret: &str, keyfile: Option<&Keyfile>, kdf: Kdf) -> Result<Self> {
+        let mut key = match &self.protection {
+            KeyProtection::Direct(_) => Self::random(),
+            KeyProtection::Slots(_) => self.clone(),
+        };
+
+        let slot = KeySlot::wrap(kind, kdf, secret, keyfile, &key.key)?;
+
+        if let KeyProtection::Slots(slots) = &mut key.protection {
+            slots.retain(|s| s.kind != kind);
+            slots.push(slot);
+        }
+
+        Ok(key)
+    }
+
+    pub fn verify_password(&self, password: &str, keyfile: Option<&Keyfile>) -> bool {
+        let derived = match &self.protection {
+            KeyProtection::Direct(kdf) => kdf.derive(password),
+            KeyProtection::Slots(slots) => Self::unlock(slots, SlotKind::Password, password, keyfile).map(|k| k.key),
+        };
+
+        derived.is_ok_and(|key| key == self.key)
+    }
+
+    pub fn requires_keyfile(&self) -> bool {
+        self.slot(SlotKind::Password).is_some_and(|s| s.keyfile)
+    }
+
+    /// True when the vault should be re-keyed with `Kdf::generate` on unlock
+    pub fn needs_upgrade(&self) -> bool {
+        match &self.protection {
+            KeyProtection::Direct(_) => true,
+            KeyProtection::Slots(_) => self.slot(SlotKind::Password).is_some_and(|s| s.kdf.needs_upgrade()),
+        }
+    }
+}
+
+/// A new recovery key: 160 random bits in base32, in groups of four
+/// characters so it can be written down
+pub fn generate_recovery_key() -> Zeroizing<String> {
+    let bytes: [u8; RECOVERY_KEY_BYTES] = rand::random();
+    let encoded = Zeroizing::new(totp_rs::Secret::Raw(bytes.to_vec()).to_encoded().to_string());
+
+    Zeroizing::new(
+        encoded.as_bytes()
+            .chunks(4)
+            .map(|chunk| String::from_utf8_lossy(chunk).into_owned())
+            .collect::<Vec<_>>()
+            .join("-"),
+    )
+}
+
+/// The form a recovery key slot is derived from, ignoring case, dashes and spaces
+pub fn normalize_recovery_key(recovery_key: &str) -> Zeroizing<String> {
+    Zeroizing::new(
+        recovery_key
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric())
+            .map(|c| c.to_ascii_uppercase())
+            .collect(),
+    )
+}
+
+/// Legacy key derivation, kept to open vaults written before `Kdf` existed
 pub fn derive_key(password: &str) -> [u8; 32] {
     let mut hasher = Sha256::new();
     hasher.update(password.as_bytes());
@@ -35,6 +371,80 @@ pub fn encrypt_data(data: &str, key: &[u8; 32]) -> Result<String> {
     Ok(general_purpose::STANDARD.encode(result))
 }
 
+/// AES-256-GCM with associated data, returning the random nonce and the
+/// ciphertext with its tag
+#[allow(deprecated)]
+pub fn encrypt_with_aad(key: &[u8; 32], plaintext: &[u8], aad: &[u8]) -> Result<([u8; NONCE_SIZE], Vec<u8>)> {
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key));
+    
+    let mut nonce_bytes = [0u8; NONCE_SIZE];
+    rand::thread_rng().fill_bytes(&mut nonce_bytes);
+    
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce_bytes), Payload { msg: plaintext, aad })
+        .map_err(|_| AppError::Encryption("Failed to encrypt data".to_string()))?;
+    
+    Ok((nonce_bytes, ciphertext))
+}
+
+#[allow(deprecated)]
+pub fn decrypt_with_aad(key: &[u8; 32], nonce: &[u8], ciphertext: &[u8], aad: &[u8]) -> Result<Vec<u8>> {
+    if nonce.len() != NONCE_SIZE {
+        return Err(AppError::Encryption("Invalid nonce size".to_string()));
+    }
+    
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key));
+    
+    cipher
+        .decrypt(Nonce::from_slice(nonce), Payload { msg: ciphertext, aad })
+        .map_err(|_| AppError::InvalidMasterPassword)
+}
+
+/// Derives a 32-byte key with scrypt, as used by Aegis vault key slots
+pub fn derive_scrypt_key(password: &[u8], salt: &[u8], n: u64, r: u32, p: u32) -> Result<Zeroizing<[u8; 32]>> {
+    if !n.is_power_of_two() || n < 2 {
+        return Err(AppError::Encryption(format!("Invalid scrypt cost parameter: {}", n)));
+    }
+    
+    // The parameters come from the imported file, so a crafted one must not
+    // be able to make scrypt allocate more than the machine has
+    if n > MAX_SCRYPT_N || r > MAX_SCRYPT_R || p > MAX_SCRYPT_P || 128 * u64::from(r) * n > MAX_SCRYPT_MEMORY {
+        return Err(AppError::Encryption(format!("Scrypt parameters are too high (n={}, r={}, p={})", n, r, p)));
+    }
+    
+    let log_n = n.trailing_zeros() as u8;
+    let params = scrypt::Params::new(log_n, r, p, 32)
+        .map_err(|_| AppError::Encryption("Invalid scrypt parameters".to_string()))?;
+    
+    let mut key = Zeroizing::new([0u8; 32]);
+    scrypt::scrypt(password, salt, &params, key.as_mut())
+        .map_err(|_| AppError::Encryption("Failed to derive key".to_string()))?;
+    
+    Ok(key)
+}
+
+/// Derives a 32-byte key with PBKDF2-HMAC-SHA256, as used by 2FAS backups
+pub fn derive_pbkdf2_key(password: &[u8], salt: &[u8], iterations: u32) -> Zeroizing<[u8; 32]> {
+    let mut key = Zeroizing::new([0u8; 32]);
+    pbkdf2::pbkdf2_hmac::<Sha256>(password, salt, iterations, key.as_mut());
+    key
+}
+
+/// AES-256-GCM decryption for formats that store the nonce separately
+/// instead of prefixing it, with the tag appended to `ciphertext`
+#[allow(deprecated)]
+pub fn decrypt_with_nonce(key: &[u8; 32], nonce: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>> {
+    if nonce.len() != NONCE_SIZE {
+        return Err(AppError::Encryption("Invalid nonce size".to_string()));
+    }
+    
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key));
+    
+    cipher
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| AppError::InvalidMasterPassword)
+}
+
 #[allow(deprecated)]
 pub fn decrypt_data(encrypted_data: &str, key: &[u8; 32]) -> Result<String> {
     let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key));
@@ -75,6 +485,82 @@ mod tests {
         assert_eq!(data, decrypted);
     }
 
+    #[test]
+    fn test_salt_is_per_vault() {
+        let a = VaultKey::derive("same", Kdf::argon2id(1024, 1, 1)).unwrap();
+        let b = VaultKey::derive("same", Kdf::argon2id(1024, 1, 1)).unwrap();
+
+        assert_ne!(a.protection, b.protection);
+        assert_ne!(a.key, b.key);
+    }
+
+    #[test]
+    fn test_key_slots() {
+        let key = VaultKey::generate_with("password", None, Kdf::argon2id(1024, 1, 1)).unwrap();
+        let recovery_key = generate_recovery_key();
+        let key = key
+            .with_slot(SlotKind::Recovery, &normalize_recovery_key(&recovery_key), None, Kdf::argon2id(1024, 1, 1))
+            .unwrap();
+
+        let KeyProtection::Slots(slots) = &key.protection else { panic!("expected key slots") };
+        assert_eq!(slots.len(), 2);
+
+        let by_password = VaultKey::unlock(slots, SlotKind::Password, "password", None).unwrap();
+        let by_recovery = VaultKey::unlock(slots, SlotKind::Recovery, &normalize_recovery_key(&recovery_key.to_lowercase()), None).unwrap();
+        assert_eq!(by_password, key);
+        assert_eq!(by_recovery, key);
+
+        assert!(VaultKey::unlock(slots, SlotKind::Password, "wrong", None).is_err());
+        // The recovery key does not open the password slot and vice versa
+        assert!(VaultKey::unlock(slots, SlotKind::Recovery, "password", None).is_err());
+
+        let changed = key.with_slot(SlotKind::Password, "new", None, Kdf::argon2id(1024, 1, 1)).unwrap();
+        assert_eq!(changed.key, key.key);
+        assert_eq!(changed.slot(SlotKind::Recovery), key.slot(SlotKind::Recovery));
+        assert!(changed.verify_password("new", None));
+        assert!(!changed.verify_password("password", None));
+    }
+
+    #[test]
+    fn test_keyfile_is_a_second_factor() {
+        let keyfile = Keyfile::from_contents(b"random bytes on a usb stick").unwrap();
+        let other = Keyfile::from_contents(b"some other file").unwrap();
+        let key = VaultKey::generate_with("password", Some(&keyfile), Kdf::argon2id(1024, 1, 1)).unwrap();
+        let KeyProtection::Slots(slots) = &key.protection else { panic!("expected key slots") };
+
+        assert!(key.requires_keyfile());
+        assert!(matches!(
+            VaultKey::unlock(slots, SlotKind::Password, "password", None),
+            Err(AppError::KeyfileRequired)
+        ));
+        assert!(VaultKey::unlock(slots, SlotKind::Password, "password", Some(&other)).is_err());
+        assert!(VaultKey::unlock(slots, SlotKind::Password, "wrong", Some(&keyfile)).is_err());
+        assert_eq!(VaultKey::unlock(slots, SlotKind::Password, "password", Some(&keyfile)).unwrap(), key);
+
+        let removed = key.with_slot(SlotKind::Password, "password", None, Kdf::argon2id(1024, 1, 1)).unwrap();
+        assert!(!removed.requires_keyfile());
+        assert!(removed.verify_password("password", None));
+        assert!(Keyfile::from_contents(b"").is_err());
+    }
+
+    #[test]
+    fn test_scrypt_rejects_excessive_parameters() {
+        assert!(derive_scrypt_key(b"pw", b"salt", 1 << 40, 8, 1).is_err());
+        assert!(derive_scrypt_key(b"pw", b"salt", 1 << 15, 1 << 20, 1).is_err());
+        assert!(derive_scrypt_key(b"pw", b"salt", 1 << 20, 32, 1).is_err());
+        assert!(derive_scrypt_key(b"pw", b"salt", 1 << 4, 8, 1 << 20).is_err());
+        assert!(derive_scrypt_key(b"pw", b"salt", 1 << 4, 8, 1).is_ok());
+    }
+
+    #[test]
+    fn test_recovery_key_format() {
+        let recovery_key = generate_recovery_key();
+
+        assert_eq!(recovery_key.split('-').count(), 8);
+        assert!(recovery_key.split('-').all(|group| group.len() == 4));
+        assert_eq!(normalize_recovery_key(&recovery_key).len(), 32);
+    }
+
     #[test]
     fn test_different_keys() {
         let key1 = derive_key("password1");
@@ -0,0 +1,206 @@
+use crate::aegis::{AegisDb, AegisEntry, AegisEntryInfo, AegisGroup, AegisHeader, AegisVault};
+use crate::backup::encrypt_backup;
+use crate::otp::OtpGenerator;
+use crate::otpauth::build_otpauth_uri;
+use crate::twofas::{TwoFasBackup, TwoFasGroup, TwoFasOtp, TwoFasService};
+use crate::types::{AppError, ExportFormat, OtpApp, OtpType, Result};
+
+// Versions of the third-party formats these exports are written against
+const TWOFAS_SCHEMA_VERSION: u32 = 4;
+const AEGIS_VAULT_VERSION: u32 = 1;
+const AEGIS_DB_VERSION: u32 = 3;
+
+/// Renders the entries in `format`. Only the encrypted backup uses
+/// `passphrase`; every other format is plaintext.
+pub fn export_apps(apps: &[OtpApp], format: ExportFormat, passphrase: Option<&str>) -> Result<String> {
+    match format {
+        ExportFormat::TwoFas => export_2fas(apps),
+        ExportFormat::Aegis => export_aegis(apps),
+        ExportFormat::OtpauthUris => Ok(export_otpauth_uris(apps)),
+        ExportFormat::EncryptedBackup => {
+            let passphrase = passphrase
+                .filter(|p| !p.is_empty())
+                .ok_or_else(|| AppError::Encryption("A passphrase is required for encrypted backups".to_string()))?;
+            encrypt_backup(apps, passphrase)
+        }
+    }
+}
+
+pub fn export_2fas(apps: &[OtpApp]) -> Result<String> {
+    let groups: Vec<TwoFasGroup> = group_names(apps)
+        .into_iter()
+        .map(|name| TwoFasGroup { id: uuid::Uuid::new_v4().to_string(), name })
+        .collect();
+
+    let updated_at = chrono::Utc::now().timestamp_millis();
+
+    let services = apps
+        .iter()
+        .map(|app| {
+            let service = TwoFasService {
+                name: app.name.clone(),
+                secret: OtpGenerator::new().clean_secret(&app.secret),
+                updated_at,
+                otp: TwoFasOtp {
+                    label: app.account.clone(),
+                    account: app.account.clone(),
+                    issuer: app.issuer.clone(),
+                    digits: Some(app.digits),
+                    period: Some(app.period),
+                    algorithm: Some(app.algorithm.as_str().to_string()),
+                    counter: Some(app.counter),
+                    token_type: Some(app.otp_type.as_str().to_uppercase()),
+                    source: Some("Manual".to_string()),
+                },
+                group_id: app.group.as_ref()
+                    .and_then(|name| groups.iter().find(|g| &g.name == name))
+                    .map(|g| g.id.clone()),
+            };
+            serde_json::to_value(service)
+        })
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    let backup = TwoFasBackup {
+        services,
+        groups,
+        schema_version: TWOFAS_SCHEMA_VERSION,
+        services_encrypted: None,
+    };
+
+    Ok(serde_json::to_string_pretty(&backup)?)
+}
+
+/// Writes an unencrypted Aegis export, the same as Aegis' own "export without encryption"
+pub fn export_aegis(apps: &[OtpApp]) -> Result<String> {
+    let groups: Vec<AegisGroup> = group_names(apps)
+        .into_iter()
+        .map(|name| AegisGroup { uuid: uuid::Uuid::new_v4().to_string(), name })
+        .collect();
+
+    let entries = apps
+        .iter()
+        .map(|app| {
+            let is_hotp = app.otp_type == OtpType::Hotp;
+
+            let entry = AegisEntry {
+                entry_type: app.otp_type.as_str().to_string(),
+                uuid: app.id.clone(),
+                name: app.account.clone().unwrap_or_else(|| app.name.clone()),
+                issuer: app.issuer.clone().unwrap_or_else(|| app.name.clone()),
+                note: app.note.clone().unwrap_or_default(),
+                info: AegisEntryInfo {
+                    secret: OtpGenerator::new().clean_secret(&app.secret),
+                    algo: Some(app.algorithm.as_str().to_string()),
+                    digits: Some(app.digits),
+                    period: (!is_hotp).then_some(app.period),
+                    counter: is_hotp.then_some(app.counter),
+                },
+                groups: app.group.as_ref()
+                    .and_then(|name| groups.iter().find(|g| &g.name == name))
+                    .map(|g| vec![g.uuid.clone()])
+                    .unwrap_or_default(),
+                group: None,
+            };
+            serde_json::to_value(entry)
+        })
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    let db = AegisDb { version: AEGIS_DB_VERSION, entries, groups };
+
+    let vault = AegisVault {
+        version: AEGIS_VAULT_VERSION,
+        header: AegisHeader { slots: None, params: None },
+        db: serde_json::to_value(db)?,
+    };
+
+    Ok(serde_json::to_string_pretty(&vault)?)
+}
+
+/// One `otpauth://` URI per line, the format most authenticators can import
+pub fn export_otpauth_uris(apps: &[OtpApp]) -> String {
+    apps.iter()
+        .map(build_otpauth_uri)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn group_names(apps: &[OtpApp]) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+
+    for name in apps.iter().filter_map(|app| app.group.as_ref()) {
+        if !names.contains(name) {
+            names.push(name.clone());
+        }
+    }
+
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::import::{parse_2fas, parse_aegis};
+    use crate::otpauth::parse_otpauth_uri;
+    use crate::types::OtpAlgorithm;
+
+    fn sample_apps() -> Vec<OtpApp> {
+        let mut acme = OtpApp::new("ACME".to_string(), "JBSWY3DPEHPK3PXP".to_string());
+        acme.issuer = Some("ACME".to_string());
+        acme.account = Some("alice@example.com".to_string());
+        acme.algorithm = OtpAlgorithm::Sha256;
+        acme.digits = 8;
+        acme.group = Some("Work".to_string());
+
+        let mut gateway = OtpApp::new("Gateway".to_string(), "HXDMVJECJJWSRB3HWIZR4IFUGFTMXBOZ".to_string());
+        gateway.otp_type = OtpType::Hotp;
+        gateway.counter = 12;
+
+        vec![acme, gateway]
+    }
+
+    fn assert_round_trip(original: &[OtpApp], restored: &[OtpApp]) {
+        assert_eq!(original.len(), restored.len());
+
+        for (a, b) in original.iter().zip(restored) {
+            assert_eq!(a.name, b.name);
+            assert_eq!(a.secret, b.secret);
+            assert_eq!(a.params(), b.params());
+            assert_eq!(a.group, b.group);
+        }
+    }
+
+    #[test]
+    fn test_2fas_export_round_trip() {
+        let apps = sample_apps();
+        let parsed = parse_2fas(&export_2fas(&apps).unwrap(), None).unwrap();
+        assert_round_trip(&apps, &parsed.apps);
+    }
+
+    #[test]
+    fn test_aegis_export_round_trip() {
+        let mut apps = sample_apps();
+        // Aegis has no separate display name, it is derived from the issuer
+        apps[1].issuer = Some("Gateway".to_string());
+        apps[1].account = Some("admin".to_string());
+
+        let parsed = parse_aegis(&export_aegis(&apps).unwrap(), None).unwrap();
+        assert_round_trip(&apps, &parsed.apps);
+    }
+
+    #[test]
+    fn test_otpauth_uri_list() {
+        let apps = sample_apps();
+        let content = export_otpauth_uris(&apps);
+
+        let restored: Vec<_> = content.lines().map(|line| parse_otpauth_uri(line).unwrap()).collect();
+        assert_eq!(restored.len(), 2);
+        assert_eq!(restored[0].params, apps[0].params());
+        assert_eq!(restored[1].params.counter, 12);
+    }
+
+    #[test]
+    fn test_encrypted_backup_requires_passphrase() {
+        assert!(export_apps(&sample_apps(), ExportFormat::EncryptedBackup, None).is_err());
+        assert!(export_apps(&sample_apps(), ExportFormat::EncryptedBackup, Some("")).is_err());
+    }
+}
@@ -0,0 +1,419 @@
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::store::{Blob, VaultStore};
+use crate::types::{AppError, Result};
+
+const DATA_DIR: &str = ".plaxo-otp";
+const BACKUPS_DIR: &str = "backups";
+/// The single backup kept by versions before rotating backups
+const LEGACY_BACKUP_FILE: &str = "apps.enc.backup";
+
+/// Overrides the data directory, e.g. `--data-dir /media/usb/otp`
+pub const DATA_DIR_FLAG: &str = "--data-dir";
+pub const DATA_DIR_ENV: &str = "PLAXO_OTP_DATA_DIR";
+/// A file with this name next to the executable turns on portable mode,
+/// keeping the data in `PORTABLE_DATA_DIR` beside it
+pub const PORTABLE_MARKER: &str = "plaxo-otp.portable";
+const PORTABLE_DATA_DIR: &str = "plaxo-otp-data";
+
+/// The vault files in one data directory
+#[derive(Debug, Clone)]
+pub struct FsStore {
+    data_dir: PathBuf,
+}
+
+impl FsStore {
+    pub fn new(data_dir: PathBuf) -> Self {
+        Self { data_dir }
+    }
+
+    /// Uses the directory chosen by the command line, the environment or
+    /// portable mode, in that order, and `~/.plaxo-otp` otherwise
+    pub fn from_environment() -> Result<Self> {
+        let args: Vec<String> = std::env::args().skip(1).collect();
+        let exe_dir = std::env::current_exe()
+            .ok()
+            .and_then(|exe| exe.parent().map(Path::to_path_buf));
+
+        let data_dir = resolve_data_dir(
+            &args,
+            std::env::var_os(DATA_DIR_ENV),
+            exe_dir.as_deref(),
+            dirs::home_dir(),
+        )?;
+
+        let data_dir = if data_dir.is_relative() {
+            std::env::current_dir()?.join(data_dir)
+        } else {
+            data_dir
+        };
+
+        tracing::info!("Using data directory {:?}", data_dir);
+        Ok(Self::new(data_dir))
+    }
+
+    pub fn data_dir(&self) -> &Path {
+        &self.data_dir
+    }
+
+    fn path(&self, blob: Blob) -> PathBuf {
+        match blob {
+            Blob::Backup(_) => self.data_dir.join(BACKUPS_DIR).join(blob.file_name()),
+            _ => self.data_dir.join(blob.file_name()),
+        }
+    }
+
+    /// Creates the directory `blob` goes in
+    fn prepare(&self, blob: Blob) -> Result<PathBuf> {
+        let path = self.path(blob);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        Ok(path)
+    }
+
+    /// Moves the old single backup in as the backup of its modification time.
+    /// Run once at startup, so listing backups never changes the directory.
+    pub fn adopt_legacy_backup(&self) -> Result<()> {
+        let legacy = self.data_dir.join(LEGACY_BACKUP_FILE);
+        if !legacy.exists() {
+            return Ok(());
+        }
+
+        let modified = fs::metadata(&legacy)
+            .and_then(|meta| meta.modified())
+            .map(|time| chrono::DateTime::<chrono::Utc>::from(time).timestamp_millis())
+            .unwrap_or(0);
+
+        // Moved forward a millisecond at a time past backups already using it
+        let target = (modified..=i64::MAX)
+            .map(|created_at| self.path(Blob::Backup(created_at)))
+            .find(|path| !path.exists())
+            .ok_or_else(|| AppError::Io("No free backup timestamp for the old backup".to_string()))?;
+
+        fs::create_dir_all(self.data_dir.join(BACKUPS_DIR))?;
+        fs::rename(&legacy, target)?;
+        Ok(())
+    }
+}
+
+impl VaultStore for FsStore {
+    fn read(&self, blob: Blob) -> Result<Option<Vec<u8>>> {
+        match fs::read(self.path(blob)) {
+            Ok(contents) => Ok(Some(contents)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn exists(&self, blob: Blob) -> bool {
+        self.path(blob).exists()
+    }
+
+    fn write(&self, blob: Blob, contents: &[u8]) -> Result<()> {
+        let path = self.prepare(blob)?;
+        write_durably(&path, contents)?;
+        Ok(())
+    }
+
+    fn remove(&self, blob: Blob) -> Result<()> {
+        match fs::remove_file(self.path(blob)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn backups(&self) -> Result<Vec<i64>> {
+        let dir = self.data_dir.join(BACKUPS_DIR);
+        if !dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut backups: Vec<i64> = fs::read_dir(&dir)?
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| match Blob::parse_backup(entry.file_name().to_str()?)? {
+                Blob::Backup(created_at) => Some(created_at),
+                _ => None,
+            })
+            .collect();
+
+        backups.sort_by_key(|created_at| std::cmp::Reverse(*created_at));
+        Ok(backups)
+    }
+
+    fn replace_all(&self, changes: &[(Blob, Option<Vec<u8>>)]) -> Result<()> {
+        let changes = changes
+            .iter()
+            .map(|(blob, contents)| Ok((self.prepare(*blob)?, contents.clone())))
+            .collect::<Result<Vec<_>>>()?;
+
+        replace_all(&changes)
+    }
+
+    /// Removes the files this store owns. Directories only go if nothing
+    /// else is left in them, as the data directory may be one the user
+    /// picked.
+    fn reset(&self) -> Result<()> {
+        let owned = [
+            Blob::Vault,
+            Blob::PendingVault,
+            Blob::GoogleAuth,
+            Blob::Attempts,
+            Blob::BackupPolicy,
+            Blob::Settings,
+        ];
+
+        for blob in owned.into_iter().chain(self.backups()?.into_iter().map(Blob::Backup)) {
+            self.remove(blob)?;
+        }
+
+        // Fail harmlessly when the directory is missing or not empty
+        let _ = fs::remove_dir(self.data_dir.join(BACKUPS_DIR));
+        let _ = fs::remove_dir(&self.data_dir);
+
+        tracing::info!("Reset all data");
+        Ok(())
+    }
+
+    fn location(&self) -> String {
+        self.data_dir.display().to_string()
+    }
+}
+
+/// Applies every change or none. New contents are staged next to their
+/// targets first and the originals are kept until all of them are swapped
+/// in. `None` removes the file.
+fn replace_all(changes: &[(PathBuf, Option<Vec<u8>>)]) -> Result<()> {
+    let discard_staged = || {
+        for (path, _) in changes {
+            let _ = fs::remove_file(suffixed(path, "new"));
+        }
+    };
+
+    for (path, contents) in changes {
+        if let Some(contents) = contents {
+            if let Err(e) = write_synced(&suffixed(path, "new"), contents) {
+                discard_staged();
+                return Err(e.into());
+            }
+        }
+    }
+
+    // Targets touched so far, and whether their original was moved aside
+    let mut swapped: Vec<(&PathBuf, bool)> = Vec::new();
+
+    for (path, contents) in changes {
+        let had_original = path.exists();
+
+        let result = (if had_original { fs::rename(path, suffixed(path, "old")) } else { Ok(()) })
+            .map(|()| swapped.push((path, had_original)))
+            .and_then(|()| match contents {
+                Some(_) => fs::rename(suffixed(path, "new"), path),
+                None => Ok(()),
+            });
+
+        if let Err(e) = result {
+            tracing::error!("Failed to replace {:?}, rolling back: {}", path, e);
+
+            for (path, had_original) in swapped.iter().rev() {
+                if *had_original {
+                    let _ = fs::rename(suffixed(path, "old"), path);
+                } else {
+                    let _ = fs::remove_file(path);
+                }
+            }
+            discard_staged();
+            return Err(e.into());
+        }
+    }
+
+    for (path, _) in &swapped {
+        let _ = fs::remove_file(suffixed(path, "old"));
+    }
+
+    for (path, _) in swapped {
+        sync_parent_dir(path)?;
+    }
+
+    Ok(())
+}
+
+fn suffixed(path: &Path, suffix: &str) -> PathBuf {
+    PathBuf::from(format!("{}.{}", path.to_string_lossy(), suffix))
+}
+
+/// Writes `contents` and waits until they are on disk
+fn write_synced(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut file = File::create(path)?;
+    file.write_all(contents)?;
+    file.sync_all()
+}
+
+/// Replaces `path` so that after a crash it holds either the old or the new
+/// contents, never a truncated mix: the data goes to `<path>.tmp` and is
+/// flushed, the rename swaps it in, and flushing the directory makes the
+/// rename itself durable.
+fn write_durably(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let temp_path = suffixed(path, "tmp");
+    write_synced(&temp_path, contents)?;
+    fs::rename(&temp_path, path)?;
+    sync_parent_dir(path)
+}
+
+#[cfg(unix)]
+fn sync_parent_dir(path: &Path) -> io::Result<()> {
+    match path.parent() {
+        Some(dir) => File::open(dir)?.sync_all(),
+        None => Ok(()),
+    }
+}
+
+// Directories cannot be opened for flushing on Windows; NTFS journals the
+// rename itself
+#[cfg(not(unix))]
+fn sync_parent_dir(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+/// Picks the data directory: `--data-dir <path>` or `--data-dir=<path>`,
+/// then `env_dir`, then the portable marker in `exe_dir`, then `home`
+fn resolve_data_dir(
+    args: &[String],
+    env_dir: Option<OsString>,
+    exe_dir: Option<&Path>,
+    home: Option<PathBuf>,
+) -> Result<PathBuf> {
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == DATA_DIR_FLAG {
+            let value = args.next()
+                .ok_or_else(|| AppError::Io(format!("{} needs a directory", DATA_DIR_FLAG)))?;
+            return Ok(PathBuf::from(value));
+        }
+
+        if let Some(value) = arg.strip_prefix(DATA_DIR_FLAG).and_then(|rest| rest.strip_prefix('=')) {
+            return Ok(PathBuf::from(value));
+        }
+    }
+
+    if let Some(dir) = env_dir.filter(|dir| !dir.is_empty()) {
+        return Ok(PathBuf::from(dir));
+    }
+
+    if let Some(exe_dir) = exe_dir.filter(|dir| dir.join(PORTABLE_MARKER).exists()) {
+        return Ok(exe_dir.join(PORTABLE_DATA_DIR));
+    }
+
+    home.map(|home| home.join(DATA_DIR))
+        .ok_or_else(|| AppError::Io(format!(
+            "Could not determine home directory, set {} or pass {}",
+            DATA_DIR_ENV, DATA_DIR_FLAG
+        )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_write_leaves_no_temp_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = FsStore::new(temp_dir.path().to_path_buf());
+
+        store.write(Blob::Vault, b"first").unwrap();
+        store.write(Blob::Vault, b"second").unwrap();
+        store.write(Blob::Backup(1), b"first").unwrap();
+
+        assert_eq!(store.read(Blob::Vault).unwrap().unwrap(), b"second");
+        assert!(!store.exists(Blob::PendingVault));
+        assert_eq!(fs::read_dir(temp_dir.path().join(BACKUPS_DIR)).unwrap().count(), 1);
+        assert_eq!(store.read(Blob::GoogleAuth).unwrap(), None);
+    }
+
+    #[test]
+    fn test_legacy_backup_is_adopted() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = FsStore::new(temp_dir.path().to_path_buf());
+        fs::write(temp_dir.path().join(LEGACY_BACKUP_FILE), b"old vault").unwrap();
+        assert!(store.backups().unwrap().is_empty());
+
+        store.adopt_legacy_backup().unwrap();
+        let backups = store.backups().unwrap();
+        assert_eq!(backups.len(), 1);
+        assert_eq!(store.read(Blob::Backup(backups[0])).unwrap().unwrap(), b"old vault");
+        assert!(!temp_dir.path().join(LEGACY_BACKUP_FILE).exists());
+    }
+
+    #[test]
+    fn test_replace_all_applies_every_change() {
+        let temp_dir = TempDir::new().unwrap();
+        let kept = temp_dir.path().join("kept");
+        let removed = temp_dir.path().join("removed");
+        fs::write(&kept, "old").unwrap();
+        fs::write(&removed, "old").unwrap();
+
+        replace_all(&[(kept.clone(), Some(b"new".to_vec())), (removed.clone(), None)]).unwrap();
+
+        assert_eq!(fs::read_to_string(&kept).unwrap(), "new");
+        assert!(!removed.exists());
+        assert_eq!(fs::read_dir(temp_dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_replace_all_leaves_files_untouched_on_failure() {
+        let temp_dir = TempDir::new().unwrap();
+        let first = temp_dir.path().join("first");
+        let unwritable = temp_dir.path().join("missing-dir").join("second");
+        fs::write(&first, "old").unwrap();
+
+        let result = replace_all(&[(first.clone(), Some(b"new".to_vec())), (unwritable, Some(b"new".to_vec()))]);
+
+        assert!(result.is_err());
+        assert_eq!(fs::read_to_string(&first).unwrap(), "old");
+        assert_eq!(fs::read_dir(temp_dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_data_dir_resolution_order() {
+        let temp_dir = TempDir::new().unwrap();
+        let exe_dir = temp_dir.path();
+        let home = Some(PathBuf::from("/home/user"));
+        let flag = vec![DATA_DIR_FLAG.to_string(), "/media/usb".to_string()];
+        let env = || Some(OsString::from("/from/env"));
+
+        assert_eq!(resolve_data_dir(&[], None, Some(exe_dir), home.clone()).unwrap(), PathBuf::from("/home/user/.plaxo-otp"));
+
+        fs::write(exe_dir.join(PORTABLE_MARKER), "").unwrap();
+        assert_eq!(resolve_data_dir(&[], None, Some(exe_dir), home.clone()).unwrap(), exe_dir.join(PORTABLE_DATA_DIR));
+
+        assert_eq!(resolve_data_dir(&[], env(), Some(exe_dir), home.clone()).unwrap(), PathBuf::from("/from/env"));
+        assert_eq!(resolve_data_dir(&flag, env(), Some(exe_dir), home.clone()).unwrap(), PathBuf::from("/media/usb"));
+        assert_eq!(
+            resolve_data_dir(&["--data-dir=/media/usb".to_string()], None, None, None).unwrap(),
+            PathBuf::from("/media/usb")
+        );
+
+        assert!(resolve_data_dir(&[DATA_DIR_FLAG.to_string()], None, None, home).is_err());
+        assert!(resolve_data_dir(&[], None, None, None).is_err());
+    }
+
+    #[test]
+    fn test_reset_keeps_foreign_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = FsStore::new(temp_dir.path().to_path_buf());
+        let foreign = temp_dir.path().join("notes.txt");
+
+        store.write(Blob::Vault, b"vault").unwrap();
+        store.write(Blob::Backup(1), b"vault").unwrap();
+        fs::write(&foreign, "keep me").unwrap();
+        store.reset().unwrap();
+
+        assert!(!store.exists(Blob::Vault));
+        assert!(!temp_dir.path().join(BACKUPS_DIR).exists());
+        assert!(foreign.exists());
+    }
+}
@@ -0,0 +1,563 @@
+use crate::aegis::{read_vault, AegisEntry};
+use crate::backup::decrypt_backup;
+use crate::migration::decode_migration_uris;
+use crate::otp::{OtpGenerator, STEAM_DIGITS};
+use crate::twofas::{read_backup, TwoFasService};
+use crate::types::{
+    DuplicatePolicy, ImportPreview, ImportPreviewEntry, ImportReport, OtpAlgorithm, OtpApp,
+    OtpParams, OtpType, QrData, Result, SkippedEntry, DEFAULT_PERIOD,
+};
+
+/// Result of parsing an import source: the entries found in it and the ones
+/// that could not be read, each with a reason for the user. Entries are only
+/// validated when they are previewed or merged.
+#[derive(Debug, Default)]
+pub struct ParsedImport {
+    pub apps: Vec<OtpApp>,
+    pub skipped: Vec<SkippedEntry>,
+}
+
+impl ParsedImport {
+    fn skip(&mut self, name: &str, reason: impl Into<String>) {
+        let reason = reason.into();
+        tracing::warn!("Skipped {}: {}", name, reason);
+        self.skipped.push(SkippedEntry { name: name.to_string(), reason });
+    }
+}
+
+/// Describes what merging `parsed` into `existing` would do, without changing anything
+pub fn preview(parsed: ParsedImport, existing: &[OtpApp]) -> ImportPreview {
+    let entries = parsed.apps
+        .into_iter()
+        .map(|app| ImportPreviewEntry {
+            warnings: validate_app(&app).err().map(|e| e.to_string()).into_iter().collect(),
+            duplicate: existing.iter().any(|current| is_same_entry(current, &app)),
+            app,
+        })
+        .collect();
+
+    ImportPreview { entries, invalid: parsed.skipped }
+}
+
+/// Adds the valid entries of `parsed` to `existing`, resolving entries that
+/// are already present according to `policy`
+pub fn merge_apps(existing: &mut Vec<OtpApp>, parsed: ParsedImport, policy: DuplicatePolicy) -> ImportReport {
+    let mut report = ImportReport {
+        invalid: parsed.skipped,
+        ..ImportReport::default()
+    };
+
+    for app in parsed.apps {
+        if let Err(e) = validate_app(&app) {
+            tracing::warn!("Skipped {}: {}", app.name, e);
+            report.invalid.push(SkippedEntry { name: app.name, reason: e.to_string() });
+            continue;
+        }
+
+        let duplicate = existing.iter_mut().find(|current| is_same_entry(current, &app));
+
+        match (duplicate, policy) {
+            (Some(current), DuplicatePolicy::Overwrite) => {
+                overwrite_metadata(current, app);
+                report.updated.push(current.name.clone());
+            }
+            (Some(_), DuplicatePolicy::Skip) => {
+                tracing::info!("Skipped duplicate: {}", app.name);
+                report.duplicates.push(app.name);
+            }
+            (Some(_), DuplicatePolicy::KeepBoth) | (None, _) => {
+                let mut app = app;
+                // Backups keep their ids; a copy kept next to the original, or
+                // another account that happens to share the id, needs its own,
+                // or commands would act on either one
+                if existing.iter().any(|current| current.id == app.id) {
+                    app.id = uuid::Uuid::new_v4().to_string();
+                }
+                report.added.push(app.name.clone());
+                existing.push(app);
+            }
+        }
+    }
+
+    report
+}
+
+/// Two entries are the same account when they have the same secret and the
+/// same issuer/account once normalised. Ids are not compared: one shared by
+/// a different account would let `Overwrite` pair it with the wrong secret.
+pub fn is_same_entry(a: &OtpApp, b: &OtpApp) -> bool {
+    let otp_generator = OtpGenerator::new();
+
+    otp_generator.clean_secret(&a.secret) == otp_generator.clean_secret(&b.secret)
+        && identity(a) == identity(b)
+}
+
+fn identity(app: &OtpApp) -> (String, String) {
+    let normalise = |value: Option<&str>| value.unwrap_or_default().trim().to_lowercase();
+
+    // Entries added by hand only have a name, which stands in for the issuer
+    let issuer = app.issuer.as_deref().filter(|i| !i.trim().is_empty()).unwrap_or(&app.name);
+
+    (normalise(Some(issuer)), normalise(app.account.as_deref()))
+}
+
+fn overwrite_metadata(current: &mut OtpApp, incoming: OtpApp) {
+    // Never move an HOTP counter backwards, that would replay used codes
+    let counter = current.counter.max(incoming.counter);
+
+    *current = OtpApp {
+        id: current.id.clone(),
+        secret: current.secret.clone(),
+        counter,
+        ..incoming
+    };
+}
+
+pub fn parse_2fas(content: &str, password: Option<&str>) -> Result<ParsedImport> {
+    let backup = read_backup(content, password)?;
+
+    let mut parsed = ParsedImport::default();
+
+    for value in &backup.services {
+        let service: TwoFasService = match serde_json::from_value(value.clone()) {
+            Ok(service) => service,
+            Err(e) => {
+                let name = value.get("name").and_then(|n| n.as_str()).unwrap_or("Unknown");
+                parsed.skip(name, format!("Invalid entry: {}", e));
+                continue;
+            }
+        };
+
+        match twofas_params(&service) {
+            Ok(params) => {
+                let mut app = OtpApp::with_params(service.name.clone(), service.secret.clone(), params);
+                app.group = backup.group_name(&service);
+                parsed.apps.push(app);
+            }
+            Err(reason) => parsed.skip(&service.name, reason),
+        }
+    }
+
+    Ok(parsed)
+}
+
+pub fn parse_backup(content: &str, passphrase: &str) -> Result<ParsedImport> {
+    let apps = decrypt_backup(content, passphrase)?;
+    Ok(ParsedImport { apps, skipped: Vec::new() })
+}
+
+pub fn parse_google_migration(uris: &[String]) -> Result<ParsedImport> {
+    let export = decode_migration_uris(uris)?;
+
+    let mut parsed = ParsedImport::default();
+    for QrData { name, secret, params } in export.entries {
+        parsed.apps.push(OtpApp::with_params(name, secret, params));
+    }
+    for entry in export.skipped {
+        parsed.skip(&entry.name, entry.reason);
+    }
+
+    Ok(parsed)
+}
+
+pub fn parse_aegis(content: &str, password: Option<&str>) -> Result<ParsedImport> {
+    let db = read_vault(content, password)?;
+
+    let mut parsed = ParsedImport::default();
+
+    for value in &db.entries {
+        let entry: AegisEntry = match serde_json::from_value(value.clone()) {
+            Ok(entry) => entry,
+            Err(e) => {
+                let name = value.get("name").and_then(|n| n.as_str()).unwrap_or("Unknown");
+                parsed.skip(name, format!("Malformed entry: {}", e));
+                continue;
+            }
+        };
+
+        let display_name = if entry.issuer.trim().is_empty() {
+            entry.name.clone()
+        } else {
+            entry.issuer.clone()
+        };
+
+        match aegis_params(&entry) {
+            Ok(params) => {
+                let mut app = OtpApp::with_params(display_name, entry.info.secret.clone(), params);
+                app.group = db.group_name(&entry);
+                app.note = Some(entry.note.clone()).filter(|n| !n.is_empty());
+                parsed.apps.push(app);
+            }
+            Err(reason) => parsed.skip(&display_name, reason),
+        }
+    }
+
+    Ok(parsed)
+}
+
+fn aegis_params(entry: &AegisEntry) -> std::result::Result<OtpParams, String> {
+    let otp_type = match entry.entry_type.as_str() {
+        "totp" => OtpType::Totp,
+        "hotp" => OtpType::Hotp,
+        "steam" => OtpType::Steam,
+        other => return Err(format!("Unsupported entry type: {}", other)),
+    };
+
+    let algorithm = match entry.info.algo.as_deref().unwrap_or("SHA1").to_ascii_uppercase().as_str() {
+        "SHA1" => OtpAlgorithm::Sha1,
+        "SHA256" => OtpAlgorithm::Sha256,
+        "SHA512" => OtpAlgorithm::Sha512,
+        other => return Err(format!("Unsupported algorithm: {}", other)),
+    };
+
+    let default_digits = match otp_type {
+        OtpType::Steam => STEAM_DIGITS,
+        _ => OtpParams::default().digits,
+    };
+
+    Ok(OtpParams {
+        otp_type,
+        algorithm,
+        digits: entry.info.digits.unwrap_or(default_digits),
+        period: entry.info.period.unwrap_or(DEFAULT_PERIOD),
+        counter: entry.info.counter.unwrap_or(0),
+        issuer: Some(entry.issuer.trim().to_string()).filter(|i| !i.is_empty()),
+        account: Some(entry.name.trim().to_string()).filter(|a| !a.is_empty()),
+    })
+}
+
+fn twofas_params(service: &TwoFasService) -> std::result::Result<OtpParams, String> {
+    let otp = &service.otp;
+
+    let otp_type = match otp.token_type.as_deref().unwrap_or("TOTP").to_ascii_uppercase().as_str() {
+        "TOTP" => OtpType::Totp,
+        "HOTP" => OtpType::Hotp,
+        "STEAM" => OtpType::Steam,
+        other => return Err(format!("Unsupported token type: {}", other)),
+    };
+
+    let algorithm = match otp.algorithm.as_deref().unwrap_or("SHA1").to_ascii_uppercase().as_str() {
+        "SHA1" => OtpAlgorithm::Sha1,
+        "SHA256" => OtpAlgorithm::Sha256,
+        "SHA512" => OtpAlgorithm::Sha512,
+        other => return Err(format!("Unsupported algorithm: {}", other)),
+    };
+
+    let default_digits = match otp_type {
+        OtpType::Steam => STEAM_DIGITS,
+        _ => OtpParams::default().digits,
+    };
+
+    let non_empty = |value: &Option<String>| value.as_deref()
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .map(str::to_string);
+
+    Ok(OtpParams {
+        otp_type,
+        algorithm,
+        digits: otp.digits.unwrap_or(default_digits),
+        period: otp.period.unwrap_or(DEFAULT_PERIOD),
+        counter: otp.counter.unwrap_or(0),
+        issuer: non_empty(&otp.issuer),
+        account: non_empty(&otp.account),
+    })
+}
+
+fn validate_app(app: &OtpApp) -> Result<()> {
+    let otp_generator = OtpGenerator::new();
+
+    otp_generator.validate_secret(&app.secret)?;
+    otp_generator.validate_entry_params(&app.params())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aes_gcm::{Aes256Gcm, Key, Nonce, aead::{Aead, KeyInit}};
+    use base64::{Engine as _, engine::general_purpose};
+    use crate::crypto::{derive_pbkdf2_key, derive_scrypt_key};
+
+    const TWOFAS_SERVICES: &str = r#"[
+        {"name": "ACME", "secret": "JBSWY3DPEHPK3PXP", "groupId": "g1",
+         "otp": {"account": "alice@example.com", "issuer": "ACME", "digits": 8, "period": 60,
+                 "algorithm": "SHA256", "tokenType": "TOTP", "source": "Link"}},
+        {"name": "Gateway", "secret": "JBSWY3DPEHPK3PXP",
+         "otp": {"digits": 6, "algorithm": "SHA1", "counter": 3, "tokenType": "HOTP"}},
+        {"name": "Steam", "secret": "JBSWY3DPEHPK3PXP", "otp": {"tokenType": "STEAM"}},
+        {"name": "Legacy", "secret": "JBSWY3DPEHPK3PXP", "otp": {"algorithm": "MD5"}},
+        {"name": "Broken"}
+    ]"#;
+
+    const AEGIS_DB: &str = r#"{
+        "version": 3,
+        "entries": [
+            {"type": "totp", "uuid": "1", "name": "alice@example.com", "issuer": "ACME", "note": "work",
+             "info": {"secret": "JBSWY3DPEHPK3PXP", "algo": "SHA256", "digits": 8, "period": 60},
+             "groups": ["g1"]},
+            {"type": "hotp", "uuid": "2", "name": "gateway", "issuer": "",
+             "info": {"secret": "JBSWY3DPEHPK3PXP", "algo": "SHA1", "digits": 6, "counter": 5}},
+            {"type": "steam", "uuid": "3", "name": "player", "issuer": "Steam",
+             "info": {"secret": "JBSWY3DPEHPK3PXP", "algo": "SHA1", "digits": 5, "period": 30}},
+            {"type": "motp", "uuid": "4", "name": "legacy", "issuer": "Old",
+             "info": {"secret": "JBSWY3DPEHPK3PXP", "algo": "MD5", "digits": 6, "period": 10}}
+        ],
+        "groups": [{"uuid": "g1", "name": "Work"}]
+    }"#;
+
+    #[allow(deprecated)]
+    fn seal(key: &[u8; 32], nonce: &[u8; 12], plaintext: &[u8]) -> (Vec<u8>, Vec<u8>) {
+        let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key));
+        let mut sealed = cipher.encrypt(Nonce::from_slice(nonce), plaintext).unwrap();
+        let tag = sealed.split_off(sealed.len() - 16);
+        (sealed, tag)
+    }
+
+    fn encrypted_vault(password: &str) -> String {
+        let master_key = [7u8; 32];
+        let salt = [1u8; 32];
+        let (n, r, p) = (1024, 8, 1);
+
+        let slot_key = derive_scrypt_key(password.as_bytes(), &salt, n, r, p).unwrap();
+        let (wrapped_key, key_tag) = seal(&slot_key, &[2u8; 12], &master_key);
+        let (db, db_tag) = seal(&master_key, &[3u8; 12], AEGIS_DB.as_bytes());
+
+        serde_json::json!({
+            "version": 1,
+            "header": {
+                "slots": [{
+                    "type": 1, "uuid": "slot", "key": hex::encode(wrapped_key),
+                    "key_params": {"nonce": hex::encode([2u8; 12]), "tag": hex::encode(key_tag)},
+                    "n": n, "r": r, "p": p, "salt": hex::encode(salt)
+                }],
+                "params": {"nonce": hex::encode([3u8; 12]), "tag": hex::encode(db_tag)}
+            },
+            "db": general_purpose::STANDARD.encode(db)
+        })
+        .to_string()
+    }
+
+    fn encrypted_2fas(password: &str) -> String {
+        let salt = [4u8; 256];
+        let iv = [5u8; 12];
+        let key = derive_pbkdf2_key(password.as_bytes(), &salt, 10_000);
+        let (mut sealed, tag) = seal(&key, &iv, TWOFAS_SERVICES.as_bytes());
+        sealed.extend_from_slice(&tag);
+
+        let encoded = [&sealed[..], &salt[..], &iv[..]]
+            .map(|part| general_purpose::STANDARD.encode(part))
+            .join(":");
+
+        serde_json::json!({
+            "services": [],
+            "groups": [{"id": "g1", "name": "Work", "isExpanded": true}],
+            "schemaVersion": 4,
+            "servicesEncrypted": encoded
+        })
+        .to_string()
+    }
+
+    #[test]
+    fn test_parse_full_2fas() {
+        let content = format!(
+            r#"{{"services": {}, "groups": [{{"id": "g1", "name": "Work"}}], "schemaVersion": 4}}"#,
+            TWOFAS_SERVICES
+        );
+
+        let parsed = parse_2fas(&content, None).unwrap();
+        assert_eq!(parsed.apps.len(), 3);
+        assert_eq!(parsed.skipped.len(), 2);
+        assert!(parsed.skipped[0].reason.contains("MD5"));
+        assert!(parsed.skipped[1].reason.contains("missing field `secret`"));
+
+        let acme = &parsed.apps[0];
+        assert_eq!(acme.issuer.as_deref(), Some("ACME"));
+        assert_eq!(acme.account.as_deref(), Some("alice@example.com"));
+        assert_eq!(acme.algorithm, OtpAlgorithm::Sha256);
+        assert_eq!((acme.digits, acme.period), (8, 60));
+        assert_eq!(acme.group.as_deref(), Some("Work"));
+
+        assert_eq!(parsed.apps[1].otp_type, OtpType::Hotp);
+        assert_eq!(parsed.apps[1].counter, 3);
+        assert_eq!(parsed.apps[2].otp_type, OtpType::Steam);
+        assert_eq!(parsed.apps[2].digits, STEAM_DIGITS);
+    }
+
+    #[test]
+    fn test_parse_legacy_2fas() {
+        let content = r#"{"services": [{"name": "GitHub", "secret": "JBSWY3DPEHPK3PXP"}]}"#;
+
+        let parsed = parse_2fas(content, None).unwrap();
+        assert_eq!(parsed.apps.len(), 1);
+        assert_eq!(parsed.apps[0].params(), OtpParams::default());
+    }
+
+    #[test]
+    fn test_other_json_is_not_a_2fas_backup() {
+        assert!(parse_2fas(r#"{"version": 1, "entries": []}"#, None).is_err());
+        assert!(parse_2fas("{}", None).is_err());
+        assert!(parse_2fas(r#"{"services": []}"#, None).unwrap().apps.is_empty());
+    }
+
+    #[test]
+    fn test_parse_encrypted_2fas() {
+        let content = encrypted_2fas("hunter2");
+
+        assert!(parse_2fas(&content, None).is_err());
+        assert!(parse_2fas(&content, Some("wrong")).is_err());
+
+        let parsed = parse_2fas(&content, Some("hunter2")).unwrap();
+        assert_eq!(parsed.apps.len(), 3);
+        assert_eq!(parsed.apps[0].group.as_deref(), Some("Work"));
+    }
+
+    fn app(name: &str, secret: &str, account: Option<&str>) -> OtpApp {
+        let mut app = OtpApp::new(name.to_string(), secret.to_string());
+        app.account = account.map(str::to_string);
+        app
+    }
+
+    fn parsed(apps: Vec<OtpApp>) -> ParsedImport {
+        ParsedImport { apps, skipped: Vec::new() }
+    }
+
+    #[test]
+    fn test_duplicates_are_matched_on_normalised_identity() {
+        let stored = app("GitHub", "JBSWY3DPEHPK3PXP", Some("alice"));
+
+        assert!(is_same_entry(&stored, &app("github ", "jbsw y3dp ehpk 3pxp", Some("Alice"))));
+        assert!(!is_same_entry(&stored, &app("GitHub", "JBSWY3DPEHPK3PXP", Some("bob"))));
+        assert!(!is_same_entry(&stored, &app("GitHub", "HXDMVJECJJWSRB3H", Some("alice"))));
+    }
+
+    #[test]
+    fn test_merge_policies() {
+        let stored = app("GitHub", "JBSWY3DPEHPK3PXP", None);
+        let mut renamed = app("GitHub", "JBSWY3DPEHPK3PXP", None);
+        renamed.group = Some("Work".to_string());
+        let new_entry = app("GitLab", "HXDMVJECJJWSRB3H", None);
+
+        let mut apps = vec![stored.clone()];
+        let report = merge_apps(&mut apps, parsed(vec![renamed.clone(), new_entry.clone()]), DuplicatePolicy::Skip);
+        assert_eq!(report.added, vec!["GitLab"]);
+        assert_eq!(report.duplicates, vec!["GitHub"]);
+        assert_eq!(apps.len(), 2);
+
+        let mut apps = vec![stored.clone()];
+        let report = merge_apps(&mut apps, parsed(vec![renamed.clone()]), DuplicatePolicy::Overwrite);
+        assert_eq!(report.updated, vec!["GitHub"]);
+        assert_eq!(apps.len(), 1);
+        assert_eq!(apps[0].id, stored.id);
+        assert_eq!(apps[0].group.as_deref(), Some("Work"));
+
+        let mut apps = vec![stored];
+        let report = merge_apps(&mut apps, parsed(vec![renamed]), DuplicatePolicy::KeepBoth);
+        assert_eq!(report.added.len(), 1);
+        assert_eq!(apps.len(), 2);
+    }
+
+    #[test]
+    fn test_kept_copies_get_their_own_id() {
+        let stored = app("GitHub", "JBSWY3DPEHPK3PXP", None);
+        let mut same_id = app("GitLab", "HXDMVJECJJWSRB3H", None);
+        same_id.id = stored.id.clone();
+
+        let mut apps = vec![stored.clone()];
+        let report = merge_apps(&mut apps, parsed(vec![stored.clone(), same_id]), DuplicatePolicy::KeepBoth);
+
+        assert_eq!(report.added.len(), 2);
+        assert_eq!(apps.len(), 3);
+        assert_eq!(apps[0].id, stored.id);
+        assert_ne!(apps[1].id, stored.id);
+        assert_ne!(apps[2].id, stored.id);
+        assert_ne!(apps[1].id, apps[2].id);
+    }
+
+    #[test]
+    fn test_matching_id_alone_is_not_a_duplicate() {
+        let stored = app("GitHub", "JBSWY3DPEHPK3PXP", None);
+        let mut same_id = app("GitLab", "HXDMVJECJJWSRB3H", None);
+        same_id.id = stored.id.clone();
+        same_id.digits = 8;
+
+        let mut apps = vec![stored.clone()];
+        let report = merge_apps(&mut apps, parsed(vec![same_id]), DuplicatePolicy::Overwrite);
+
+        assert_eq!(report.added, vec!["GitLab"]);
+        assert!(report.updated.is_empty());
+        assert_eq!(apps[0], stored);
+        assert_ne!(apps[1].id, stored.id);
+        assert_eq!(apps[1].secret, "HXDMVJECJJWSRB3H");
+    }
+
+    #[test]
+    fn test_preview_reports_warnings_without_merging() {
+        let stored = app("GitHub", "JBSWY3DPEHPK3PXP", None);
+        let incoming = vec![
+            app("GitHub", "JBSWY3DPEHPK3PXP", None),
+            app("Short", "JBSWY3DP", None),
+        ];
+
+        let preview = preview(parsed(incoming.clone()), std::slice::from_ref(&stored));
+        assert_eq!(preview.entries.len(), 2);
+        assert!(preview.entries[0].duplicate);
+        assert!(preview.entries[0].warnings.is_empty());
+        assert!(!preview.entries[1].duplicate);
+        assert_eq!(preview.entries[1].warnings.len(), 1);
+
+        let mut apps = vec![stored];
+        let report = merge_apps(&mut apps, parsed(incoming), DuplicatePolicy::KeepBoth);
+        assert_eq!(report.added, vec!["GitHub"]);
+        assert_eq!(report.invalid.len(), 1);
+        assert_eq!(report.invalid[0].name, "Short");
+    }
+
+    #[test]
+    fn test_reimporting_the_same_file_adds_nothing() {
+        let content = r#"{"services": [{"name": "GitHub", "secret": "JBSWY3DPEHPK3PXP"}]}"#;
+
+        let mut apps = Vec::new();
+        merge_apps(&mut apps, parse_2fas(content, None).unwrap(), DuplicatePolicy::Skip);
+        let report = merge_apps(&mut apps, parse_2fas(content, None).unwrap(), DuplicatePolicy::Skip);
+
+        assert!(report.added.is_empty());
+        assert_eq!(report.duplicates, vec!["GitHub"]);
+        assert_eq!(apps.len(), 1);
+    }
+
+    #[test]
+    fn test_parse_plain_aegis() {
+        let content = format!(
+            r#"{{"version": 1, "header": {{"slots": null, "params": null}}, "db": {}}}"#,
+            AEGIS_DB
+        );
+
+        let parsed = parse_aegis(&content, None).unwrap();
+        assert_eq!(parsed.apps.len(), 3);
+        assert_eq!(parsed.skipped.len(), 1);
+        assert!(parsed.skipped[0].reason.contains("motp"));
+
+        let acme = &parsed.apps[0];
+        assert_eq!(acme.name, "ACME");
+        assert_eq!(acme.account.as_deref(), Some("alice@example.com"));
+        assert_eq!(acme.algorithm, OtpAlgorithm::Sha256);
+        assert_eq!((acme.digits, acme.period), (8, 60));
+        assert_eq!(acme.group.as_deref(), Some("Work"));
+        assert_eq!(acme.note.as_deref(), Some("work"));
+
+        assert_eq!(parsed.apps[1].otp_type, OtpType::Hotp);
+        assert_eq!(parsed.apps[1].counter, 5);
+        assert_eq!(parsed.apps[2].otp_type, OtpType::Steam);
+    }
+
+    #[test]
+    fn test_parse_encrypted_aegis() {
+        let content = encrypted_vault("hunter2");
+
+        assert!(parse_aegis(&content, None).is_err());
+        assert!(parse_aegis(&content, Some("wrong")).is_err());
+
+        let parsed = parse_aegis(&content, Some("hunter2")).unwrap();
+        assert_eq!(parsed.apps.len(), 3);
+    }
+}
@@ -0,0 +1,176 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+use fs2::FileExt;
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, Manager, Runtime};
+
+use crate::types::{AppError, Result};
+
+const LOCK_FILE: &str = ".lock";
+/// Loopback port the running instance takes later launches on
+const PORT_FILE: &str = "instance.port";
+const FORWARD_TIMEOUT: Duration = Duration::from_secs(2);
+const MAX_FORWARDED_BYTES: u64 = 64 * 1024;
+
+/// Emitted to the frontend with the arguments of a later launch
+pub const SECOND_INSTANCE_EVENT: &str = "second-instance";
+
+/// Exclusive advisory lock on a data directory, held for as long as the
+/// process runs. Instances using other data directories are not affected.
+pub struct InstanceLock {
+    _file: File,
+    instance_dir: PathBuf,
+}
+
+pub enum Instance {
+    Primary(InstanceLock),
+    /// Another process already has this data directory open
+    Running,
+}
+
+/// Where the lock and port files of `data_dir` live. They stay on this
+/// machine, since the data directory may be synced to others, and are
+/// keyed by its path so other data directories get their own.
+fn instance_dir(data_dir: &Path) -> Result<PathBuf> {
+    let base = dirs::runtime_dir()
+        .or_else(dirs::cache_dir)
+        .ok_or_else(|| AppError::Io("Could not determine a runtime directory".to_string()))?;
+    let data_dir = fs::canonicalize(data_dir).unwrap_or_else(|_| data_dir.to_path_buf());
+    let key = hex::encode(Sha256::digest(data_dir.to_string_lossy().as_bytes()));
+
+    Ok(base.join("plaxo-otp").join("instances").join(key))
+}
+
+pub fn acquire(data_dir: &Path) -> Result<Instance> {
+    let instance = lock(&instance_dir(data_dir)?)?;
+
+    // Older versions kept both files in the data directory itself
+    if matches!(instance, Instance::Primary(_)) {
+        let _ = fs::remove_file(data_dir.join(LOCK_FILE));
+        let _ = fs::remove_file(data_dir.join(PORT_FILE));
+    }
+
+    Ok(instance)
+}
+
+fn lock(instance_dir: &Path) -> Result<Instance> {
+    fs::create_dir_all(instance_dir)?;
+    let file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(false)
+        .open(instance_dir.join(LOCK_FILE))?;
+
+    match file.try_lock_exclusive() {
+        Ok(()) => Ok(Instance::Primary(InstanceLock {
+            _file: file,
+            instance_dir: instance_dir.to_path_buf(),
+        })),
+        Err(e) if e.raw_os_error() == fs2::lock_contended_error().raw_os_error() => Ok(Instance::Running),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Hands the arguments of this launch to the instance holding `data_dir`
+pub fn forward(data_dir: &Path, args: &[String]) -> Result<()> {
+    forward_to(&instance_dir(data_dir)?, args)
+}
+
+fn forward_to(instance_dir: &Path, args: &[String]) -> Result<()> {
+    let port: u16 = fs::read_to_string(instance_dir.join(PORT_FILE))?
+        .trim()
+        .parse()
+        .map_err(|_| AppError::Io("Invalid instance port file".to_string()))?;
+
+    let address = SocketAddr::from((Ipv4Addr::LOCALHOST, port));
+    let mut stream = TcpStream::connect_timeout(&address, FORWARD_TIMEOUT)?;
+    stream.write_all(serde_json::to_string(args)?.as_bytes())?;
+    Ok(())
+}
+
+impl InstanceLock {
+    /// Takes later launches on a loopback port published next to the lock.
+    /// Each one brings the window to the front; its arguments are only
+    /// passed on to the frontend.
+    pub fn listen<R: Runtime>(&self, app: AppHandle<R>) -> Result<()> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?;
+        fs::write(self.instance_dir.join(PORT_FILE), listener.local_addr()?.port().to_string())?;
+
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let args = read_forwarded_args(stream).unwrap_or_else(|e| {
+                    tracing::warn!("Ignoring invalid message from another launch: {}", e);
+                    Vec::new()
+                });
+                tracing::info!("Another launch was forwarded here with {} argument(s)", args.len());
+
+                if let Some(window) = app.get_window("main") {
+                    let _ = window.unminimize();
+                    let _ = window.show();
+                    let _ = window.set_focus();
+                }
+
+                if let Err(e) = app.emit_all(SECOND_INSTANCE_EVENT, args) {
+                    tracing::error!("Failed to notify frontend of another launch: {}", e);
+                }
+            }
+        });
+
+        Ok(())
+    }
+}
+
+fn read_forwarded_args(stream: TcpStream) -> Result<Vec<String>> {
+    stream.set_read_timeout(Some(FORWARD_TIMEOUT))?;
+
+    let mut message = String::new();
+    stream.take(MAX_FORWARDED_BYTES).read_to_string(&mut message)?;
+    Ok(serde_json::from_str(&message)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_second_lock_sees_running_instance() {
+        let temp_dir = TempDir::new().unwrap();
+
+        let first = lock(temp_dir.path()).unwrap();
+        assert!(matches!(first, Instance::Primary(_)));
+        assert!(matches!(lock(temp_dir.path()).unwrap(), Instance::Running));
+
+        drop(first);
+        assert!(matches!(lock(temp_dir.path()).unwrap(), Instance::Primary(_)));
+    }
+
+    #[test]
+    fn test_instance_dir_is_per_data_dir_and_outside_it() {
+        let first = TempDir::new().unwrap();
+        let second = TempDir::new().unwrap();
+
+        let instance = instance_dir(first.path()).unwrap();
+        assert_eq!(instance, instance_dir(first.path()).unwrap());
+        assert_ne!(instance, instance_dir(second.path()).unwrap());
+        assert!(!instance.starts_with(first.path()));
+    }
+
+    #[test]
+    fn test_forwarded_args_round_trip() {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join(PORT_FILE), listener.local_addr().unwrap().port().to_string()).unwrap();
+
+        let args = vec!["--data-dir".to_string(), "/media/usb".to_string()];
+        forward_to(temp_dir.path(), &args).unwrap();
+
+        let (stream, _) = listener.accept().unwrap();
+        assert_eq!(read_forwarded_args(stream).unwrap(), args);
+    }
+}
@@ -0,0 +1,49 @@
+use std::thread;
+use std::time::Duration;
+
+use tauri::{AppHandle, Manager, Runtime};
+
+use crate::state::AppState;
+use crate::storage::Storage;
+
+/// Emitted to the frontend whenever the vault locks, whatever triggered it
+pub const VAULT_LOCKED_EVENT: &str = "vault-locked";
+
+const IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Locks the vault and sends the frontend back to the unlock screen
+pub fn lock_and_notify<R: Runtime>(app: &AppHandle<R>) {
+    let state = app.state::<AppState>();
+
+    // A vault that was just created has no file yet; without one the next
+    // unlock would be taken as first-time setup and accept any password
+    if let Some(key) = state.get_encryption_key() {
+        let storage = app.state::<Storage>();
+        if !storage.has_apps_file() {
+            if let Err(e) = storage.save_apps(&state.get_apps(), &key) {
+                tracing::warn!("Failed to save vault before locking: {}", e);
+            }
+        }
+    }
+
+    state.lock();
+
+    if let Err(e) = app.emit_all(VAULT_LOCKED_EVENT, ()) {
+        tracing::error!("Failed to notify frontend of lock: {}", e);
+    }
+
+    tracing::info!("Vault locked");
+}
+
+/// Checks in the background whether the vault has been idle for longer than
+/// the configured timeout and locks it
+pub fn spawn_idle_watcher<R: Runtime>(app: AppHandle<R>) {
+    thread::spawn(move || loop {
+        thread::sleep(IDLE_CHECK_INTERVAL);
+
+        if app.state::<AppState>().should_auto_lock() {
+            tracing::info!("Idle timeout reached");
+            lock_and_notify(&app);
+        }
+    });
+}
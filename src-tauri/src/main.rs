@@ -1,17 +1,41 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod aegis;
+mod backup;
 mod commands;
 mod crypto;
+mod export;
+mod fs_store;
 mod google_drive;
+mod import;
+mod instance;
+mod lock;
+mod migration;
 mod otp;
+mod otpauth;
 mod qr;
+mod rotation;
+mod secret;
+mod settings;
 mod state;
 mod storage;
+mod store;
 mod sync;
+mod throttle;
 mod tray;
+mod twofas;
 mod types;
+mod vault;
+mod watcher;
 
+use std::sync::Arc;
+
+use tauri::Manager;
+
+use fs_store::FsStore;
+use instance::Instance;
 use state::AppState;
+use storage::Storage;
 use tray::{create_tray, handle_tray_event, update_tray_menu};
 
 fn main() {
@@ -20,39 +44,119 @@ fn main() {
         .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
         .init();
 
+    let fs_store = match FsStore::from_environment() {
+        Ok(fs_store) => fs_store,
+        Err(e) => {
+            eprintln!("Could not determine the data directory: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    // A second launch on the same data directory would race the first one on
+    // the vault file, so it only hands over its arguments and exits
+    let instance_lock = match instance::acquire(fs_store.data_dir()) {
+        Ok(Instance::Primary(lock)) => lock,
+        Ok(Instance::Running) => {
+            let args: Vec<String> = std::env::args().skip(1).collect();
+            if let Err(e) = instance::forward(fs_store.data_dir(), &args) {
+                eprintln!("Plaxo OTP is already running with {:?}: {}", fs_store.data_dir(), e);
+            }
+            return;
+        }
+        Err(e) => {
+            eprintln!("Could not lock the data directory: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    if let Err(e) = fs_store.adopt_legacy_backup() {
+        tracing::warn!("Could not move the old backup in with the others: {}", e);
+    }
+
+    let data_dir = fs_store.data_dir().to_path_buf();
+    let storage = Storage::new(Arc::new(fs_store));
     let state = AppState::new();
+    state.set_settings(storage.load_settings());
 
     tauri::Builder::<tauri::Wry>::new()
         .manage(state)
+        .manage(storage)
         .system_tray(create_tray())
         .on_system_tray_event(handle_tray_event)
         .on_window_event(|event| match event.event() {
             tauri::WindowEvent::CloseRequested { api, .. } => {
-                let _ = event.window().hide();
-                api.prevent_close();
+                if event.window().state::<AppState>().get_settings().close_to_tray {
+                    let _ = event.window().hide();
+                    api.prevent_close();
+                }
             }
             _ => {}
         })
-        .setup(|app| {
+        .setup(move |app| {
+            instance_lock.listen(app.handle())?;
+            app.manage(instance_lock);
+
             // Initialize tray menu with correct autostart status
             let autostart_enabled = commands::get_autostart_status().unwrap_or(false);
             update_tray_menu(&app.handle(), autostart_enabled);
+            lock::spawn_idle_watcher(app.handle());
+            sync::spawn_sync_scheduler(app.handle());
+            // Not being able to watch only costs the live reload; saves
+            // still refuse to overwrite a file changed elsewhere
+            if let Err(e) = watcher::spawn_vault_watcher(app.handle(), &data_dir) {
+                tracing::warn!("Could not watch the vault file: {}", e);
+            }
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
             commands::has_master_password,
+            commands::get_data_dir,
             commands::verify_master_password,
+            commands::unlock_with_recovery_key,
+            commands::create_recovery_key,
+            commands::has_recovery_key,
+            commands::set_keyfile,
+            commands::is_keyfile_required,
+            commands::set_wipe_after_failures,
+            commands::get_wipe_after_failures,
+            commands::list_backups,
+            commands::restore_backup,
+            commands::check_vault_integrity,
+            commands::repair_vault,
+            commands::get_backup_policy,
+            commands::set_backup_policy,
+            commands::lock_vault,
+            commands::record_activity,
+            commands::get_settings,
+            commands::set_settings,
             commands::get_apps,
             commands::add_app,
             commands::edit_app_name,
             commands::delete_app,
             commands::generate_otp,
+            commands::next_hotp_code,
+            commands::resync_hotp_counter,
             commands::copy_to_clipboard,
             commands::import_2fas_file,
+            commands::import_aegis_file,
+            commands::import_google_migration,
+            commands::import_google_migration_images,
+            commands::import_backup_file,
+            commands::preview_2fas_file,
+            commands::preview_aegis_file,
+            commands::preview_google_migration,
+            commands::preview_google_migration_images,
+            commands::preview_backup_file,
+            commands::commit_import,
             commands::decode_qr_from_image,
             commands::decode_qr_from_clipboard,
+            commands::export_vault,
+            commands::get_otpauth_uri,
+            commands::generate_qr_code,
+            commands::export_migration_qr_codes,
             commands::set_autostart,
             commands::get_autostart_status,
+            commands::change_master_password,
             commands::reset_master_password,
             commands::google_drive_auth_url,
             commands::google_drive_auth_flow,
@@ -0,0 +1,436 @@
+use base64::alphabet;
+use base64::engine::{general_purpose, DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig};
+use base64::Engine as _;
+use prost::Message;
+use totp_rs::Secret;
+
+use crate::otp::OtpGenerator;
+use crate::otpauth::split_label;
+use crate::types::{AppError, OtpAlgorithm, OtpApp, OtpParams, OtpType, QrData, Result, SkippedEntry, DEFAULT_PERIOD};
+
+const MIGRATION_SCHEME: &str = "otpauth-migration://";
+// Google Authenticator itself never puts more than ten accounts in one QR code
+const MIGRATION_BATCH_SIZE: usize = 10;
+
+// Exports seen in the wild come both with and without base64 padding
+const MIGRATION_BASE64: GeneralPurpose = GeneralPurpose::new(
+    &alphabet::STANDARD,
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
+
+/// Google Authenticator "Transfer accounts" payload. Field numbers follow the
+/// MigrationPayload message of the app; enums are kept as raw integers.
+#[derive(Clone, PartialEq, Message)]
+pub struct MigrationPayload {
+    #[prost(message, repeated, tag = "1")]
+    pub otp_parameters: Vec<MigrationOtpParameters>,
+    #[prost(int32, tag = "2")]
+    pub version: i32,
+    #[prost(int32, tag = "3")]
+    pub batch_size: i32,
+    #[prost(int32, tag = "4")]
+    pub batch_index: i32,
+    #[prost(int32, tag = "5")]
+    pub batch_id: i32,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct MigrationOtpParameters {
+    #[prost(bytes = "vec", tag = "1")]
+    pub secret: Vec<u8>,
+    #[prost(string, tag = "2")]
+    pub name: String,
+    #[prost(string, tag = "3")]
+    pub issuer: String,
+    #[prost(int32, tag = "4")]
+    pub algorithm: i32,
+    #[prost(int32, tag = "5")]
+    pub digits: i32,
+    #[prost(int32, tag = "6")]
+    pub otp_type: i32,
+    #[prost(int64, tag = "7")]
+    pub counter: i64,
+}
+
+/// One decoded QR code of a (possibly multi-part) export
+#[derive(Debug)]
+pub struct MigrationBatch {
+    pub batch_id: i32,
+    pub batch_index: i32,
+    pub batch_size: i32,
+    pub entries: Vec<QrData>,
+    /// Accounts that cannot be imported, with the reason
+    pub skipped: Vec<SkippedEntry>,
+}
+
+/// The accounts of one or more complete exports
+#[derive(Debug, Default)]
+pub struct MigrationExport {
+    pub entries: Vec<QrData>,
+    pub skipped: Vec<SkippedEntry>,
+}
+
+pub fn is_migration_uri(content: &str) -> bool {
+    let content = content.trim();
+    content.get(..MIGRATION_SCHEME.len())
+        .is_some_and(|prefix| prefix.eq_ignore_ascii_case(MIGRATION_SCHEME))
+}
+
+pub fn decode_migration_uri(uri: &str) -> Result<MigrationBatch> {
+    let uri = uri.trim();
+
+    if !is_migration_uri(uri) {
+        return Err(AppError::QrCode("Not a Google Authenticator export QR code".to_string()));
+    }
+
+    let query = uri
+        .split_once('?')
+        .map(|(_, query)| query)
+        .unwrap_or_default();
+
+    let data = query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| key.eq_ignore_ascii_case("data"))
+        .map(|(_, value)| value)
+        .ok_or_else(|| AppError::QrCode("Export QR code has no data".to_string()))?;
+
+    // A '+' that went through form decoding comes back as a space
+    let data = urlencoding::decode(data)
+        .map_err(|_| AppError::QrCode("Invalid encoding in export QR code".to_string()))?
+        .replace(' ', "+");
+
+    let bytes = MIGRATION_BASE64
+        .decode(data.as_bytes())
+        .map_err(|_| AppError::QrCode("Invalid base64 in export QR code".to_string()))?;
+
+    let payload = MigrationPayload::decode(bytes.as_slice())
+        .map_err(|e| AppError::QrCode(format!("Invalid export payload: {}", e)))?;
+
+    let mut entries = Vec::with_capacity(payload.otp_parameters.len());
+    let mut skipped = Vec::new();
+    for parameters in payload.otp_parameters {
+        let name = if parameters.name.is_empty() { "Unknown".to_string() } else { parameters.name.clone() };
+        match to_qr_data(parameters) {
+            Ok(entry) => entries.push(entry),
+            Err(AppError::QrCode(reason)) => skipped.push(SkippedEntry { name, reason }),
+            Err(e) => skipped.push(SkippedEntry { name, reason: e.to_string() }),
+        }
+    }
+
+    Ok(MigrationBatch {
+        batch_id: payload.batch_id,
+        batch_index: payload.batch_index,
+        // Single-QR exports from older app versions leave the batch fields unset
+        batch_size: payload.batch_size.max(1),
+        entries,
+        skipped,
+    })
+}
+
+/// Decodes every part of one or more exports and checks that no part of a
+/// batch is missing, so a partial scan never looks like a complete import.
+pub fn decode_migration_uris(uris: &[String]) -> Result<MigrationExport> {
+    let mut batches: Vec<MigrationBatch> = Vec::new();
+
+    for uri in uris {
+        let batch = decode_migration_uri(uri)?;
+
+        // The same QR code scanned twice must not import its accounts twice
+        let already_seen = batches
+            .iter()
+            .any(|b| b.batch_id == batch.batch_id && b.batch_index == batch.batch_index);
+        if already_seen {
+            tracing::info!("Ignoring repeated part {} of batch {}", batch.batch_index + 1, batch.batch_id);
+            continue;
+        }
+
+        batches.push(batch);
+    }
+
+    for batch in &batches {
+        let missing: Vec<String> = (0..batch.batch_size)
+            .filter(|index| {
+                !batches
+                    .iter()
+                    .any(|b| b.batch_id == batch.batch_id && b.batch_index == *index)
+            })
+            .map(|index| (index + 1).to_string())
+            .collect();
+
+        if !missing.is_empty() {
+            return Err(AppError::QrCode(format!(
+                "Export is incomplete: missing QR code(s) {} of {}",
+                missing.join(", "),
+                batch.batch_size
+            )));
+        }
+    }
+
+    batches.sort_by_key(|b| (b.batch_id, b.batch_index));
+    let mut export = MigrationExport::default();
+    for batch in batches {
+        export.entries.extend(batch.entries);
+        export.skipped.extend(batch.skipped);
+    }
+    Ok(export)
+}
+
+/// Encodes entries as one or more `otpauth-migration://` URIs that Google
+/// Authenticator's "Transfer accounts > Import" can scan in sequence.
+pub fn encode_migration_uris(apps: &[OtpApp]) -> Result<Vec<String>> {
+    if apps.is_empty() {
+        return Err(AppError::QrCode("No entries selected for export".to_string()));
+    }
+
+    let otp_generator = OtpGenerator::new();
+    let parameters = apps
+        .iter()
+        .map(|app| to_migration_parameters(app, &otp_generator))
+        .collect::<Result<Vec<_>>>()?;
+
+    let batch_size = (parameters.len() + MIGRATION_BATCH_SIZE - 1) / MIGRATION_BATCH_SIZE;
+    // The app only uses the id to tell batches apart, any non-negative value works
+    let batch_id = rand::random::<i32>() & i32::MAX;
+
+    let uris = parameters
+        .chunks(MIGRATION_BATCH_SIZE)
+        .enumerate()
+        .map(|(index, chunk)| {
+            let payload = MigrationPayload {
+                otp_parameters: chunk.to_vec(),
+                version: 1,
+                batch_size: batch_size as i32,
+                batch_index: index as i32,
+                batch_id,
+            };
+            let data = general_purpose::STANDARD.encode(payload.encode_to_vec());
+            format!("{}offline?data={}", MIGRATION_SCHEME, urlencoding::encode(&data))
+        })
+        .collect();
+
+    Ok(uris)
+}
+
+fn to_migration_parameters(app: &OtpApp, otp_generator: &OtpGenerator) -> Result<MigrationOtpParameters> {
+    if app.otp_type == OtpType::Steam {
+        return Err(AppError::QrCode(format!(
+            "{} is a Steam Guard entry, which Google Authenticator does not support",
+            app.name
+        )));
+    }
+
+    // The payload has no period field, every account is assumed to use 30 seconds
+    if app.otp_type == OtpType::Totp && app.period != DEFAULT_PERIOD {
+        return Err(AppError::QrCode(format!(
+            "{} uses a {}-second period, which Google Authenticator exports cannot represent",
+            app.name, app.period
+        )));
+    }
+
+    let digits = match app.digits {
+        6 => 1,
+        8 => 2,
+        other => {
+            return Err(AppError::QrCode(format!(
+                "{} uses {} digits, which Google Authenticator exports cannot represent",
+                app.name, other
+            )))
+        }
+    };
+
+    let secret = Secret::Encoded(otp_generator.clean_secret(&app.secret))
+        .to_bytes()
+        .map_err(|e| AppError::InvalidSecret(format!("Invalid Base32 secret for {}: {}", app.name, e)))?;
+
+    // Same fallback as build_otpauth_uri: the entry name acts as issuer next to an account
+    let issuer = app.issuer.clone()
+        .or_else(|| app.account.as_ref().map(|_| app.name.clone()))
+        .unwrap_or_default();
+
+    Ok(MigrationOtpParameters {
+        secret,
+        name: app.account.clone().unwrap_or_else(|| app.name.clone()),
+        issuer,
+        algorithm: match app.algorithm {
+            OtpAlgorithm::Sha1 => 1,
+            OtpAlgorithm::Sha256 => 2,
+            OtpAlgorithm::Sha512 => 3,
+        },
+        digits,
+        otp_type: match app.otp_type {
+            OtpType::Hotp => 1,
+            OtpType::Totp | OtpType::Steam => 2,
+        },
+        counter: app.counter as i64,
+    })
+}
+
+fn to_qr_data(parameters: MigrationOtpParameters) -> Result<QrData> {
+    let algorithm = match parameters.algorithm {
+        0 | 1 => OtpAlgorithm::Sha1,
+        2 => OtpAlgorithm::Sha256,
+        3 => OtpAlgorithm::Sha512,
+        other => {
+            return Err(AppError::QrCode(format!(
+                "Unsupported algorithm {} for {}",
+                other, parameters.name
+            )))
+        }
+    };
+
+    let digits = match parameters.digits {
+        2 => 8,
+        _ => 6,
+    };
+
+    let otp_type = match parameters.otp_type {
+        1 => OtpType::Hotp,
+        _ => OtpType::Totp,
+    };
+
+    if parameters.secret.is_empty() {
+        return Err(AppError::QrCode(format!("Empty secret for {}", parameters.name)));
+    }
+    let secret = Secret::Raw(parameters.secret).to_encoded().to_string();
+
+    let (label_issuer, account) = split_label(&parameters.name);
+    let issuer = Some(parameters.issuer.trim().to_string())
+        .filter(|i| !i.is_empty())
+        .or(label_issuer);
+    let account = Some(account).filter(|a| !a.is_empty());
+
+    let name = issuer.clone()
+        .or_else(|| account.clone())
+        .unwrap_or_else(|| "Unknown".to_string());
+
+    Ok(QrData {
+        name,
+        secret,
+        params: OtpParams {
+            otp_type,
+            algorithm,
+            digits,
+            period: DEFAULT_PERIOD,
+            counter: parameters.counter.max(0) as u64,
+            issuer,
+            account,
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode_uri(payload: &MigrationPayload) -> String {
+        let data = general_purpose::STANDARD.encode(payload.encode_to_vec());
+        format!("otpauth-migration://offline?data={}", urlencoding::encode(&data))
+    }
+
+    fn account(name: &str, issuer: &str) -> MigrationOtpParameters {
+        MigrationOtpParameters {
+            secret: b"12345678901234567890".to_vec(),
+            name: name.to_string(),
+            issuer: issuer.to_string(),
+            algorithm: 2,
+            digits: 2,
+            otp_type: 2,
+            counter: 0,
+        }
+    }
+
+    #[test]
+    fn test_non_ascii_content_is_not_a_migration_uri() {
+        let almost = format!("{}é", &MIGRATION_SCHEME[..MIGRATION_SCHEME.len() - 1]);
+        assert!(!is_migration_uri(&almost));
+        assert!(!is_migration_uri("é"));
+    }
+
+    #[test]
+    fn test_decode_single_batch() {
+        let payload = MigrationPayload {
+            otp_parameters: vec![account("ACME:alice@example.com", "ACME"), account("bob", "")],
+            version: 1,
+            batch_size: 1,
+            batch_index: 0,
+            batch_id: 7,
+        };
+
+        let entries = decode_migration_uris(&[encode_uri(&payload)]).unwrap().entries;
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].secret, "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+        assert_eq!(entries[0].name, "ACME");
+        assert_eq!(entries[0].params.account.as_deref(), Some("alice@example.com"));
+        assert_eq!(entries[0].params.algorithm, OtpAlgorithm::Sha256);
+        assert_eq!(entries[0].params.digits, 8);
+        assert_eq!(entries[1].name, "bob");
+    }
+
+    #[test]
+    fn test_unreadable_accounts_are_reported() {
+        let mut unsupported = account("Legacy", "");
+        unsupported.algorithm = 4;
+        let payload = MigrationPayload {
+            otp_parameters: vec![account("bob", ""), unsupported],
+            version: 1,
+            batch_size: 1,
+            batch_index: 0,
+            batch_id: 3,
+        };
+
+        let export = decode_migration_uris(&[encode_uri(&payload)]).unwrap();
+        assert_eq!(export.entries.len(), 1);
+        assert_eq!(export.skipped.len(), 1);
+        assert_eq!(export.skipped[0].name, "Legacy");
+        assert!(export.skipped[0].reason.contains("Unsupported algorithm"));
+    }
+
+    #[test]
+    fn test_decode_multi_part_batch() {
+        let part = |index: i32, name: &str| MigrationPayload {
+            otp_parameters: vec![account(name, "")],
+            version: 1,
+            batch_size: 2,
+            batch_index: index,
+            batch_id: 42,
+        };
+
+        let first = encode_uri(&part(0, "first"));
+        let second = encode_uri(&part(1, "second"));
+
+        assert!(decode_migration_uris(std::slice::from_ref(&second)).is_err());
+
+        let entries = decode_migration_uris(&[second, first.clone(), first]).unwrap().entries;
+        let names: Vec<&str> = entries.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, vec!["first", "second"]);
+    }
+
+    #[test]
+    fn test_encode_round_trip() {
+        let apps: Vec<OtpApp> = (0..12)
+            .map(|i| {
+                let mut app = OtpApp::new(format!("Service {}", i), "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ".to_string());
+                app.account = Some(format!("user{}@example.com", i));
+                app.algorithm = OtpAlgorithm::Sha256;
+                app.digits = 8;
+                app
+            })
+            .collect();
+
+        let uris = encode_migration_uris(&apps).unwrap();
+        assert_eq!(uris.len(), 2);
+
+        let entries = decode_migration_uris(&uris).unwrap().entries;
+        assert_eq!(entries.len(), 12);
+        assert_eq!(entries[11].name, "Service 11");
+        assert_eq!(entries[11].params.account.as_deref(), Some("user11@example.com"));
+        assert_eq!(entries[11].secret, *apps[11].secret);
+        assert_eq!(entries[11].params.algorithm, OtpAlgorithm::Sha256);
+        assert_eq!(entries[11].params.digits, 8);
+
+        let mut long_period = apps[0].clone();
+        long_period.period = 60;
+        assert!(encode_migration_uris(&[long_period]).is_err());
+    }
+}
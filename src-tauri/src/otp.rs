@@ -1,6 +1,11 @@
 use totp_rs::{Algorithm, TOTP, Secret};
 
-use crate::types::{AppError, Result};
+use crate::types::{AppError, OtpAlgorithm, OtpApp, OtpParams, OtpType, Result, DEFAULT_PERIOD};
+
+// How far ahead of the stored counter a HOTP resync looks for a matching code
+const HOTP_RESYNC_WINDOW: u64 = 100;
+// Steam Guard codes are always 5 characters of Steam's alphabet, SHA1 / 30 seconds
+pub const STEAM_DIGITS: usize = 5;
 
 pub struct OtpGenerator;
 
@@ -9,34 +14,130 @@ impl OtpGenerator {
         Self
     }
 
-    pub fn generate_code(&self, secret: &str) -> Result<String> {
-        let clean_secret = self.clean_secret(secret);
-        
-        if clean_secret.is_empty() {
-            return Err(AppError::InvalidSecret("Empty secret".to_string()));
+    pub fn generate_for_app(&self, app: &OtpApp) -> Result<String> {
+        match app.otp_type {
+            OtpType::Totp => self.generate_totp(&app.secret, app.algorithm, app.digits, app.period),
+            OtpType::Hotp => self.generate_hotp(&app.secret, app.algorithm, app.digits, app.counter),
+            OtpType::Steam => self.generate_steam(&app.secret),
         }
+    }
+
+    pub fn generate_steam(&self, secret: &str) -> Result<String> {
+        let secret_bytes = self.decode_secret(secret)?;
+        let totp = TOTP::new_unchecked(Algorithm::Steam, STEAM_DIGITS, 1, DEFAULT_PERIOD, secret_bytes);
         
-        let secret_bytes = Secret::Encoded(clean_secret.clone())
-            .to_bytes()
-            .map_err(|e| AppError::InvalidSecret(format!("Invalid Base32 secret: {}", e)))?;
+        totp.generate_current()
+            .map_err(|e| AppError::InvalidSecret(format!("Failed to generate code: {}", e)))
+    }
+
+    pub fn generate_hotp(
+        &self,
+        secret: &str,
+        algorithm: OtpAlgorithm,
+        digits: usize,
+        counter: u64,
+    ) -> Result<String> {
+        // With a 1-second step the TOTP time counter is the timestamp itself,
+        // which makes generate(counter) exactly RFC 4226 HOTP
+        let hotp = self.build_totp(secret, algorithm, digits, 1)?;
+        Ok(hotp.generate(counter))
+    }
+
+    /// Looks for `code` in the window after `counter` and returns the counter
+    /// that produced it, so the stored value can be moved past it.
+    pub fn find_hotp_counter(
+        &self,
+        secret: &str,
+        algorithm: OtpAlgorithm,
+        digits: usize,
+        counter: u64,
+        code: &str,
+    ) -> Result<Option<u64>> {
+        let hotp = self.build_totp(secret, algorithm, digits, 1)?;
+        let code = code.trim();
+        
+        Ok((counter..counter.saturating_add(HOTP_RESYNC_WINDOW))
+            .find(|candidate| hotp.generate(*candidate) == code))
+    }
+
+    pub fn generate_totp(
+        &self,
+        secret: &str,
+        algorithm: OtpAlgorithm,
+        digits: usize,
+        period: u64,
+    ) -> Result<String> {
+        let totp = self.build_totp(secret, algorithm, digits, period)?;
+        
+        let code = totp
+            .generate_current()
+            .map_err(|e| AppError::InvalidSecret(format!("Failed to generate code: {}", e)))?;
+        
+        Ok(code)
+    }
+
+    fn build_totp(
+        &self,
+        secret: &str,
+        algorithm: OtpAlgorithm,
+        digits: usize,
+        period: u64,
+    ) -> Result<TOTP> {
+        self.validate_params(digits, period)?;
+        
+        let secret_bytes = self.decode_secret(secret)?;
+        
+        let algorithm = to_totp_algorithm(algorithm);
         
         // Try to create TOTP with validation
-        let totp = match TOTP::new(Algorithm::SHA1, 6, 1, 30, secret_bytes.clone()) {
+        let totp = match TOTP::new(algorithm, digits, 1, period, secret_bytes.clone()) {
             Ok(t) => t,
             Err(_) => {
                 // If it fails due to size validation, use unchecked version
-                TOTP::new_unchecked(Algorithm::SHA1, 6, 1, 30, secret_bytes)
+                TOTP::new_unchecked(algorithm, digits, 1, period, secret_bytes)
             }
         };
         
-        let code = totp
-            .generate_current()
-            .map_err(|e| AppError::InvalidSecret(format!("Failed to generate code: {}", e)))?;
+        Ok(totp)
+    }
+
+    fn decode_secret(&self, secret: &str) -> Result<Vec<u8>> {
+        let clean_secret = self.clean_secret(secret);
         
-        Ok(code)
+        if clean_secret.is_empty() {
+            return Err(AppError::InvalidSecret("Empty secret".to_string()));
+        }
+        
+        Secret::Encoded(clean_secret)
+            .to_bytes()
+            .map_err(|e| AppError::InvalidSecret(format!("Invalid Base32 secret: {}", e)))
+    }
+
+    /// Steam entries have fixed parameters, everything else goes through `validate_params`
+    pub fn validate_entry_params(&self, params: &OtpParams) -> Result<()> {
+        match params.otp_type {
+            OtpType::Steam => Ok(()),
+            OtpType::Totp | OtpType::Hotp => self.validate_params(params.digits, params.period),
+        }
+    }
+
+    pub fn validate_params(&self, digits: usize, period: u64) -> Result<()> {
+        if !(6..=8).contains(&digits) {
+            return Err(AppError::InvalidSecret(
+                format!("Número de dígitos inválido: {}. Use entre 6 e 8", digits)
+            ));
+        }
+        
+        if period == 0 || period > 300 {
+            return Err(AppError::InvalidSecret(
+                format!("Período inválido: {}s. Use entre 1 e 300 segundos", period)
+            ));
+        }
+        
+        Ok(())
     }
 
-    fn clean_secret(&self, secret: &str) -> String {
+    pub fn clean_secret(&self, secret: &str) -> String {
         secret
             .replace(' ', "")
             .replace('-', "")
@@ -76,6 +177,14 @@ impl OtpGenerator {
     }
 }
 
+fn to_totp_algorithm(algorithm: OtpAlgorithm) -> Algorithm {
+    match algorithm {
+        OtpAlgorithm::Sha1 => Algorithm::SHA1,
+        OtpAlgorithm::Sha256 => Algorithm::SHA256,
+        OtpAlgorithm::Sha512 => Algorithm::SHA512,
+    }
+}
+
 impl Default for OtpGenerator {
     fn default() -> Self {
         Self::new()
@@ -85,17 +194,60 @@ impl Default for OtpGenerator {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::types::{DEFAULT_DIGITS, DEFAULT_PERIOD};
 
     #[test]
     fn test_generate_code() {
         let generator = OtpGenerator::new();
         let secret = "JBSWY3DPEHPK3PXP";
         
-        let code = generator.generate_code(secret).unwrap();
+        let code = generator
+            .generate_totp(secret, OtpAlgorithm::Sha1, DEFAULT_DIGITS, DEFAULT_PERIOD)
+            .unwrap();
         assert_eq!(code.len(), 6);
         assert!(code.chars().all(|c| c.is_ascii_digit()));
     }
 
+    #[test]
+    fn test_generate_totp_with_params() {
+        let generator = OtpGenerator::new();
+        // RFC 6238 test vector for SHA256 at T = 59
+        let secret = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZA";
+        
+        let totp = generator
+            .build_totp(secret, OtpAlgorithm::Sha256, 8, 30)
+            .unwrap();
+        assert_eq!(totp.generate(59), "46119246");
+        
+        assert!(generator.build_totp(secret, OtpAlgorithm::Sha1, 5, 30).is_err());
+        assert!(generator.build_totp(secret, OtpAlgorithm::Sha1, 6, 0).is_err());
+    }
+
+    #[test]
+    fn test_generate_hotp() {
+        let generator = OtpGenerator::new();
+        // RFC 4226 appendix D test vectors
+        let secret = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+        
+        assert_eq!(generator.generate_hotp(secret, OtpAlgorithm::Sha1, 6, 0).unwrap(), "755224");
+        assert_eq!(generator.generate_hotp(secret, OtpAlgorithm::Sha1, 6, 1).unwrap(), "287082");
+        assert_eq!(generator.generate_hotp(secret, OtpAlgorithm::Sha1, 6, 9).unwrap(), "520489");
+        
+        let found = generator
+            .find_hotp_counter(secret, OtpAlgorithm::Sha1, 6, 2, "520489")
+            .unwrap();
+        assert_eq!(found, Some(9));
+    }
+
+    #[test]
+    fn test_generate_steam() {
+        let generator = OtpGenerator::new();
+        
+        let code = generator.generate_steam("JBSWY3DPEHPK3PXP").unwrap();
+        assert_eq!(code.len(), STEAM_DIGITS);
+        assert!(code.chars().all(|c| "23456789BCDFGHJKMNPQRTVWXY".contains(c)));
+    }
+
     #[test]
     fn test_clean_secret() {
         let generator = OtpGenerator::new();
@@ -0,0 +1,243 @@
+use std::borrow::Cow;
+
+use crate::otp::OtpGenerator;
+use crate::types::{AppError, OtpAlgorithm, OtpApp, OtpParams, OtpType, QrData, Result};
+
+const OTPAUTH_SCHEME: &str = "otpauth://";
+
+pub fn is_otpauth_uri(content: &str) -> bool {
+    // Decoded QR text may put a multi-byte character across the prefix
+    content.get(..OTPAUTH_SCHEME.len())
+        .is_some_and(|prefix| prefix.eq_ignore_ascii_case(OTPAUTH_SCHEME))
+}
+
+/// Parses a Key URI (`otpauth://TYPE/LABEL?PARAMETERS`) as produced by
+/// Google Authenticator and most providers' enrollment QR codes.
+pub fn parse_otpauth_uri(uri: &str) -> Result<QrData> {
+    let uri = uri.trim();
+
+    if !is_otpauth_uri(uri) {
+        return Err(AppError::QrCode("Not a valid OTP QR code".to_string()));
+    }
+
+    let rest = &uri[OTPAUTH_SCHEME.len()..];
+    let (otp_type, rest) = rest
+        .split_once('/')
+        .ok_or_else(|| AppError::QrCode("Missing OTP type in QR code".to_string()))?;
+
+    let otp_type = match otp_type.to_ascii_lowercase().as_str() {
+        "totp" => OtpType::Totp,
+        "hotp" => OtpType::Hotp,
+        // Used by Aegis and other apps for Steam Guard entries
+        "steam" => OtpType::Steam,
+        other => return Err(AppError::QrCode(format!("Unsupported OTP type: {}", other))),
+    };
+
+    let (label, query) = rest.split_once('?').unwrap_or((rest, ""));
+    let (label_issuer, account) = split_label(&percent_decode(label));
+
+    let mut params = OtpParams {
+        otp_type,
+        ..OtpParams::default()
+    };
+    let mut secret = None;
+    let mut issuer = None;
+
+    for pair in query.split('&').filter(|p| !p.is_empty()) {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        let value = percent_decode(value);
+
+        match key.to_ascii_lowercase().as_str() {
+            "secret" => secret = Some(value.replace(' ', "").trim_end_matches('=').to_uppercase()),
+            "issuer" => issuer = Some(value.trim().to_string()),
+            "algorithm" => params.algorithm = parse_algorithm(&value)?,
+            "digits" => params.digits = parse_number(&value, "digits")?,
+            "period" => params.period = parse_number(&value, "period")?,
+            "counter" => params.counter = parse_number(&value, "counter")?,
+            _ => tracing::debug!("Ignoring unknown otpauth parameter: {}", key),
+        }
+    }
+
+    let secret = secret
+        .filter(|s| !s.is_empty())
+        .ok_or_else(|| AppError::QrCode("QR code does not contain secret key".to_string()))?;
+
+    // The issuer parameter is the one the spec recommends; the label prefix is the legacy form
+    params.issuer = issuer.filter(|i| !i.is_empty()).or(label_issuer);
+    params.account = Some(account).filter(|a| !a.is_empty());
+
+    let name = params.issuer.clone()
+        .or_else(|| params.account.clone())
+        .unwrap_or_else(|| "Unknown".to_string());
+
+    Ok(QrData { name, secret, params })
+}
+
+/// Builds the canonical Key URI for an entry, the inverse of `parse_otpauth_uri`.
+/// Every parameter is written explicitly so authenticators that assume other
+/// defaults still produce the same codes.
+pub fn build_otpauth_uri(app: &OtpApp) -> String {
+    let secret = OtpGenerator::new().clean_secret(&app.secret);
+    let account = app.account.as_deref()
+        .filter(|a| !a.is_empty())
+        .unwrap_or(&app.name);
+
+    // Entries added by hand only have a name; it doubles as the issuer when
+    // there is a separate account to show next to it
+    let issuer = app.issuer.as_deref()
+        .filter(|i| !i.is_empty())
+        .or_else(|| (account != app.name).then_some(app.name.as_str()));
+
+    let label = match issuer {
+        Some(issuer) => format!("{}:{}", urlencoding::encode(issuer), urlencoding::encode(account)),
+        None => urlencoding::encode(account).into_owned(),
+    };
+
+    let mut uri = format!("otpauth://{}/{}?secret={}", app.otp_type.as_str(), label, secret);
+
+    if let Some(issuer) = issuer {
+        uri.push_str(&format!("&issuer={}", urlencoding::encode(issuer)));
+    }
+
+    uri.push_str(&format!("&algorithm={}&digits={}", app.algorithm.as_str(), app.digits));
+
+    match app.otp_type {
+        OtpType::Totp | OtpType::Steam => uri.push_str(&format!("&period={}", app.period)),
+        OtpType::Hotp => uri.push_str(&format!("&counter={}", app.counter)),
+    }
+
+    uri
+}
+
+pub fn split_label(label: &str) -> (Option<String>, String) {
+    match label.split_once(':') {
+        Some((issuer, account)) => {
+            let issuer = issuer.trim();
+            let issuer = (!issuer.is_empty()).then(|| issuer.to_string());
+            (issuer, account.trim().to_string())
+        }
+        None => (None, label.trim().to_string()),
+    }
+}
+
+fn percent_decode(value: &str) -> String {
+    urlencoding::decode(value)
+        .unwrap_or(Cow::Borrowed(value))
+        .into_owned()
+}
+
+fn parse_algorithm(value: &str) -> Result<OtpAlgorithm> {
+    match value.trim().to_ascii_uppercase().as_str() {
+        "SHA1" => Ok(OtpAlgorithm::Sha1),
+        "SHA256" => Ok(OtpAlgorithm::Sha256),
+        "SHA512" => Ok(OtpAlgorithm::Sha512),
+        other => Err(AppError::QrCode(format!("Unsupported algorithm: {}", other))),
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(value: &str, field: &str) -> Result<T> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| AppError::QrCode(format!("Invalid {} in QR code: {}", field, value)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_non_ascii_content_is_not_a_uri() {
+        assert!(!is_otpauth_uri("otpauth:/é"));
+        assert!(!is_otpauth_uri("ótpauth://totp/x"));
+        assert!(is_otpauth_uri("OTPAUTH://totp/é"));
+    }
+
+    #[test]
+    fn test_parse_full_uri() {
+        let uri = "otpauth://totp/ACME%20Co:john.doe@email.com?secret=HXDMVJECJJWSRB3HWIZR4IFUGFTMXBOZ&issuer=ACME%20Co&algorithm=SHA256&digits=8&period=60";
+
+        let data = parse_otpauth_uri(uri).unwrap();
+        assert_eq!(data.name, "ACME Co");
+        assert_eq!(data.secret, "HXDMVJECJJWSRB3HWIZR4IFUGFTMXBOZ");
+        assert_eq!(data.params.issuer.as_deref(), Some("ACME Co"));
+        assert_eq!(data.params.account.as_deref(), Some("john.doe@email.com"));
+        assert_eq!(data.params.algorithm, OtpAlgorithm::Sha256);
+        assert_eq!(data.params.digits, 8);
+        assert_eq!(data.params.period, 60);
+    }
+
+    #[test]
+    fn test_parse_is_order_and_case_insensitive() {
+        let uri = "OTPAUTH://TOTP/alice%40example.com?Issuer=Example&SECRET=jbswy3dpehpk3pxp&Algorithm=sha512";
+
+        let data = parse_otpauth_uri(uri).unwrap();
+        assert_eq!(data.secret, "JBSWY3DPEHPK3PXP");
+        assert_eq!(data.params.issuer.as_deref(), Some("Example"));
+        assert_eq!(data.params.account.as_deref(), Some("alice@example.com"));
+        assert_eq!(data.params.algorithm, OtpAlgorithm::Sha512);
+        assert_eq!(data.params.digits, 6);
+        assert_eq!(data.params.period, 30);
+    }
+
+    #[test]
+    fn test_parse_label_issuer_and_encoded_colon() {
+        let uri = "otpauth://totp/Example%3A%20alice@google.com?secret=JBSWY3DPEHPK3PXP";
+
+        let data = parse_otpauth_uri(uri).unwrap();
+        assert_eq!(data.params.issuer.as_deref(), Some("Example"));
+        assert_eq!(data.params.account.as_deref(), Some("alice@google.com"));
+    }
+
+    #[test]
+    fn test_parse_hotp_counter() {
+        let uri = "otpauth://hotp/Gateway:bob?secret=JBSWY3DPEHPK3PXP&counter=42";
+
+        let data = parse_otpauth_uri(uri).unwrap();
+        assert_eq!(data.params.otp_type, OtpType::Hotp);
+        assert_eq!(data.params.counter, 42);
+    }
+
+    #[test]
+    fn test_build_uri_round_trip() {
+        let mut app = OtpApp::new("ACME Co".to_string(), "jbsw y3dp ehpk 3pxp".to_string());
+        app.account = Some("john.doe@email.com".to_string());
+        app.algorithm = OtpAlgorithm::Sha512;
+        app.digits = 8;
+        app.period = 60;
+
+        let uri = build_otpauth_uri(&app);
+        assert_eq!(
+            uri,
+            "otpauth://totp/ACME%20Co:john.doe%40email.com?secret=JBSWY3DPEHPK3PXP&issuer=ACME%20Co&algorithm=SHA512&digits=8&period=60"
+        );
+
+        let data = parse_otpauth_uri(&uri).unwrap();
+        assert_eq!(data.name, app.name);
+        assert_eq!(data.params.account, app.account);
+        assert_eq!(data.params.algorithm, app.algorithm);
+        assert_eq!(data.params.digits, app.digits);
+        assert_eq!(data.params.period, app.period);
+    }
+
+    #[test]
+    fn test_build_hotp_uri_without_account() {
+        let mut app = OtpApp::new("Gateway".to_string(), "JBSWY3DPEHPK3PXP".to_string());
+        app.otp_type = OtpType::Hotp;
+        app.counter = 7;
+
+        let uri = build_otpauth_uri(&app);
+        assert_eq!(
+            uri,
+            "otpauth://hotp/Gateway?secret=JBSWY3DPEHPK3PXP&algorithm=SHA1&digits=6&counter=7"
+        );
+    }
+
+    #[test]
+    fn test_invalid_uris() {
+        assert!(parse_otpauth_uri("not an otpauth url").is_err());
+        assert!(parse_otpauth_uri("otpauth://totp/Example?issuer=Example").is_err());
+        assert!(parse_otpauth_uri("otpauth://motp/Example?secret=JBSWY3DPEHPK3PXP").is_err());
+        assert!(parse_otpauth_uri("otpauth://totp/Example?secret=JBSWY3DPEHPK3PXP&digits=six").is_err());
+    }
+}
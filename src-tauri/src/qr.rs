@@ -1,7 +1,17 @@
-use image::DynamicImage;
+use base64::{Engine as _, engine::general_purpose};
+use image::{DynamicImage, GrayImage, Luma};
+use qrcode::render::svg;
+use qrcode::{Color, QrCode};
 use rqrr::PreparedImage;
 
-use crate::types::{AppError, Result};
+use crate::migration::is_migration_uri;
+use crate::otpauth::{is_otpauth_uri, parse_otpauth_uri};
+use crate::types::{AppError, OtpParams, QrCodeImage, QrData, QrImageFormat, Result};
+
+// Pixels per module in generated PNGs, comfortably scannable from a screen
+const PNG_MODULE_SIZE: u32 = 8;
+const QUIET_ZONE_MODULES: u32 = 4;
+const SVG_MIN_SIZE: u32 = 256;
 
 pub struct QrCodeReader;
 
@@ -10,14 +20,21 @@ impl QrCodeReader {
         Self
     }
 
-    pub fn decode_from_image(&self, image_data: &[u8]) -> Result<crate::types::QrData> {
+    pub fn decode_from_image(&self, image_data: &[u8]) -> Result<QrData> {
+        let content = self.decode_content_from_image(image_data)?;
+        self.parse_content(&content)
+    }
+
+    /// Returns the raw text of the QR code, for callers that handle
+    /// payloads other than a single account (e.g. export batches)
+    pub fn decode_content_from_image(&self, image_data: &[u8]) -> Result<String> {
         let img = image::load_from_memory(image_data)
             .map_err(|_| AppError::QrCode("Invalid image format".to_string()))?;
         
         self.decode_from_dynamic_image(img)
     }
 
-    fn decode_from_dynamic_image(&self, img: DynamicImage) -> Result<crate::types::QrData> {
+    fn decode_from_dynamic_image(&self, img: DynamicImage) -> Result<String> {
         let gray_img = img.to_luma8();
         let mut prepared = PreparedImage::prepare(gray_img);
         let grids = prepared.detect_grids();
@@ -30,70 +47,83 @@ impl QrCodeReader {
             .decode()
             .map_err(|_| AppError::QrCode("Failed to decode QR code".to_string()))?;
         
-        if content.starts_with("otpauth://totp/") {
-            let secret = self.extract_secret_from_otpauth(&content)?;
-            let name = self.extract_name_from_otpauth(&content).unwrap_or_else(|_| "Unknown".to_string());
-            Ok(crate::types::QrData { name, secret })
-        } else {
-            Ok(crate::types::QrData { name: "Unknown".to_string(), secret: content })
-        }
+        Ok(content)
     }
 
-    fn extract_secret_from_otpauth(&self, content: &str) -> Result<String> {
-        if !content.starts_with("otpauth://totp/") {
-            return Err(AppError::QrCode("Not a valid OTP QR code".to_string()));
+    fn parse_content(&self, content: &str) -> Result<QrData> {
+        if is_migration_uri(content) {
+            return Err(AppError::QrCode(
+                "This is a Google Authenticator export QR code, add it with Import > Google Authenticator instead".to_string()
+            ));
         }
         
-        let secret_start = content
-            .find("secret=")
-            .ok_or_else(|| AppError::QrCode("QR code does not contain secret key".to_string()))?;
-        
-        let secret_part = &content[secret_start + 7..];
-        let secret = if let Some(secret_end) = secret_part.find('&') {
-            &secret_part[..secret_end]
+        if is_otpauth_uri(content) {
+            parse_otpauth_uri(content)
         } else {
-            secret_part
-        };
-        
-        if secret.is_empty() {
-            return Err(AppError::QrCode("Empty secret in QR code".to_string()));
+            Ok(QrData {
+                name: "Unknown".to_string(),
+                secret: content.to_string(),
+                params: OtpParams::default(),
+            })
         }
-        
-        Ok(secret.to_string())
     }
+}
 
-    fn extract_name_from_otpauth(&self, content: &str) -> Result<String> {
-        if !content.starts_with("otpauth://totp/") {
-            return Err(AppError::QrCode("Not a valid OTP QR code".to_string()));
-        }
-        
-        let name_start = "otpauth://totp/".len();
-        let name_part = &content[name_start..];
+impl Default for QrCodeReader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct QrCodeWriter;
+
+impl QrCodeWriter {
+    pub fn new() -> Self {
+        Self
+    }
+
+    pub fn render(&self, content: &str, format: QrImageFormat) -> Result<QrCodeImage> {
+        let code = QrCode::new(content.as_bytes())
+            .map_err(|e| AppError::QrCode(format!("Failed to build QR code: {}", e)))?;
         
-        let name = if let Some(query_start) = name_part.find('?') {
-            &name_part[..query_start]
-        } else {
-            name_part
+        let data = match format {
+            QrImageFormat::Png => general_purpose::STANDARD.encode(self.render_png(&code)?),
+            QrImageFormat::Svg => code
+                .render::<svg::Color>()
+                .min_dimensions(SVG_MIN_SIZE, SVG_MIN_SIZE)
+                .build(),
         };
         
-        if name.is_empty() {
-            return Err(AppError::QrCode("Empty name in QR code".to_string()));
-        }
+        Ok(QrCodeImage { content: content.to_string(), format, data })
+    }
+
+    fn render_png(&self, code: &QrCode) -> Result<Vec<u8>> {
+        let modules = code.width() as u32;
+        let colors = code.to_colors();
+        let size = (modules + 2 * QUIET_ZONE_MODULES) * PNG_MODULE_SIZE;
         
-        let decoded = urlencoding::decode(name)
-            .unwrap_or_else(|_| std::borrow::Cow::Borrowed(name))
-            .to_string();
+        let img = GrayImage::from_fn(size, size, |x, y| {
+            let module_x = (x / PNG_MODULE_SIZE).wrapping_sub(QUIET_ZONE_MODULES);
+            let module_y = (y / PNG_MODULE_SIZE).wrapping_sub(QUIET_ZONE_MODULES);
+            
+            // Coordinates inside the quiet zone wrap around and fail the bounds check
+            let dark = module_x < modules
+                && module_y < modules
+                && colors[(module_y * modules + module_x) as usize] == Color::Dark;
+            
+            if dark { Luma([0]) } else { Luma([255]) }
+        });
         
-        // Remove issuer prefix if present (e.g., "Google:user@gmail.com" -> "Google")
-        if let Some(colon_pos) = decoded.find(':') {
-            Ok(decoded[..colon_pos].to_string())
-        } else {
-            Ok(decoded)
-        }
+        let mut png_bytes = Vec::new();
+        DynamicImage::ImageLuma8(img)
+            .write_to(&mut std::io::Cursor::new(&mut png_bytes), image::ImageOutputFormat::Png)
+            .map_err(|e| AppError::QrCode(format!("Failed to encode PNG: {}", e)))?;
+        
+        Ok(png_bytes)
     }
 }
 
-impl Default for QrCodeReader {
+impl Default for QrCodeWriter {
     fn default() -> Self {
         Self::new()
     }
@@ -102,24 +132,58 @@ impl Default for QrCodeReader {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::types::OtpType;
 
     #[test]
-    fn test_extract_secret_from_otpauth() {
+    fn test_parse_otpauth_content() {
         let reader = QrCodeReader::new();
         let otpauth = "otpauth://totp/Example:alice@google.com?secret=JBSWY3DPEHPK3PXP&issuer=Example";
         
-        let secret = reader.extract_secret_from_otpauth(otpauth).unwrap();
-        assert_eq!(secret, "JBSWY3DPEHPK3PXP");
+        let data = reader.parse_content(otpauth).unwrap();
+        assert_eq!(data.secret, "JBSWY3DPEHPK3PXP");
+        assert_eq!(data.name, "Example");
     }
 
+    #[test]
+    fn test_hotp_otpauth() {
+        let reader = QrCodeReader::new();
+        let otpauth = "otpauth://hotp/Gateway:bob?secret=JBSWY3DPEHPK3PXP&counter=42";
+        
+        let data = reader.parse_content(otpauth).unwrap();
+        assert_eq!(data.params.otp_type, OtpType::Hotp);
+        assert_eq!(data.params.counter, 42);
+    }
 
+    #[test]
+    fn test_migration_content_is_not_a_secret() {
+        let reader = QrCodeReader::new();
+        let export = "otpauth-migration://offline?data=CgA%3D";
+        
+        assert!(reader.parse_content(export).is_err());
+    }
 
     #[test]
     fn test_invalid_otpauth() {
         let reader = QrCodeReader::new();
-        let invalid = "not an otpauth url";
+        let invalid = "otpauth://totp/Example?issuer=Example";
         
-        let result = reader.extract_secret_from_otpauth(invalid);
+        let result = reader.parse_content(invalid);
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_render_png_and_svg() {
+        let writer = QrCodeWriter::new();
+        let content = "otpauth://totp/Example?secret=JBSWY3DPEHPK3PXP";
+        
+        let png = writer.render(content, QrImageFormat::Png).unwrap();
+        let bytes = general_purpose::STANDARD.decode(&png.data).unwrap();
+        let img = image::load_from_memory(&bytes).unwrap();
+        assert_eq!(img.width(), img.height());
+        assert_eq!(img.width() % PNG_MODULE_SIZE, 0);
+        
+        let svg = writer.render(content, QrImageFormat::Svg).unwrap();
+        assert!(svg.data.contains("<svg"));
+        assert_eq!(svg.content, content);
+    }
 }
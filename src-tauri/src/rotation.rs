@@ -0,0 +1,74 @@
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_KEEP: usize = 10;
+pub const MAX_KEEP: usize = 100;
+
+const DAY_MILLIS: i64 = 24 * 60 * 60 * 1000;
+
+/// How many generations of the vault file are kept as backups, and for how
+/// long. Persisted in clear next to the vault, it holds no secrets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BackupPolicy {
+    #[serde(default = "default_keep")]
+    pub keep: usize,
+    /// Backups older than this are dropped, off when `None`
+    #[serde(default)]
+    pub max_age_days: Option<u32>,
+}
+
+fn default_keep() -> usize {
+    DEFAULT_KEEP
+}
+
+impl Default for BackupPolicy {
+    fn default() -> Self {
+        Self {
+            keep: DEFAULT_KEEP,
+            max_age_days: None,
+        }
+    }
+}
+
+impl BackupPolicy {
+    /// Whether the backup at `position`, counted from the newest, stays.
+    /// Times are Unix milliseconds. The newest backup is kept whatever its
+    /// age, so a vault left untouched for a while still has one.
+    pub fn keeps(&self, position: usize, created_at: i64, now: i64) -> bool {
+        if position == 0 {
+            return true;
+        }
+
+        if position >= self.keep {
+            return false;
+        }
+
+        match self.max_age_days {
+            Some(days) => now.saturating_sub(created_at) <= i64::from(days) * DAY_MILLIS,
+            None => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keeps_the_newest_generations() {
+        let policy = BackupPolicy { keep: 3, max_age_days: None };
+
+        assert!(policy.keeps(0, 0, 0));
+        assert!(policy.keeps(2, 0, 0));
+        assert!(!policy.keeps(3, 0, 0));
+    }
+
+    #[test]
+    fn test_max_age_spares_the_newest() {
+        let policy = BackupPolicy { keep: 10, max_age_days: Some(7) };
+        let now = 30 * DAY_MILLIS;
+
+        assert!(policy.keeps(1, now - 7 * DAY_MILLIS, now));
+        assert!(!policy.keeps(1, now - 8 * DAY_MILLIS, now));
+        assert!(policy.keeps(0, 0, now));
+    }
+}
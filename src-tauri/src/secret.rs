@@ -0,0 +1,230 @@
+use std::alloc;
+use std::fmt;
+use std::ops::Deref;
+use std::ptr::NonNull;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use zeroize::{Zeroize, Zeroizing};
+
+pub const KEY_SIZE: usize = 32;
+
+/// A 256-bit key that is wiped when dropped. The bytes live in a page of
+/// their own, locked out of swap where the platform allows it, so moving the
+/// key around never leaves copies behind.
+pub struct SecretKey(NonNull<[u8; KEY_SIZE]>);
+
+// The key owns its allocation, as a `Box` would
+unsafe impl Send for SecretKey {}
+unsafe impl Sync for SecretKey {}
+
+impl SecretKey {
+    pub fn zeroed() -> Self {
+        Self(allocate())
+    }
+
+    /// Copies `bytes` into a new key and wipes the source
+    pub fn from_bytes(bytes: &mut [u8; KEY_SIZE]) -> Self {
+        let mut key = Self::zeroed();
+        key.expose_mut().copy_from_slice(bytes);
+        bytes.zeroize();
+        key
+    }
+
+    pub fn expose(&self) -> &[u8; KEY_SIZE] {
+        unsafe { self.0.as_ref() }
+    }
+
+    /// Lets key derivation write straight into the locked buffer
+    pub fn expose_mut(&mut self) -> &mut [u8; KEY_SIZE] {
+        unsafe { self.0.as_mut() }
+    }
+}
+
+impl Drop for SecretKey {
+    fn drop(&mut self) {
+        self.expose_mut().zeroize();
+        release(self.0);
+    }
+}
+
+impl Clone for SecretKey {
+    fn clone(&self) -> Self {
+        let mut key = Self::zeroed();
+        key.expose_mut().copy_from_slice(self.expose());
+        key
+    }
+}
+
+impl PartialEq for SecretKey {
+    // Constant time, this is used to check a password against the session key
+    fn eq(&self, other: &Self) -> bool {
+        self.expose().iter().zip(other.expose().iter()).fold(0u8, |acc, (a, b)| acc | (a ^ b)) == 0
+    }
+}
+
+impl Eq for SecretKey {}
+
+impl fmt::Debug for SecretKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SecretKey([redacted])")
+    }
+}
+
+/// An OTP secret, serialized as a plain string and wiped when dropped
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct Secret(Zeroizing<String>);
+
+impl Secret {
+    pub fn new(value: String) -> Self {
+        Self(Zeroizing::new(value))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<String> for Secret {
+    fn from(value: String) -> Self {
+        Self::new(value)
+    }
+}
+
+impl From<&str> for Secret {
+    fn from(value: &str) -> Self {
+        Self::new(value.to_string())
+    }
+}
+
+impl Deref for Secret {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl PartialEq<str> for Secret {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for Secret {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Secret([redacted])")
+    }
+}
+
+impl Serialize for Secret {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Secret {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Self::new)
+    }
+}
+
+fn allocate() -> NonNull<[u8; KEY_SIZE]> {
+    let layout = memory::layout();
+    let Some(ptr) = NonNull::new(unsafe { alloc::alloc_zeroed(layout) }) else {
+        alloc::handle_alloc_error(layout);
+    };
+    memory::lock(ptr.as_ptr(), layout.size());
+    ptr.cast()
+}
+
+fn release(ptr: NonNull<[u8; KEY_SIZE]>) {
+    let layout = memory::layout();
+    memory::unlock(ptr.as_ptr().cast(), layout.size());
+    unsafe { alloc::dealloc(ptr.as_ptr().cast(), layout) };
+}
+
+#[cfg(unix)]
+mod memory {
+    use std::alloc::Layout;
+
+    use super::KEY_SIZE;
+
+    /// A whole page per key: locks apply to pages and do not nest, so a key
+    /// sharing its page would have it unlocked when the other is dropped
+    pub fn layout() -> Layout {
+        let page = usize::try_from(unsafe { libc::sysconf(libc::_SC_PAGESIZE) }).unwrap_or(4096);
+        Layout::from_size_align(page.max(KEY_SIZE), page).expect("page size is a power of two")
+    }
+
+    // Best effort: mlock fails once RLIMIT_MEMLOCK is reached, and the key is
+    // still wiped on drop in that case
+    pub fn lock(ptr: *const u8, len: usize) {
+        unsafe {
+            libc::mlock(ptr as *const libc::c_void, len);
+        }
+    }
+
+    pub fn unlock(ptr: *const u8, len: usize) {
+        unsafe {
+            libc::munlock(ptr as *const libc::c_void, len);
+        }
+    }
+}
+
+#[cfg(not(unix))]
+mod memory {
+    use std::alloc::Layout;
+
+    use super::KEY_SIZE;
+
+    pub fn layout() -> Layout {
+        Layout::new::<[u8; KEY_SIZE]>()
+    }
+
+    pub fn lock(_ptr: *const u8, _len: usize) {}
+
+    pub fn unlock(_ptr: *const u8, _len: usize) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_secrets_are_redacted() {
+        let secret = Secret::from("JBSWY3DPEHPK3PXP");
+        let key = SecretKey::from_bytes(&mut [7u8; KEY_SIZE]);
+
+        assert!(!format!("{:?}", secret).contains("JBSWY3DPEHPK3PXP"));
+        assert!(!format!("{:?}", key).contains('7'));
+        assert_eq!(serde_json::to_string(&secret).unwrap(), "\"JBSWY3DPEHPK3PXP\"");
+    }
+
+    #[test]
+    fn test_from_bytes_wipes_source() {
+        let mut bytes = [7u8; KEY_SIZE];
+        let key = SecretKey::from_bytes(&mut bytes);
+
+        assert_eq!(bytes, [0u8; KEY_SIZE]);
+        assert_eq!(key.expose(), &[7u8; KEY_SIZE]);
+        assert_eq!(key.clone(), key);
+    }
+
+    #[test]
+    fn test_keys_do_not_share_pages() {
+        let page = memory::layout().align();
+        let key = SecretKey::zeroed();
+        let copy = key.clone();
+
+        assert_eq!(key.expose().as_ptr() as usize % page, 0);
+        assert_eq!(copy.expose().as_ptr() as usize % page, 0);
+        drop(copy);
+        assert_eq!(key.expose(), &[0u8; KEY_SIZE]);
+    }
+}
@@ -0,0 +1,144 @@
+use serde::{Deserialize, Serialize};
+
+use crate::types::{AppError, OtpApp, Result};
+
+pub const DEFAULT_AUTO_LOCK_MINUTES: u64 = 5;
+/// A day is plenty; anything above is almost certainly a unit mistake
+pub const MAX_AUTO_LOCK_MINUTES: u64 = 24 * 60;
+pub const DEFAULT_CLIPBOARD_CLEAR_SECONDS: u32 = 30;
+pub const MAX_CLIPBOARD_CLEAR_SECONDS: u32 = 10 * 60;
+pub const MAX_SYNC_INTERVAL_MINUTES: u32 = 24 * 60;
+
+/// Emitted to the frontend with the new settings whenever they change
+pub const SETTINGS_CHANGED_EVENT: &str = "settings-changed";
+
+/// Order `get_apps` returns the entries in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    /// As they were added or imported
+    #[default]
+    Added,
+    Name,
+    /// By issuer, then name; entries without one come last
+    Issuer,
+}
+
+impl SortOrder {
+    pub fn sort(&self, apps: &mut [OtpApp]) {
+        match self {
+            Self::Added => {}
+            Self::Name => apps.sort_by_cached_key(|app| app.name.to_lowercase()),
+            Self::Issuer => apps.sort_by_cached_key(|app| {
+                let issuer = app.issuer.as_ref().map(|issuer| issuer.to_lowercase());
+                (issuer.is_none(), issuer, app.name.to_lowercase())
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Language {
+    #[default]
+    #[serde(rename = "pt-BR")]
+    PtBr,
+    #[serde(rename = "en")]
+    En,
+}
+
+/// User preferences. Persisted in clear next to the vault, like the backup
+/// policy, so they apply before the vault is unlocked; they hold no secrets.
+/// Fields missing from the file take their default.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Idle time before the vault locks itself, 0 disables auto-lock
+    pub auto_lock_minutes: u64,
+    /// Time after which a copied code is cleared from the clipboard, 0 keeps it
+    pub clipboard_clear_seconds: u32,
+    pub sort_order: SortOrder,
+    /// Time between automatic Google Drive syncs, 0 syncs only on demand
+    pub sync_interval_minutes: u32,
+    /// Closing the window hides it to the tray instead of quitting
+    pub close_to_tray: bool,
+    pub language: Language,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            auto_lock_minutes: DEFAULT_AUTO_LOCK_MINUTES,
+            clipboard_clear_seconds: DEFAULT_CLIPBOARD_CLEAR_SECONDS,
+            sort_order: SortOrder::default(),
+            sync_interval_minutes: 0,
+            close_to_tray: true,
+            language: Language::default(),
+        }
+    }
+}
+
+impl Settings {
+    pub fn validate(&self) -> Result<()> {
+        if self.auto_lock_minutes > MAX_AUTO_LOCK_MINUTES {
+            return Err(AppError::InvalidInput(format!("Auto-lock timeout must be at most {} minutes", MAX_AUTO_LOCK_MINUTES)));
+        }
+
+        if self.clipboard_clear_seconds > MAX_CLIPBOARD_CLEAR_SECONDS {
+            return Err(AppError::InvalidInput(format!("Clipboard clear delay must be at most {} seconds", MAX_CLIPBOARD_CLEAR_SECONDS)));
+        }
+
+        if self.sync_interval_minutes > MAX_SYNC_INTERVAL_MINUTES {
+            return Err(AppError::InvalidInput(format!("Sync interval must be at most {} minutes", MAX_SYNC_INTERVAL_MINUTES)));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missing_fields_take_defaults() {
+        let settings: Settings = serde_json::from_str(r#"{"sort_order":"name","language":"en"}"#).unwrap();
+
+        assert_eq!(settings.sort_order, SortOrder::Name);
+        assert_eq!(settings.language, Language::En);
+        assert_eq!(settings.auto_lock_minutes, DEFAULT_AUTO_LOCK_MINUTES);
+        assert!(settings.close_to_tray);
+    }
+
+    #[test]
+    fn test_sort_orders() {
+        let named = |name: &str, issuer: Option<&str>| {
+            let mut app = OtpApp::new(name.to_string(), "JBSWY3DPEHPK3PXP".to_string());
+            app.issuer = issuer.map(str::to_string);
+            app
+        };
+        let apps = vec![named("b", None), named("C", Some("Zeta")), named("a", Some("acme"))];
+        let sorted = |order: SortOrder| {
+            let mut apps = apps.clone();
+            order.sort(&mut apps);
+            apps.into_iter().map(|app| app.name).collect::<Vec<_>>()
+        };
+
+        assert_eq!(sorted(SortOrder::Added), ["b", "C", "a"]);
+        assert_eq!(sorted(SortOrder::Name), ["a", "b", "C"]);
+        assert_eq!(sorted(SortOrder::Issuer), ["a", "C", "b"]);
+    }
+
+    #[test]
+    fn test_validate_limits() {
+        assert!(Settings::default().validate().is_ok());
+
+        let settings = Settings { auto_lock_minutes: MAX_AUTO_LOCK_MINUTES + 1, ..Settings::default() };
+        assert!(matches!(settings.validate(), Err(AppError::InvalidInput(_))));
+
+        let settings = Settings { clipboard_clear_seconds: MAX_CLIPBOARD_CLEAR_SECONDS + 1, ..Settings::default() };
+        assert!(settings.validate().is_err());
+
+        let settings = Settings { sync_interval_minutes: MAX_SYNC_INTERVAL_MINUTES + 1, ..Settings::default() };
+        assert!(settings.validate().is_err());
+    }
+}
@@ -1,25 +1,31 @@
 use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
 
+use crate::crypto::VaultKey;
 use crate::google_drive::GoogleDriveAuth;
-use crate::types::OtpApp;
+use crate::settings::Settings;
+use crate::types::{AppError, OtpApp, Result};
 
 #[derive(Debug)]
 pub struct AppState {
     pub apps: Arc<RwLock<Vec<OtpApp>>>,
-    pub master_password: Arc<RwLock<Option<String>>>,
-    pub encryption_key: Arc<RwLock<Option<[u8; 32]>>>,
+    pub encryption_key: Arc<RwLock<Option<VaultKey>>>,
     pub google_auth: Arc<RwLock<Option<GoogleDriveAuth>>>,
     pub syncing: Arc<RwLock<bool>>,
+    pub last_activity: Arc<RwLock<Instant>>,
+    /// The settings in effect, as last loaded or saved
+    pub settings: Arc<RwLock<Settings>>,
 }
 
 impl AppState {
     pub fn new() -> Self {
         Self {
             apps: Arc::new(RwLock::new(Vec::new())),
-            master_password: Arc::new(RwLock::new(None)),
             encryption_key: Arc::new(RwLock::new(None)),
             google_auth: Arc::new(RwLock::new(None)),
             syncing: Arc::new(RwLock::new(false)),
+            last_activity: Arc::new(RwLock::new(Instant::now())),
+            settings: Arc::new(RwLock::new(Settings::default())),
         }
     }
 
@@ -61,22 +67,74 @@ impl AppState {
         }
     }
 
-    pub fn get_encryption_key(&self) -> Option<[u8; 32]> {
-        *self.encryption_key.read().unwrap()
+    /// Takes the current HOTP counter of an app for a code and stores the
+    /// next one, handing the updated list to `persist` while still holding
+    /// the write lock, so two callers can never use the same counter. The old
+    /// value is restored if `persist` fails.
+    pub fn advance_counter<F>(&self, id: &str, persist: F) -> Result<u64>
+    where
+        F: FnOnce(&[OtpApp]) -> Result<()>,
+    {
+        let mut apps_guard = self.apps.write().unwrap();
+        let app = apps_guard.iter_mut()
+            .find(|a| a.id == id)
+            .ok_or(AppError::AppNotFound)?;
+        
+        let current = app.counter;
+        app.counter = current.checked_add(1)
+            .ok_or_else(|| AppError::InvalidSecret("HOTP counter is exhausted".to_string()))?;
+        
+        if let Err(e) = persist(&apps_guard) {
+            if let Some(app) = apps_guard.iter_mut().find(|a| a.id == id) {
+                app.counter = current;
+            }
+            return Err(e);
+        }
+        
+        Ok(current)
     }
 
-    pub fn set_encryption_key(&self, key: [u8; 32]) {
+    pub fn set_counter(&self, id: &str, counter: u64) -> bool {
+        let mut apps_guard = self.apps.write().unwrap();
+        if let Some(app) = apps_guard.iter_mut().find(|a| a.id == id) {
+            app.counter = counter;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn get_encryption_key(&self) -> Option<VaultKey> {
+        self.encryption_key.read().unwrap().clone()
+    }
+
+    pub fn set_encryption_key(&self, key: VaultKey) {
         let mut key_guard = self.encryption_key.write().unwrap();
         *key_guard = Some(key);
     }
 
-    pub fn set_master_password(&self, password: String) {
-        let mut password_guard = self.master_password.write().unwrap();
-        *password_guard = Some(password);
-    }
+    /// Swaps in the entries and key `reload` returns for the current ones.
+    /// Both stay locked meanwhile, so no command changes them mid-reload.
+    /// Returns `Ok(false)` when the vault is locked or `reload` found nothing
+    /// to change.
+    pub fn reload_with<F>(&self, reload: F) -> Result<bool>
+    where
+        F: FnOnce(&[OtpApp], &VaultKey) -> Result<Option<(Vec<OtpApp>, VaultKey)>>,
+    {
+        let mut apps_guard = self.apps.write().unwrap();
+        let mut key_guard = self.encryption_key.write().unwrap();
+
+        let Some(key) = key_guard.as_ref() else {
+            return Ok(false);
+        };
+
+        let Some((apps, key)) = reload(&apps_guard, key)? else {
+            return Ok(false);
+        };
 
-    pub fn has_master_password(&self) -> bool {
-        self.master_password.read().unwrap().is_some()
+        *apps_guard = apps;
+        *key_guard = Some(key);
+        Ok(true)
     }
 
     pub fn get_google_auth(&self) -> Option<GoogleDriveAuth> {
@@ -97,17 +155,50 @@ impl AppState {
         *syncing_guard = syncing;
     }
 
-    pub fn clear_all(&self) {
+    pub fn is_unlocked(&self) -> bool {
+        self.encryption_key.read().unwrap().is_some()
+    }
+
+    /// Forgets everything derived from the master password. The vault stays
+    /// on disk and is opened again with `verify_master_password`. Secrets and
+    /// the key wipe their memory as they are dropped here.
+    pub fn lock(&self) {
         let mut apps_guard = self.apps.write().unwrap();
-        let mut password_guard = self.master_password.write().unwrap();
         let mut key_guard = self.encryption_key.write().unwrap();
         let mut auth_guard = self.google_auth.write().unwrap();
 
         apps_guard.clear();
-        *password_guard = None;
+        apps_guard.shrink_to_fit();
         *key_guard = None;
+        // Decrypted with the vault key, so it goes with it
         *auth_guard = None;
     }
+
+    pub fn touch(&self) {
+        *self.last_activity.write().unwrap() = Instant::now();
+    }
+
+    pub fn get_settings(&self) -> Settings {
+        self.settings.read().unwrap().clone()
+    }
+
+    pub fn set_settings(&self, settings: Settings) {
+        *self.settings.write().unwrap() = settings;
+    }
+
+    /// True once the vault is unlocked and has been idle for longer than the
+    /// auto-lock timeout
+    pub fn should_auto_lock(&self) -> bool {
+        let minutes = self.settings.read().unwrap().auto_lock_minutes;
+        
+        minutes > 0
+            && self.is_unlocked()
+            && self.last_activity.read().unwrap().elapsed() >= Duration::from_secs(minutes * 60)
+    }
+
+    pub fn clear_all(&self) {
+        self.lock();
+    }
 }
 
 impl Default for AppState {
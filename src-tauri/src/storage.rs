@@ -1,46 +1,55 @@
-use std::fs;
-use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
 
-use crate::crypto::{encrypt_data, decrypt_data};
-use crate::types::{OtpApp, AppError, Result};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use zeroize::Zeroizing;
 
-const DATA_DIR: &str = ".plaxo-otp";
-const APPS_FILE: &str = "apps.enc";
-const GOOGLE_AUTH_FILE: &str = "google_auth.enc";
+use crate::crypto::{KeyProtection, Keyfile, VaultKey};
+use crate::rotation::BackupPolicy;
+use crate::settings::Settings;
+use crate::store::{Blob, VaultStore};
+use crate::throttle::AttemptLog;
+use crate::types::{OtpApp, AppError, BackupInfo, IntegrityReport, Result, VaultFileKind, VaultFileStatus};
+use crate::vault::{self, DecryptedVault, APPS_SCHEMA_VERSION};
 
-pub struct Storage;
+const GOOGLE_AUTH_SCHEMA_VERSION: u32 = 1;
 
-impl Storage {
-    pub fn new() -> Self {
-        Self
-    }
+/// SHA-256 of the vault file contents
+type Fingerprint = [u8; 32];
 
-    fn get_data_dir() -> Result<PathBuf> {
-        let mut path = dirs::home_dir()
-            .or_else(|| std::env::current_dir().ok())
-            .ok_or_else(|| AppError::Io("Could not determine home directory".to_string()))?;
-        
-        path.push(DATA_DIR);
-        fs::create_dir_all(&path)?;
-        Ok(path)
-    }
+/// The vault file as this process last loaded or saved it
+struct LoadedVault {
+    fingerprint: Fingerprint,
+    /// Its entries, the common base when merging a file changed elsewhere
+    apps: Vec<OtpApp>,
+}
+
+/// The encrypted vault, its backups and the small files kept next to it,
+/// on top of whichever `VaultStore` holds them
+#[derive(Clone)]
+pub struct Storage {
+    store: Arc<dyn VaultStore>,
+    /// A file that no longer matches was changed by someone else and is not
+    /// overwritten
+    loaded: Arc<Mutex<Option<LoadedVault>>>,
+}
 
-    fn get_apps_file_path() -> Result<PathBuf> {
-        let mut path = Self::get_data_dir()?;
-        path.push(APPS_FILE);
-        Ok(path)
+impl Storage {
+    pub fn new(store: Arc<dyn VaultStore>) -> Self {
+        Self { store, loaded: Arc::default() }
     }
 
-    fn get_google_auth_file_path() -> Result<PathBuf> {
-        let mut path = Self::get_data_dir()?;
-        path.push(GOOGLE_AUTH_FILE);
-        Ok(path)
+    /// Where the vault lives, for display
+    pub fn location(&self) -> String {
+        self.store.location()
     }
 
-    pub fn save_apps(&self, apps: &[OtpApp], key: &[u8; 32]) -> Result<()> {
+    pub fn save_apps(&self, apps: &[OtpApp], key: &VaultKey) -> Result<()> {
         tracing::info!("Starting save of {} apps", apps.len());
         
         let json = serde_json::to_string(apps)
+            .map(Zeroizing::new)
             .map_err(|e| {
                 tracing::error!("Failed to serialize apps: {}", e);
                 e
@@ -48,7 +57,7 @@ impl Storage {
         
         tracing::debug!("Serialized {} bytes of JSON", json.len());
         
-        let encrypted = encrypt_data(&json, key)
+        let encrypted = vault::encrypt(&json, key, APPS_SCHEMA_VERSION)
             .map_err(|e| {
                 tracing::error!("Failed to encrypt data: {}", e);
                 e
@@ -56,16 +65,15 @@ impl Storage {
         
         tracing::debug!("Encrypted {} bytes", encrypted.len());
         
-        let file_path = Self::get_apps_file_path()?;
-        tracing::debug!("Target file: {:?}", file_path);
-        
-        // Secure write with temporary file
-        let temp_path = format!("{}.tmp", file_path.to_string_lossy());
+        // Held until the new file is remembered, so the watcher cannot take
+        // this save for someone else's
+        let mut loaded = self.loaded.lock().unwrap();
+        let current = self.store.read(Blob::Vault)?;
+        ensure_unchanged(&loaded, current.as_deref())?;
         
         // Backup current file if it exists
-        if file_path.exists() {
-            let backup_path = format!("{}.backup", file_path.to_string_lossy());
-            fs::copy(&file_path, &backup_path)
+        if let Some(current) = current {
+            self.rotate_backups(&current)
                 .map_err(|e| {
                     tracing::warn!("Failed to create backup: {}", e);
                     e
@@ -73,152 +81,697 @@ impl Storage {
             tracing::debug!("Backup created");
         }
         
-        // Write to temporary file
-        fs::write(&temp_path, &encrypted)
+        self.store.write(Blob::Vault, &encrypted)
             .map_err(|e| {
-                tracing::error!("Failed to write temp file: {}", e);
+                tracing::error!("Failed to write vault file: {}", e);
                 e
             })?;
+        *loaded = Some(LoadedVault { fingerprint: fingerprint(&encrypted), apps: apps.to_vec() });
         
-        tracing::debug!("Temp file written");
-        
-        // Atomic move to final file
-        fs::rename(&temp_path, &file_path)
-            .map_err(|e| {
-                tracing::error!("Failed to rename temp file: {}", e);
-                e
-            })?;
-        
-        tracing::info!("Successfully saved {} apps to {:?}", apps.len(), file_path);
+        tracing::info!("Successfully saved {} apps to {}", apps.len(), self.store.location());
         Ok(())
     }
 
-    pub fn load_apps(&self, key: &[u8; 32]) -> Result<Vec<OtpApp>> {
-        let file_path = Self::get_apps_file_path()?;
-        
-        if !file_path.exists() {
-            return Ok(Vec::new());
+    /// Opens the vault with the master password. Returns the entries and the
+    /// key unlocked from the file's header.
+    pub fn load_apps(&self, password: &str, keyfile: Option<&Keyfile>) -> Result<(Vec<OtpApp>, VaultKey)> {
+        if !self.has_apps_file() {
+            return Ok((Vec::new(), VaultKey::generate(password, keyfile)?));
         }
         
+        self.load_apps_with(|content| vault::decrypt_with_password(content, password, keyfile))
+    }
+
+    /// Opens the vault with its recovery key, for when the password is lost
+    pub fn load_apps_with_recovery_key(&self, recovery_key: &str) -> Result<(Vec<OtpApp>, VaultKey)> {
+        self.load_apps_with(|content| vault::decrypt_with_recovery_key(content, recovery_key))
+    }
+
+    fn load_apps_with<F>(&self, unlock: F) -> Result<(Vec<OtpApp>, VaultKey)>
+    where
+        F: Fn(&[u8]) -> Result<(DecryptedVault, VaultKey)>,
+    {
         // Try to load main file
-        match self.try_load_file(&file_path, key) {
-            Ok(apps) => {
+        match self.try_load_blob(Blob::Vault, &unlock) {
+            Ok((apps, key, outdated)) => {
                 tracing::info!("Loaded {} apps from storage", apps.len());
-                Ok(apps)
+                // Keys still waiting to move to slots are re-keyed by the caller,
+                // which rewrites the file anyway
+                if outdated && !key.needs_upgrade() {
+                    self.rewrite_outdated(&apps, &key);
+                }
+                Ok((apps, key))
             }
+            // The file is fine, the credentials are not. A backup could still
+            // open under an older password and would then replace a newer file.
+            Err(e @ (AppError::InvalidMasterPassword | AppError::KeyfileRequired | AppError::InvalidRecoveryKey)) => Err(e),
             Err(e) => {
                 tracing::warn!("Failed to load main file: {}", e);
                 
-                // Try to load from backup
-                let backup_path = format!("{}.backup", file_path.to_string_lossy());
-                if std::path::Path::new(&backup_path).exists() {
-                    tracing::info!("Attempting to load from backup...");
-                    match self.try_load_file(&PathBuf::from(&backup_path), key) {
-                        Ok(apps) => {
+                // Fall back through the backups, newest first
+                let backups = self.store.backups().unwrap_or_else(|list_err| {
+                    tracing::warn!("Could not list backups: {}", list_err);
+                    Vec::new()
+                });
+                
+                for backup in backups.into_iter().map(Blob::Backup) {
+                    tracing::info!("Attempting to load from backup {}...", backup.file_name());
+                    match self.try_load_blob(backup, &unlock) {
+                        Ok((apps, key, _)) => {
                             tracing::info!("Backup loaded successfully, restoring main file...");
                             // Restore main file from backup
-                            if let Err(restore_err) = fs::copy(&backup_path, &file_path) {
+                            if let Err(restore_err) = self.restore_vault(backup, &apps) {
                                 tracing::warn!("Could not restore main file: {}", restore_err);
                             }
-                            Ok(apps)
+                            return Ok((apps, key));
                         }
                         Err(backup_err) => {
                             tracing::error!("Backup also corrupted: {}", backup_err);
-                            Err(e) // Return original error
                         }
                     }
-                } else {
-                    Err(e)
                 }
+                
+                Err(e) // Return original error
             }
         }
     }
 
-    fn try_load_file(&self, file_path: &PathBuf, key: &[u8; 32]) -> Result<Vec<OtpApp>> {
-        let encrypted_data = fs::read_to_string(file_path)?;
-        let decrypted = decrypt_data(&encrypted_data, key)?;
-        let apps: Vec<OtpApp> = serde_json::from_str(&decrypted)?;
-        Ok(apps)
+    /// Decrypts and migrates one vault file. The flag is set when the file
+    /// uses an older container format or schema.
+    fn try_load_blob<F>(&self, blob: Blob, unlock: &F) -> Result<(Vec<OtpApp>, VaultKey, bool)>
+    where
+        F: Fn(&[u8]) -> Result<(DecryptedVault, VaultKey)>,
+    {
+        let encrypted_data = self.read_existing(blob)?;
+        let (decrypted, key) = unlock(&encrypted_data)?;
+        
+        let outdated = decrypted.is_outdated() || decrypted.header.schema_version < APPS_SCHEMA_VERSION;
+        let apps = vault::migrate_apps(&decrypted.data, decrypted.header.schema_version)?;
+        
+        if blob == Blob::Vault {
+            *self.loaded.lock().unwrap() = Some(LoadedVault { fingerprint: fingerprint(&encrypted_data), apps: apps.clone() });
+        }
+        
+        Ok((apps, key, outdated))
     }
 
-    pub fn save_google_auth(&self, auth_data: &str, key: &[u8; 32]) -> Result<()> {
-        let encrypted = encrypt_data(auth_data, key)?;
-        let file_path = Self::get_google_auth_file_path()?;
-        fs::write(&file_path, &encrypted)?;
-        tracing::info!("Saved Google auth to storage");
+    /// Opens the vault file again if another program replaced it since it was
+    /// loaded or saved here, for instance a sync client bringing in a save
+    /// from another machine, and merges it with the entries in memory
+    /// (`local`). Changes not saved yet, such as a save refused because of
+    /// this very file, are kept and written back. Returns `None` when the
+    /// file is unchanged or gone.
+    ///
+    /// The returned key has the same data key as `key` but the file's slots,
+    /// so a password changed on the other machine carries over.
+    pub fn reload_changed(&self, local: &[OtpApp], key: &VaultKey) -> Result<Option<(Vec<OtpApp>, VaultKey)>> {
+        let mut loaded = self.loaded.lock().unwrap();
+        let Some(content) = self.store.read(Blob::Vault)? else {
+            return Ok(None);
+        };
+        
+        let current = fingerprint(&content);
+        if loaded.as_ref().is_some_and(|loaded| loaded.fingerprint == current) {
+            return Ok(None);
+        }
+        
+        let decrypted = vault::decrypt(&content, key)?;
+        let remote = vault::migrate_apps(&decrypted.data, decrypted.header.schema_version)?;
+        
+        let mut key = key.clone();
+        if let KeyProtection::Slots(_) = decrypted.header.protection {
+            key.protection = decrypted.header.protection;
+        }
+        
+        let base = loaded.take().map(|loaded| loaded.apps).unwrap_or_default();
+        let merged = merge_external(&base, local, &remote);
+        let unsaved = merged != remote;
+        
+        *loaded = Some(LoadedVault { fingerprint: current, apps: remote });
+        drop(loaded);
+        tracing::info!("Vault file changed on disk, reloaded {} apps", merged.len());
+        
+        if unsaved {
+            // Kept in memory either way; the next save writes them
+            if let Err(e) = self.save_apps(&merged, &key) {
+                tracing::warn!("Could not save the merged vault: {}", e);
+            }
+        }
+        
+        Ok(Some((merged, key)))
+    }
+
+    fn rewrite_outdated(&self, apps: &[OtpApp], key: &VaultKey) {
+        match self.save_apps(apps, key) {
+            Ok(()) => tracing::info!("Vault migrated to format version {}", vault::FORMAT_VERSION),
+            Err(e) => tracing::warn!("Could not migrate vault file: {}", e),
+        }
+    }
+
+    /// Re-encrypts the vault, its backups and the Google auth file from
+    /// `old_key` to `new_key`, for a new password, a new recovery key or a
+    /// stronger KDF. Either every file is rewritten or none is.
+    pub fn rekey(&self, old_key: &VaultKey, new_key: &VaultKey) -> Result<()> {
+        let backups: Vec<Blob> = self.store.backups()?
+            .into_iter()
+            .map(Blob::Backup)
+            .collect();
+        
+        let mut loaded = self.loaded.lock().unwrap();
+        ensure_unchanged(&loaded, self.store.read(Blob::Vault)?.as_deref())?;
+        
+        let mut changes = Vec::new();
+        
+        for blob in [Blob::Vault, Blob::GoogleAuth].into_iter().chain(backups.iter().copied()) {
+            let Some(content) = self.store.read(blob)? else {
+                continue;
+            };
+            
+            match vault::decrypt(&content, old_key) {
+                Ok(decrypted) => {
+                    let encrypted = vault::encrypt(&decrypted.data, new_key, decrypted.header.schema_version)?;
+                    changes.push((blob, Some(encrypted)));
+                }
+                // A stale backup must not block the change, and it is not
+                // worth keeping under a key the vault is moving away from
+                Err(e) if backups.contains(&blob) => {
+                    tracing::warn!("Dropping unreadable backup: {}", e);
+                    changes.push((blob, None));
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        
+        self.store.replace_all(&changes)?;
+        // Only the key changed, the entries are the same
+        if let (Some(loaded), Some(content)) = (loaded.as_mut(), self.store.read(Blob::Vault)?) {
+            loaded.fingerprint = fingerprint(&content);
+        }
+        tracing::info!("Vault re-keyed, {} file(s) rewritten", changes.len());
         Ok(())
     }
 
-    pub fn load_google_auth(&self, key: &[u8; 32]) -> Result<String> {
-        let file_path = Self::get_google_auth_file_path()?;
+    /// Backups of the vault file, newest first, with their entry count when
+    /// `key` opens them
+    pub fn list_backups(&self, key: &VaultKey) -> Result<Vec<BackupInfo>> {
+        let backups = self.store.backups()?;
+        
+        Ok(backups
+            .into_iter()
+            .map(|created_at| {
+                let blob = Blob::Backup(created_at);
+                let content = self.read_existing(blob);
+                let entry_count = content.as_ref()
+                    .map_err(|e| e.to_string())
+                    .and_then(|content| open_apps(content, key).map_err(|e| e.to_string()))
+                    .map(|apps| apps.len())
+                    .map_err(|e| tracing::warn!("Could not open backup {}: {}", blob.file_name(), e))
+                    .ok();
+                
+                BackupInfo {
+                    id: blob.file_name(),
+                    created_at,
+                    size: content.map(|content| content.len() as u64).unwrap_or(0),
+                    entry_count,
+                }
+            })
+            .collect())
+    }
+
+    /// Entries of the backup `id`, as returned by `list_backups`
+    pub fn load_backup(&self, id: &str, key: &VaultKey) -> Result<Vec<OtpApp>> {
+        let backup = self.find_backup(id)?;
+        open_apps(&self.read_existing(backup)?, key)
+    }
+
+    /// Tries the vault file, a save interrupted before it was swapped in and
+    /// every backup with `key`, from the newest contents to the oldest
+    pub fn check_integrity(&self, key: &VaultKey) -> Result<IntegrityReport> {
+        let mut candidates = vec![
+            (Blob::PendingVault, VaultFileKind::Temp),
+            (Blob::Vault, VaultFileKind::Main),
+        ];
+        candidates.extend(self.store.backups()?.into_iter().map(|created_at| (Blob::Backup(created_at), VaultFileKind::Backup)));
+        
+        let files: Vec<VaultFileStatus> = candidates
+            .into_iter()
+            // Only a missing main file is worth reporting
+            .filter(|(blob, kind)| self.store.exists(*blob) || *kind == VaultFileKind::Main)
+            .map(|(blob, kind)| {
+                let opened = self.read_existing(blob).and_then(|content| open_apps(&content, key));
+                let (entry_count, error) = match opened {
+                    Ok(apps) => (Some(apps.len()), None),
+                    Err(e) => (None, Some(e.to_string())),
+                };
+                
+                VaultFileStatus { id: blob.file_name(), kind, entry_count, error }
+            })
+            .collect();
+        
+        // Only offered when the vault file itself cannot be opened
+        let main_broken = files.iter().any(|file| file.kind == VaultFileKind::Main && file.error.is_some());
+        let repair_from = files.iter()
+            .filter(|_| main_broken)
+            .find(|file| file.error.is_none())
+            .map(|file| file.id.clone());
+        
+        Ok(IntegrityReport { files, repair_from })
+    }
+
+    /// Replaces the vault file with the interrupted save or backup `id`,
+    /// once `key` has opened it
+    pub fn repair_from(&self, id: &str, key: &VaultKey) -> Result<Vec<OtpApp>> {
+        let source = if id == Blob::PendingVault.file_name() {
+            Blob::PendingVault
+        } else {
+            self.find_backup(id)?
+        };
+        
+        let apps = open_apps(&self.read_existing(source)?, key)?;
+        self.restore_vault(source, &apps)?;
+        self.store.remove(Blob::PendingVault)?;
+        
+        tracing::info!("Vault file repaired from {}", id);
+        Ok(apps)
+    }
+
+    pub fn load_backup_policy(&self) -> BackupPolicy {
+        self.load_json(Blob::BackupPolicy)
+    }
+
+    pub fn save_backup_policy(&self, policy: &BackupPolicy) -> Result<()> {
+        self.save_json(Blob::BackupPolicy, policy)
+    }
+
+    /// Missing or invalid settings fall back to the defaults
+    pub fn load_settings(&self) -> Settings {
+        self.load_json(Blob::Settings)
+    }
+
+    pub fn save_settings(&self, settings: &Settings) -> Result<()> {
+        self.save_json(Blob::Settings, settings)
+    }
+
+    /// Copies `current` into a new backup and drops the backups the policy
+    /// no longer keeps
+    fn rotate_backups(&self, current: &[u8]) -> Result<()> {
+        let now = chrono::Utc::now().timestamp_millis();
+        let existing = self.store.backups()?;
+        
+        // Moved forward a millisecond at a time past backups already using it
+        let created_at = (now..)
+            .find(|time| !existing.contains(time))
+            .expect("backup timestamps exhausted");
+        self.store.write(Blob::Backup(created_at), current)?;
         
-        if !file_path.exists() {
-            return Err(AppError::GoogleDrive("Auth not found".to_string()));
+        let policy = self.load_backup_policy();
+        for (position, created_at) in self.store.backups()?.into_iter().enumerate() {
+            if !policy.keeps(position, created_at, now) {
+                let backup = Blob::Backup(created_at);
+                tracing::debug!("Removing old backup {}", backup.file_name());
+                if let Err(e) = self.store.remove(backup) {
+                    tracing::warn!("Could not remove old backup: {}", e);
+                }
+            }
         }
         
-        let encrypted_data = fs::read_to_string(&file_path)?;
-        let decrypted = decrypt_data(&encrypted_data, key)?;
+        Ok(())
+    }
+
+    /// Only names of existing backups are accepted, never a path
+    fn find_backup(&self, id: &str) -> Result<Blob> {
+        Blob::parse_backup(id)
+            .filter(|backup| self.store.exists(*backup))
+            .ok_or_else(|| AppError::Io(format!("Backup not found: {}", id)))
+    }
+
+    pub fn save_google_auth(&self, auth_data: &str, key: &VaultKey) -> Result<()> {
+        let encrypted = vault::encrypt(auth_data, key, GOOGLE_AUTH_SCHEMA_VERSION)?;
+        self.store.write(Blob::GoogleAuth, &encrypted)?;
+        tracing::info!("Saved Google auth to storage");
+        Ok(())
+    }
+
+    pub fn load_google_auth(&self, key: &VaultKey) -> Result<Zeroizing<String>> {
+        let encrypted_data = self.store.read(Blob::GoogleAuth)?
+            .ok_or_else(|| AppError::GoogleDrive("Auth not found".to_string()))?;
+        
+        let decrypted = vault::decrypt(&encrypted_data, key)?.data;
         tracing::info!("Loaded Google auth from storage");
         Ok(decrypted)
     }
 
     pub fn clear_google_auth(&self) -> Result<()> {
-        let file_path = Self::get_google_auth_file_path()?;
-        if file_path.exists() {
-            fs::remove_file(&file_path)?;
+        if self.store.exists(Blob::GoogleAuth) {
+            self.store.remove(Blob::GoogleAuth)?;
             tracing::info!("Cleared Google auth from storage");
         }
         Ok(())
     }
 
-    pub fn has_apps_file(&self) -> bool {
-        Self::get_apps_file_path()
-            .map(|path| path.exists())
+    /// A missing or unreadable log counts as no failures
+    pub fn load_attempt_log(&self) -> AttemptLog {
+        self.load_json(Blob::Attempts)
+    }
+
+    pub fn save_attempt_log(&self, log: &AttemptLog) -> Result<()> {
+        self.save_json(Blob::Attempts, log)
+    }
+
+    /// Whether unlocking also needs the keyfile. Only the requirement is
+    /// recorded, in the vault header; the keyfile is never stored.
+    pub fn keyfile_required(&self) -> bool {
+        self.read_existing(Blob::Vault)
+            .and_then(|content| vault::requires_keyfile(&content))
             .unwrap_or(false)
     }
 
+    pub fn has_apps_file(&self) -> bool {
+        self.store.exists(Blob::Vault)
+    }
+
     pub fn reset_all_data(&self) -> Result<()> {
-        let data_dir = Self::get_data_dir()?;
-        
-        if data_dir.exists() {
-            fs::remove_dir_all(&data_dir)?;
-            tracing::info!("Reset all data");
-        }
-        
+        self.store.reset()?;
+        *self.loaded.lock().unwrap() = None;
+        Ok(())
+    }
+
+    fn read_existing(&self, blob: Blob) -> Result<Vec<u8>> {
+        self.store.read(blob)?
+            .ok_or_else(|| AppError::Io(format!("{} not found", blob.file_name())))
+    }
+
+    /// Replaces the vault file with a copy of `from`, whatever it held.
+    /// `apps` are the entries already read from it.
+    fn restore_vault(&self, from: Blob, apps: &[OtpApp]) -> Result<()> {
+        let content = self.read_existing(from)?;
+        let mut loaded = self.loaded.lock().unwrap();
+        self.store.write(Blob::Vault, &content)?;
+        *loaded = Some(LoadedVault { fingerprint: fingerprint(&content), apps: apps.to_vec() });
         Ok(())
     }
+
+    /// Plain JSON kept next to the vault; missing or invalid means default
+    fn load_json<T: DeserializeOwned + Default>(&self, blob: Blob) -> T {
+        match self.store.read(blob) {
+            Ok(Some(content)) => serde_json::from_slice(&content).unwrap_or_else(|e| {
+                tracing::warn!("Ignoring invalid {}: {}", blob.file_name(), e);
+                T::default()
+            }),
+            _ => T::default(),
+        }
+    }
+
+    fn save_json<T: Serialize>(&self, blob: Blob, value: &T) -> Result<()> {
+        self.store.write(blob, serde_json::to_string(value)?.as_bytes())
+    }
+}
+
+fn fingerprint(content: &[u8]) -> Fingerprint {
+    Sha256::digest(content).into()
+}
+
+/// Refuses to replace a vault file that is not the one last loaded or saved
+fn ensure_unchanged(loaded: &Option<LoadedVault>, current: Option<&[u8]>) -> Result<()> {
+    match current {
+        Some(current) if loaded.as_ref().map(|loaded| loaded.fingerprint) != Some(fingerprint(current)) => {
+            Err(AppError::VaultChanged)
+        }
+        _ => Ok(()),
+    }
 }
 
-impl Default for Storage {
-    fn default() -> Self {
-        Self::new()
+/// Merges, by id, the entries in memory (`local`) with those of a file
+/// written elsewhere (`remote`), both descending from `base`. A change made
+/// on one side only is taken; when both sides changed an entry, the change
+/// made here wins, except for the HOTP counter, which never moves back.
+fn merge_external(base: &[OtpApp], local: &[OtpApp], remote: &[OtpApp]) -> Vec<OtpApp> {
+    let find = |apps: &'_ [OtpApp], id: &str| apps.iter().find(|app| app.id == id).cloned();
+    let mut merged = Vec::new();
+    
+    // In the file's order, then the entries only known here
+    for theirs in remote {
+        match (find(base, &theirs.id), find(local, &theirs.id)) {
+            // Changed here, or new on both sides
+            (old, Some(mut ours)) if old.as_ref() != Some(&ours) => {
+                // Going back would hand out codes already used there
+                ours.counter = ours.counter.max(theirs.counter);
+                merged.push(ours);
+            }
+            // Deleted here, untouched there
+            (Some(old), None) if *theirs == old => {}
+            _ => merged.push(theirs.clone()),
+        }
+    }
+    
+    for ours in local.iter().filter(|ours| !remote.iter().any(|theirs| theirs.id == ours.id)) {
+        match find(base, &ours.id) {
+            // Added here, or changed here while deleted there
+            None => merged.push(ours.clone()),
+            Some(old) if *ours != old => merged.push(ours.clone()),
+            // Deleted there
+            Some(_) => {}
+        }
     }
+    
+    merged
+}
+
+/// Opens vault contents with the vault's data key, migrating older schemas
+fn open_apps(content: &[u8], key: &VaultKey) -> Result<Vec<OtpApp>> {
+    let decrypted = vault::decrypt(content, key)?;
+    vault::migrate_apps(&decrypted.data, decrypted.header.schema_version)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::crypto::derive_key;
-    use tempfile::TempDir;
+    use crate::crypto::{Kdf, SlotKind};
+    use crate::store::MemoryStore;
+
+    fn memory_storage() -> (Storage, Arc<MemoryStore>) {
+        let store = Arc::new(MemoryStore::default());
+        (Storage::new(store.clone()), store)
+    }
+
+    fn numbered_apps(count: usize) -> Vec<OtpApp> {
+        (0..count)
+            .map(|i| OtpApp::new(format!("App {}", i), "JBSWY3DPEHPK3PXP".to_string()))
+            .collect()
+    }
 
     #[test]
     fn test_save_load_apps() {
-        let _temp_dir = TempDir::new().unwrap();
-        let storage = Storage::new();
-        let key = derive_key("test_password");
+        let (storage, _) = memory_storage();
+        let key = VaultKey::generate_with("test_password", None, Kdf::argon2id(1024, 1, 1)).unwrap();
         
         let apps = vec![
-            OtpApp {
-                id: "1".to_string(),
-                name: "Test App".to_string(),
-                secret: "JBSWY3DPEHPK3PXP".to_string(),
-            }
+            OtpApp::new("Test App".to_string(), "JBSWY3DPEHPK3PXP".to_string())
         ];
         
         storage.save_apps(&apps, &key).unwrap();
-        let loaded_apps = storage.load_apps(&key).unwrap();
+        let (loaded_apps, loaded_key) = storage.load_apps("test_password", None).unwrap();
         
         assert_eq!(apps.len(), loaded_apps.len());
         assert_eq!(apps[0].name, loaded_apps[0].name);
+        assert_eq!(key, loaded_key);
+    }
+
+    #[test]
+    fn test_backups_rotate_and_restore() {
+        let (storage, _) = memory_storage();
+        let key = VaultKey::generate_with("pw", None, Kdf::argon2id(1024, 1, 1)).unwrap();
+        storage.save_backup_policy(&BackupPolicy { keep: 3, max_age_days: None }).unwrap();
+        
+        for count in 1..=5 {
+            storage.save_apps(&numbered_apps(count), &key).unwrap();
+        }
+        
+        let backups = storage.list_backups(&key).unwrap();
+        let counts: Vec<_> = backups.iter().map(|backup| backup.entry_count).collect();
+        assert_eq!(counts, vec![Some(4), Some(3), Some(2)]);
+        
+        assert_eq!(storage.load_backup(&backups[2].id, &key).unwrap().len(), 2);
+        assert!(storage.load_backup("../apps.enc", &key).is_err());
+        
+        let other_key = VaultKey::generate_with("other", None, Kdf::argon2id(1024, 1, 1)).unwrap();
+        assert!(storage.list_backups(&other_key).unwrap().iter().all(|backup| backup.entry_count.is_none()));
+    }
+
+    #[test]
+    fn test_load_falls_back_through_backups() {
+        let (storage, store) = memory_storage();
+        let key = VaultKey::generate_with("pw", None, Kdf::argon2id(1024, 1, 1)).unwrap();
+        
+        for count in 1..=3 {
+            storage.save_apps(&numbered_apps(count), &key).unwrap();
+        }
+        
+        // Main file and newest backup are both damaged
+        let newest = store.backups().unwrap()[0];
+        store.write(Blob::Backup(newest), b"garbage").unwrap();
+        store.write(Blob::Vault, b"garbage").unwrap();
+        
+        let (apps, _) = storage.load_apps("pw", None).unwrap();
+        assert_eq!(apps.len(), 1);
+    }
+
+    #[test]
+    fn test_wrong_password_does_not_restore_a_backup() {
+        let (storage, store) = memory_storage();
+        let key = VaultKey::generate_with("old", None, Kdf::argon2id(1024, 1, 1)).unwrap();
+        
+        storage.save_apps(&numbered_apps(1), &key).unwrap();
+        storage.save_apps(&numbered_apps(2), &key).unwrap();
+        
+        // The password was changed on another machine and the file synced in
+        let new_key = VaultKey::generate_with("new", None, Kdf::argon2id(1024, 1, 1)).unwrap();
+        let json = serde_json::to_string(&numbered_apps(3)).unwrap();
+        let synced = vault::encrypt(&json, &new_key, APPS_SCHEMA_VERSION).unwrap();
+        store.write(Blob::Vault, &synced).unwrap();
+        
+        assert!(matches!(storage.load_apps("old", None), Err(AppError::InvalidMasterPassword)));
+        assert_eq!(store.read(Blob::Vault).unwrap().unwrap(), synced);
+        assert_eq!(storage.load_apps("new", None).unwrap().0.len(), 3);
+    }
+
+    #[test]
+    fn test_external_change_is_reloaded_not_overwritten() {
+        let (storage, store) = memory_storage();
+        let key = VaultKey::generate_with("pw", None, Kdf::argon2id(1024, 1, 1)).unwrap();
+        
+        let local = numbered_apps(1);
+        storage.save_apps(&local, &key).unwrap();
+        assert!(storage.reload_changed(&local, &key).unwrap().is_none());
+        
+        // Another machine adds an entry and changes the password
+        let remote_key = key.with_slot(SlotKind::Password, "remote", None, Kdf::argon2id(1024, 1, 1)).unwrap();
+        let mut remote = local.clone();
+        remote.extend(numbered_apps(1));
+        let json = serde_json::to_string(&remote).unwrap();
+        store.write(Blob::Vault, &vault::encrypt(&json, &remote_key, APPS_SCHEMA_VERSION).unwrap()).unwrap();
+        
+        assert!(matches!(storage.save_apps(&local, &key), Err(AppError::VaultChanged)));
+        
+        let (apps, reloaded_key) = storage.reload_changed(&local, &key).unwrap().unwrap();
+        assert_eq!(apps, remote);
+        assert_eq!(reloaded_key, remote_key);
+        
+        storage.save_apps(&numbered_apps(3), &reloaded_key).unwrap();
+        assert_eq!(storage.load_apps("remote", None).unwrap().0.len(), 3);
+    }
+
+    #[test]
+    fn test_reload_keeps_unsaved_edits() {
+        let (storage, store) = memory_storage();
+        let key = VaultKey::generate_with("pw", None, Kdf::argon2id(1024, 1, 1)).unwrap();
+        
+        let base = numbered_apps(3);
+        storage.save_apps(&base, &key).unwrap();
+        
+        // Edited here, but the save is refused after another machine renamed
+        // one entry, deleted another and added a third
+        let mut local = base.clone();
+        local[0].name = "Edited here".to_string();
+        
+        let mut remote = base.clone();
+        remote[0].name = "Edited there".to_string();
+        remote[1].name = "Renamed there".to_string();
+        remote.remove(2);
+        remote.extend(numbered_apps(1));
+        let json = serde_json::to_string(&remote).unwrap();
+        store.write(Blob::Vault, &vault::encrypt(&json, &key, APPS_SCHEMA_VERSION).unwrap()).unwrap();
+        
+        assert!(matches!(storage.save_apps(&local, &key), Err(AppError::VaultChanged)));
+        
+        let (apps, _) = storage.reload_changed(&local, &key).unwrap().unwrap();
+        let names: Vec<_> = apps.iter().map(|app| app.name.as_str()).collect();
+        assert_eq!(names, ["Edited here", "Renamed there", "App 0"]);
+        
+        // The merge is written back, so the edit survives a restart
+        assert_eq!(storage.load_apps("pw", None).unwrap().0, apps);
+    }
+
+    #[test]
+    fn test_reload_never_moves_a_counter_back() {
+        let (storage, store) = memory_storage();
+        let key = VaultKey::generate_with("pw", None, Kdf::argon2id(1024, 1, 1)).unwrap();
+        
+        let base = numbered_apps(1);
+        storage.save_apps(&base, &key).unwrap();
+        
+        // Renamed here, while codes were generated on the other machine
+        let mut local = base.clone();
+        local[0].name = "Renamed here".to_string();
+        local[0].counter = 1;
+        
+        let mut remote = base.clone();
+        remote[0].counter = 5;
+        let json = serde_json::to_string(&remote).unwrap();
+        store.write(Blob::Vault, &vault::encrypt(&json, &key, APPS_SCHEMA_VERSION).unwrap()).unwrap();
+        
+        let (apps, _) = storage.reload_changed(&local, &key).unwrap().unwrap();
+        assert_eq!(apps[0].name, "Renamed here");
+        assert_eq!(apps[0].counter, 5);
+    }
+
+    #[test]
+    fn test_integrity_check_and_repair() {
+        let (storage, store) = memory_storage();
+        let key = VaultKey::generate_with("pw", None, Kdf::argon2id(1024, 1, 1)).unwrap();
+        
+        storage.save_apps(&numbered_apps(1), &key).unwrap();
+        storage.save_apps(&numbered_apps(2), &key).unwrap();
+        assert_eq!(storage.check_integrity(&key).unwrap().repair_from, None);
+        
+        // Truncated by a crash, with an older backup still readable
+        let vault = store.read(Blob::Vault).unwrap().unwrap();
+        store.write(Blob::Vault, &vault[..10]).unwrap();
+        let report = storage.check_integrity(&key).unwrap();
+        assert_eq!(report.files[0].kind, VaultFileKind::Main);
+        assert!(report.files[0].error.is_some());
+        let backup_id = report.repair_from.unwrap();
+        
+        // A complete save that never got swapped in is newer than any backup
+        store.write(Blob::PendingVault, &vault).unwrap();
+        let report = storage.check_integrity(&key).unwrap();
+        assert_eq!(report.files[0].kind, VaultFileKind::Temp);
+        assert_eq!(report.repair_from.as_deref(), Some("apps.enc.tmp"));
+        
+        assert_eq!(storage.repair_from(&backup_id, &key).unwrap().len(), 1);
+        assert!(!store.exists(Blob::PendingVault));
+        assert_eq!(storage.check_integrity(&key).unwrap().repair_from, None);
+        assert!(storage.repair_from("apps.enc", &key).is_err());
+        
+        // A leftover save is not offered while the vault file opens
+        store.write(Blob::PendingVault, &vault).unwrap();
+        assert_eq!(storage.check_integrity(&key).unwrap().repair_from, None);
+    }
+
+    #[test]
+    fn test_rekey_rewrites_every_blob() {
+        let (storage, _) = memory_storage();
+        let key = VaultKey::generate_with("pw", None, Kdf::argon2id(1024, 1, 1)).unwrap();
+        let new_key = key.with_slot(SlotKind::Password, "new", None, Kdf::argon2id(1024, 1, 1)).unwrap();
+        
+        storage.save_apps(&numbered_apps(1), &key).unwrap();
+        storage.save_apps(&numbered_apps(2), &key).unwrap();
+        storage.save_google_auth("{}", &key).unwrap();
+        storage.rekey(&key, &new_key).unwrap();
+        
+        assert!(storage.load_apps("pw", None).is_err());
+        assert_eq!(storage.load_apps("new", None).unwrap().0.len(), 2);
+        assert_eq!(storage.list_backups(&new_key).unwrap()[0].entry_count, Some(1));
+        assert_eq!(storage.load_google_auth(&new_key).unwrap().as_str(), "{}");
+    }
+
+    #[test]
+    fn test_legacy_entries_get_default_params() {
+        let json = r#"[{"id":"1","name":"Old App","secret":"JBSWY3DPEHPK3PXP"}]"#;
+        let apps: Vec<OtpApp> = serde_json::from_str(json).unwrap();
+        
+        assert_eq!(apps[0].algorithm, crate::types::OtpAlgorithm::Sha1);
+        assert_eq!(apps[0].digits, 6);
+        assert_eq!(apps[0].period, 30);
     }
 }
@@ -0,0 +1,143 @@
+use crate::types::Result;
+
+const BACKUP_PREFIX: &str = "apps-";
+const BACKUP_EXTENSION: &str = ".enc";
+
+/// The pieces of data a vault is made of. Their contents are opaque to the
+/// store; encryption happens in `Storage`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Blob {
+    Vault,
+    /// A save that was written but not yet swapped in, left by a crash
+    PendingVault,
+    GoogleAuth,
+    Attempts,
+    BackupPolicy,
+    Settings,
+    /// A copy of an earlier vault, by its Unix time in milliseconds
+    Backup(i64),
+}
+
+impl Blob {
+    /// The name the blob is stored and reported under
+    pub fn file_name(&self) -> String {
+        match self {
+            Self::Vault => "apps.enc".to_string(),
+            Self::PendingVault => "apps.enc.tmp".to_string(),
+            Self::GoogleAuth => "google_auth.enc".to_string(),
+            Self::Attempts => "attempts.json".to_string(),
+            Self::BackupPolicy => "backup_policy.json".to_string(),
+            Self::Settings => "settings.json".to_string(),
+            Self::Backup(created_at) => format!("{}{}{}", BACKUP_PREFIX, created_at, BACKUP_EXTENSION),
+        }
+    }
+
+    /// The backup named `name` by `file_name`
+    pub fn parse_backup(name: &str) -> Option<Self> {
+        name.strip_prefix(BACKUP_PREFIX)?
+            .strip_suffix(BACKUP_EXTENSION)?
+            .parse()
+            .ok()
+            .map(Self::Backup)
+    }
+}
+
+/// Where the vault's blobs are kept. `Storage` builds everything else on
+/// these operations.
+pub trait VaultStore: Send + Sync {
+    /// `None` when the blob does not exist
+    fn read(&self, blob: Blob) -> Result<Option<Vec<u8>>>;
+
+    fn exists(&self, blob: Blob) -> bool;
+
+    /// Replaces the blob so that after a crash it holds either the old or
+    /// the new contents
+    fn write(&self, blob: Blob, contents: &[u8]) -> Result<()>;
+
+    /// Removing a missing blob is not an error
+    fn remove(&self, blob: Blob) -> Result<()>;
+
+    /// Creation times of the backups, newest first
+    fn backups(&self) -> Result<Vec<i64>>;
+
+    /// Applies every change or none. `None` removes the blob.
+    fn replace_all(&self, changes: &[(Blob, Option<Vec<u8>>)]) -> Result<()>;
+
+    /// Removes every blob
+    fn reset(&self) -> Result<()>;
+
+    /// Where the blobs live, for display
+    fn location(&self) -> String;
+}
+
+#[cfg(test)]
+pub use memory::MemoryStore;
+
+#[cfg(test)]
+mod memory {
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    use super::{Blob, VaultStore};
+    use crate::types::Result;
+
+    /// Keeps the blobs in memory, so the layers above can be tested without
+    /// touching the file system
+    #[derive(Default)]
+    pub struct MemoryStore {
+        blobs: Mutex<HashMap<Blob, Vec<u8>>>,
+    }
+
+    impl VaultStore for MemoryStore {
+        fn read(&self, blob: Blob) -> Result<Option<Vec<u8>>> {
+            Ok(self.blobs.lock().unwrap().get(&blob).cloned())
+        }
+
+        fn exists(&self, blob: Blob) -> bool {
+            self.blobs.lock().unwrap().contains_key(&blob)
+        }
+
+        fn write(&self, blob: Blob, contents: &[u8]) -> Result<()> {
+            self.blobs.lock().unwrap().insert(blob, contents.to_vec());
+            Ok(())
+        }
+
+        fn remove(&self, blob: Blob) -> Result<()> {
+            self.blobs.lock().unwrap().remove(&blob);
+            Ok(())
+        }
+
+        fn backups(&self) -> Result<Vec<i64>> {
+            let mut backups: Vec<i64> = self.blobs.lock().unwrap()
+                .keys()
+                .filter_map(|blob| match blob {
+                    Blob::Backup(created_at) => Some(*created_at),
+                    _ => None,
+                })
+                .collect();
+
+            backups.sort_by_key(|created_at| std::cmp::Reverse(*created_at));
+            Ok(backups)
+        }
+
+        fn replace_all(&self, changes: &[(Blob, Option<Vec<u8>>)]) -> Result<()> {
+            let mut blobs = self.blobs.lock().unwrap();
+            for (blob, contents) in changes {
+                match contents {
+                    Some(contents) => blobs.insert(*blob, contents.clone()),
+                    None => blobs.remove(blob),
+                };
+            }
+            Ok(())
+        }
+
+        fn reset(&self) -> Result<()> {
+            self.blobs.lock().unwrap().clear();
+            Ok(())
+        }
+
+        fn location(&self) -> String {
+            "memory".to_string()
+        }
+    }
+}
@@ -1,7 +1,16 @@
-use crate::crypto::{encrypt_data, decrypt_data};
+use std::time::{Duration, Instant};
+
+use tauri::{AppHandle, Manager, Runtime};
+use zeroize::Zeroizing;
+
+use crate::crypto::{Keyfile, VaultKey};
 use crate::google_drive::{GoogleDriveAuth, GoogleDriveClient};
+use crate::state::AppState;
 use crate::storage::Storage;
 use crate::types::{OtpApp, AppError, Result};
+use crate::vault::{self, APPS_SCHEMA_VERSION};
+
+const SYNC_CHECK_INTERVAL: Duration = Duration::from_secs(60);
 
 pub struct SyncManager {
     storage: Storage,
@@ -9,9 +18,9 @@ pub struct SyncManager {
 }
 
 impl SyncManager {
-    pub fn new() -> Self {
+    pub fn new(storage: Storage) -> Self {
         Self {
-            storage: Storage::new(),
+            storage,
             client: GoogleDriveClient::new(),
         }
     }
@@ -21,11 +30,11 @@ impl SyncManager {
     pub async fn sync_to_google_drive(
         &self,
         apps: &[OtpApp],
-        key: &[u8; 32],
+        key: &VaultKey,
         auth: &GoogleDriveAuth,
     ) -> Result<()> {
-        let json = serde_json::to_string(apps)?;
-        let encrypted = encrypt_data(&json, key)?;
+        let json = Zeroizing::new(serde_json::to_string(apps)?);
+        let encrypted = vault::encrypt(&json, key, APPS_SCHEMA_VERSION)?;
         
         let filename = "plaxo-otp-backup.enc";
         
@@ -34,12 +43,12 @@ impl SyncManager {
         match self.client.find_file(auth, filename).await? {
             Some(file_id) => {
                 tracing::info!("Updating existing file in Google Drive...");
-                self.client.update_file(auth, &file_id, encrypted.as_bytes()).await?;
+                self.client.update_file(auth, &file_id, &encrypted).await?;
                 tracing::info!("File updated successfully in Google Drive!");
             }
             None => {
                 tracing::info!("Creating new file in Google Drive...");
-                let file_id = self.client.upload_file(auth, filename, encrypted.as_bytes()).await?;
+                let file_id = self.client.upload_file(auth, filename, &encrypted).await?;
                 tracing::info!("File created successfully in Google Drive! ID: {}", file_id);
             }
         }
@@ -48,9 +57,13 @@ impl SyncManager {
         Ok(())
     }
 
+    /// Downloads the cloud copy. It may have been written by another install,
+    /// with its own data key, in which case it is unlocked with `password`.
     pub async fn sync_from_google_drive(
         &self,
-        key: &[u8; 32],
+        key: &VaultKey,
+        password: Option<&str>,
+        keyfile: Option<&Keyfile>,
         auth: &GoogleDriveAuth,
     ) -> Result<Vec<OtpApp>> {
         let filename = "plaxo-otp-backup.enc";
@@ -59,11 +72,17 @@ impl SyncManager {
             Some(file_id) => {
                 let encrypted_data = self.client.download_file(auth, &file_id).await?;
                 
-                let encrypted_str = String::from_utf8(encrypted_data)
-                    .map_err(|_| AppError::GoogleDrive("Corrupted data in Google Drive".to_string()))?;
+                let decrypted = match vault::decrypt(&encrypted_data, key) {
+                    Ok(decrypted) => decrypted,
+                    Err(AppError::InvalidMasterPassword) => {
+                        // The password is not kept in memory, so the caller has to ask for it
+                        let password = password.ok_or(AppError::CloudPasswordRequired)?;
+                        vault::decrypt_with_password(&encrypted_data, password, keyfile)?.0
+                    }
+                    Err(e) => return Err(e),
+                };
                 
-                let decrypted = decrypt_data(&encrypted_str, key)?;
-                let apps: Vec<OtpApp> = serde_json::from_str(&decrypted)?;
+                let apps = vault::migrate_apps(&decrypted.data, decrypted.header.schema_version)?;
                 
                 Ok(apps)
             }
@@ -71,13 +90,13 @@ impl SyncManager {
         }
     }
 
-    pub async fn save_google_auth(&self, auth: &GoogleDriveAuth, key: &[u8; 32]) -> Result<()> {
-        let json = serde_json::to_string(auth)?;
+    pub async fn save_google_auth(&self, auth: &GoogleDriveAuth, key: &VaultKey) -> Result<()> {
+        let json = Zeroizing::new(serde_json::to_string(auth)?);
         self.storage.save_google_auth(&json, key)?;
         Ok(())
     }
 
-    pub async fn load_google_auth(&self, key: &[u8; 32]) -> Result<GoogleDriveAuth> {
+    pub async fn load_google_auth(&self, key: &VaultKey) -> Result<GoogleDriveAuth> {
         let json = self.storage.load_google_auth(key)?;
         let mut auth: GoogleDriveAuth = serde_json::from_str(&json)?;
         
@@ -95,8 +114,35 @@ impl SyncManager {
     }
 }
 
-impl Default for SyncManager {
-    fn default() -> Self {
-        Self::new()
-    }
+/// Uploads the vault to Google Drive every `sync_interval_minutes` of the
+/// settings, while the vault is unlocked and Drive connected. Runs in the
+/// backend, so it keeps going while the window is hidden in the tray.
+pub fn spawn_sync_scheduler<R: Runtime>(app: AppHandle<R>) {
+    tauri::async_runtime::spawn(async move {
+        let mut last_sync = Instant::now();
+
+        loop {
+            tokio::time::sleep(SYNC_CHECK_INTERVAL).await;
+
+            let state = app.state::<AppState>();
+            let interval = Duration::from_secs(u64::from(state.get_settings().sync_interval_minutes) * 60);
+            if interval.is_zero() || last_sync.elapsed() < interval || state.is_syncing() {
+                continue;
+            }
+
+            let (Some(key), Some(auth)) = (state.get_encryption_key(), state.get_google_auth()) else {
+                continue;
+            };
+
+            last_sync = Instant::now();
+            let apps = state.get_apps();
+            let sync_manager = SyncManager::new(app.state::<Storage>().inner().clone());
+            state.set_syncing(true);
+            let result = sync_manager.sync_to_google_drive(&apps, &key, &auth).await;
+            state.set_syncing(false);
+            if let Err(e) = result {
+                tracing::warn!("Scheduled Google Drive sync failed: {}", e);
+            }
+        }
+    });
 }
@@ -0,0 +1,101 @@
+use serde::{Deserialize, Serialize};
+
+/// Failures allowed before any delay is imposed
+pub const FREE_ATTEMPTS: u32 = 3;
+/// Lowest accepted value for the opt-in wipe, so a couple of typos can never erase the vault
+pub const MIN_WIPE_AFTER: u32 = 5;
+
+const BASE_DELAY_SECS: u64 = 5;
+const MAX_DELAY_SECS: u64 = 60 * 60;
+
+/// Failed unlock attempts, persisted in clear next to the vault since it has
+/// to be read before there is a key
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AttemptLog {
+    #[serde(default)]
+    pub failed_attempts: u32,
+    /// Unix time of the last failure, in seconds
+    #[serde(default)]
+    pub last_failure: i64,
+    /// Erase the vault after this many consecutive failures, off when `None`
+    #[serde(default)]
+    pub wipe_after: Option<u32>,
+}
+
+impl AttemptLog {
+    /// Delay required after the current number of failures, doubling with
+    /// each failure past `FREE_ATTEMPTS`
+    pub fn backoff_secs(&self) -> u64 {
+        if self.failed_attempts < FREE_ATTEMPTS {
+            return 0;
+        }
+
+        let doublings = (self.failed_attempts - FREE_ATTEMPTS).min(16);
+        (BASE_DELAY_SECS << doublings).min(MAX_DELAY_SECS)
+    }
+
+    /// Seconds left before another attempt is accepted. A clock set back
+    /// before the last failure restarts the full delay.
+    pub fn retry_after(&self, now: i64) -> u64 {
+        let elapsed = u64::try_from(now - self.last_failure).unwrap_or(0);
+        self.backoff_secs().saturating_sub(elapsed)
+    }
+
+    pub fn record_failure(&mut self, now: i64) {
+        self.failed_attempts = self.failed_attempts.saturating_add(1);
+        self.last_failure = now;
+    }
+
+    pub fn record_success(&mut self) {
+        self.failed_attempts = 0;
+        self.last_failure = 0;
+    }
+
+    pub fn should_wipe(&self) -> bool {
+        self.wipe_after.is_some_and(|limit| self.failed_attempts >= limit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_grows_and_is_capped() {
+        let mut log = AttemptLog::default();
+
+        for now in 0..FREE_ATTEMPTS as i64 {
+            assert_eq!(log.retry_after(now), 0);
+            log.record_failure(now);
+        }
+
+        assert_eq!(log.backoff_secs(), 5);
+        log.record_failure(100);
+        assert_eq!(log.backoff_secs(), 10);
+        assert_eq!(log.retry_after(104), 6);
+        assert_eq!(log.retry_after(110), 0);
+
+        log.failed_attempts = 1000;
+        assert_eq!(log.backoff_secs(), MAX_DELAY_SECS);
+
+        log.record_success();
+        assert_eq!(log.retry_after(0), 0);
+    }
+
+    #[test]
+    fn test_clock_rollback_keeps_the_delay() {
+        let mut log = AttemptLog { failed_attempts: FREE_ATTEMPTS, ..Default::default() };
+        log.record_failure(1_000);
+
+        assert_eq!(log.retry_after(500), log.backoff_secs());
+    }
+
+    #[test]
+    fn test_wipe_is_opt_in() {
+        let mut log = AttemptLog { failed_attempts: 50, ..Default::default() };
+        assert!(!log.should_wipe());
+
+        log.wipe_after = Some(MIN_WIPE_AFTER);
+        assert!(log.should_wipe());
+    }
+}
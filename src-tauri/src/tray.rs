@@ -3,20 +3,38 @@ use tauri::{
 };
 
 use crate::commands::{get_autostart_status, set_autostart};
+use crate::lock::lock_and_notify;
+use crate::settings::Language;
+use crate::state::AppState;
 
+/// Replaced with the configured language by `update_tray_menu` at startup
 pub fn create_tray() -> SystemTray {
-    let open = CustomMenuItem::new("open".to_string(), "Open");
-    let autostart = CustomMenuItem::new("autostart".to_string(), "Start with system");
-    let quit = CustomMenuItem::new("quit".to_string(), "Quit");
+    SystemTray::new().with_menu(build_menu(Language::default(), false))
+}
+
+fn build_menu(language: Language, autostart_enabled: bool) -> SystemTrayMenu {
+    let (open_text, lock_text, autostart_text, quit_text) = match language {
+        Language::PtBr => ("Abrir", "Bloquear", "Iniciar com o sistema", "Sair"),
+        Language::En => ("Open", "Lock", "Start with system", "Quit"),
+    };
+    let autostart_text = if autostart_enabled {
+        format!("✓ {}", autostart_text)
+    } else {
+        autostart_text.to_string()
+    };
+    
+    let open = CustomMenuItem::new("open".to_string(), open_text);
+    let lock = CustomMenuItem::new("lock".to_string(), lock_text);
+    let autostart = CustomMenuItem::new("autostart".to_string(), autostart_text);
+    let quit = CustomMenuItem::new("quit".to_string(), quit_text);
     
-    let tray_menu = SystemTrayMenu::new()
+    SystemTrayMenu::new()
         .add_item(open)
+        .add_item(lock)
         .add_native_item(tauri::SystemTrayMenuItem::Separator)
         .add_item(autostart)
         .add_native_item(tauri::SystemTrayMenuItem::Separator)
-        .add_item(quit);
-    
-    SystemTray::new().with_menu(tray_menu)
+        .add_item(quit)
 }
 
 pub fn handle_tray_event<R: Runtime>(app: &AppHandle<R>, event: SystemTrayEvent) {
@@ -39,6 +57,9 @@ pub fn handle_tray_event<R: Runtime>(app: &AppHandle<R>, event: SystemTrayEvent)
                         let _ = window.set_focus();
                     }
                 }
+                "lock" => {
+                    lock_and_notify(app);
+                }
                 "autostart" => {
                     // Toggle autostart
                     match get_autostart_status() {
@@ -63,22 +84,10 @@ pub fn handle_tray_event<R: Runtime>(app: &AppHandle<R>, event: SystemTrayEvent)
     }
 }
 
+/// Rebuilds the menu in the configured language
 pub fn update_tray_menu<R: Runtime>(app: &AppHandle<R>, autostart_enabled: bool) {
-    let open = CustomMenuItem::new("open".to_string(), "Open");
-    let autostart_text = if autostart_enabled {
-        "✓ Start with system"
-    } else {
-        "Start with system"
-    };
-    let autostart = CustomMenuItem::new("autostart".to_string(), autostart_text);
-    let quit = CustomMenuItem::new("quit".to_string(), "Quit");
-    
-    let tray_menu = SystemTrayMenu::new()
-        .add_item(open)
-        .add_native_item(tauri::SystemTrayMenuItem::Separator)
-        .add_item(autostart)
-        .add_native_item(tauri::SystemTrayMenuItem::Separator)
-        .add_item(quit);
+    let language = app.state::<AppState>().get_settings().language;
+    let tray_menu = build_menu(language, autostart_enabled);
     
     if let Err(e) = app.tray_handle().set_menu(tray_menu) {
         tracing::error!("Failed to update tray menu: {}", e);
@@ -0,0 +1,134 @@
+use base64::{Engine as _, engine::general_purpose};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use zeroize::Zeroizing;
+
+use crate::crypto::{decrypt_with_nonce, derive_pbkdf2_key};
+use crate::types::{AppError, Result};
+
+// Fixed by the 2FAS apps for password-protected backups
+const PBKDF2_ITERATIONS: u32 = 10_000;
+
+/// Top level of a 2FAS backup (`*.2fas`). Password-protected backups leave
+/// `services` empty and carry them in `servicesEncrypted` instead.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TwoFasBackup {
+    // Kept as raw values so one malformed service cannot fail the whole import
+    #[serde(default)]
+    pub services: Vec<Value>,
+    #[serde(default)]
+    pub groups: Vec<TwoFasGroup>,
+    #[serde(default)]
+    pub schema_version: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub services_encrypted: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TwoFasGroup {
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TwoFasService {
+    pub name: String,
+    pub secret: String,
+    #[serde(default)]
+    pub updated_at: i64,
+    // Schema version 1 backups only have name and secret
+    #[serde(default)]
+    pub otp: TwoFasOtp,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group_id: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TwoFasOtp {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub account: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub issuer: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub digits: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub period: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub algorithm: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub counter: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+}
+
+impl TwoFasBackup {
+    pub fn group_name(&self, service: &TwoFasService) -> Option<String> {
+        let group_id = service.group_id.as_deref()?;
+
+        self.groups
+            .iter()
+            .find(|g| g.id == group_id)
+            .map(|g| g.name.clone())
+            .filter(|name| !name.is_empty())
+    }
+}
+
+pub fn is_encrypted(backup: &TwoFasBackup) -> bool {
+    backup.services_encrypted.as_deref().is_some_and(|s| !s.is_empty())
+}
+
+/// Parses a 2FAS backup, decrypting its services with `password` when the
+/// backup is protected. The returned backup always has `services` filled in.
+pub fn read_backup(content: &str, password: Option<&str>) -> Result<TwoFasBackup> {
+    let invalid = |e: serde_json::Error| AppError::Serialization(format!("Invalid 2FAS file format: {}", e));
+    let value: Value = serde_json::from_str(content).map_err(invalid)?;
+
+    // Every field has a default, so without this any JSON object would pass
+    // as an empty backup
+    if value.get("services").is_none() && value.get("servicesEncrypted").is_none() {
+        return Err(AppError::Serialization(
+            "Invalid 2FAS file format: no services or servicesEncrypted".to_string(),
+        ));
+    }
+
+    let mut backup: TwoFasBackup = serde_json::from_value(value).map_err(invalid)?;
+
+    if is_encrypted(&backup) {
+        let password = password
+            .filter(|p| !p.is_empty())
+            .ok_or_else(|| AppError::Encryption("This 2FAS backup is encrypted, a password is required".to_string()))?;
+
+        let services = Zeroizing::new(decrypt_services(backup.services_encrypted.as_deref().unwrap_or_default(), password)?);
+        backup.services = serde_json::from_slice(&services)
+            .map_err(|e| AppError::Serialization(format!("Invalid 2FAS services: {}", e)))?;
+    }
+
+    Ok(backup)
+}
+
+// `servicesEncrypted` is "ciphertext:salt:iv", each part base64 encoded and
+// the GCM tag appended to the ciphertext
+fn decrypt_services(encrypted: &str, password: &str) -> Result<Vec<u8>> {
+    let parts: Vec<&str> = encrypted.split(':').collect();
+    let [ciphertext, salt, iv] = parts[..] else {
+        return Err(AppError::Encryption("Invalid encrypted 2FAS backup".to_string()));
+    };
+
+    let key = derive_pbkdf2_key(password.as_bytes(), &decode_base64(salt)?, PBKDF2_ITERATIONS);
+
+    decrypt_with_nonce(&key, &decode_base64(iv)?, &decode_base64(ciphertext)?)
+        .map_err(|_| AppError::Encryption("Invalid password for this 2FAS backup".to_string()))
+}
+
+fn decode_base64(value: &str) -> Result<Vec<u8>> {
+    general_purpose::STANDARD
+        .decode(value)
+        .map_err(|_| AppError::Encryption("Invalid base64 data in 2FAS backup".to_string()))
+}
@@ -1,17 +1,286 @@
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+use crate::secret::Secret;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum OtpAlgorithm {
+    #[default]
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+impl OtpAlgorithm {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OtpAlgorithm::Sha1 => "SHA1",
+            OtpAlgorithm::Sha256 => "SHA256",
+            OtpAlgorithm::Sha512 => "SHA512",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum OtpType {
+    #[default]
+    Totp,
+    Hotp,
+    Steam,
+}
+
+impl OtpType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OtpType::Totp => "totp",
+            OtpType::Hotp => "hotp",
+            OtpType::Steam => "steam",
+        }
+    }
+}
+
+pub const DEFAULT_DIGITS: usize = 6;
+pub const DEFAULT_PERIOD: u64 = 30;
+
+fn default_digits() -> usize {
+    DEFAULT_DIGITS
+}
+
+fn default_period() -> u64 {
+    DEFAULT_PERIOD
+}
+
+/// Everything besides name and secret that describes how codes are generated.
+/// Missing fields fall back to the classic SHA1 / 6 digits / 30 seconds TOTP.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct OtpParams {
+    #[serde(default)]
+    pub otp_type: OtpType,
+    #[serde(default)]
+    pub algorithm: OtpAlgorithm,
+    #[serde(default = "default_digits")]
+    pub digits: usize,
+    #[serde(default = "default_period")]
+    pub period: u64,
+    #[serde(default)]
+    pub counter: u64,
+    #[serde(default)]
+    pub issuer: Option<String>,
+    #[serde(default)]
+    pub account: Option<String>,
+}
+
+impl Default for OtpParams {
+    fn default() -> Self {
+        Self {
+            otp_type: OtpType::default(),
+            algorithm: OtpAlgorithm::default(),
+            digits: DEFAULT_DIGITS,
+            period: DEFAULT_PERIOD,
+            counter: 0,
+            issuer: None,
+            account: None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
 pub struct OtpApp {
     pub id: String,
     pub name: String,
-    pub secret: String,
+    pub secret: Secret,
+    // Entries saved before these fields existed load with the old hardcoded values
+    #[serde(default)]
+    pub algorithm: OtpAlgorithm,
+    #[serde(default = "default_digits")]
+    pub digits: usize,
+    #[serde(default = "default_period")]
+    pub period: u64,
+    #[serde(default)]
+    pub otp_type: OtpType,
+    // Next HOTP counter value; unused for TOTP entries
+    #[serde(default)]
+    pub counter: u64,
+    #[serde(default)]
+    pub issuer: Option<String>,
+    #[serde(default)]
+    pub account: Option<String>,
+    #[serde(default)]
+    pub group: Option<String>,
+    #[serde(default)]
+    pub note: Option<String>,
+}
+
+impl OtpApp {
+    pub fn new(name: String, secret: String) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            name,
+            secret: Secret::new(secret),
+            algorithm: OtpAlgorithm::default(),
+            digits: DEFAULT_DIGITS,
+            period: DEFAULT_PERIOD,
+            otp_type: OtpType::default(),
+            counter: 0,
+            issuer: None,
+            account: None,
+            group: None,
+            note: None,
+        }
+    }
+
+    pub fn with_params(name: String, secret: String, params: OtpParams) -> Self {
+        Self {
+            algorithm: params.algorithm,
+            digits: params.digits,
+            period: params.period,
+            otp_type: params.otp_type,
+            counter: params.counter,
+            issuer: params.issuer,
+            account: params.account,
+            ..Self::new(name, secret)
+        }
+    }
+
+    pub fn params(&self) -> OtpParams {
+        OtpParams {
+            otp_type: self.otp_type,
+            algorithm: self.algorithm,
+            digits: self.digits,
+            period: self.period,
+            counter: self.counter,
+            issuer: self.issuer.clone(),
+            account: self.account.clone(),
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct QrData {
     pub name: String,
     pub secret: String,
+    #[serde(flatten)]
+    pub params: OtpParams,
+}
+
+/// An entry an importer could not take, with the reason shown to the user
+#[derive(Debug, Serialize, Clone)]
+pub struct SkippedEntry {
+    pub name: String,
+    pub reason: String,
+}
+
+/// An entry an import would create, with the problems that would keep it out
+#[derive(Debug, Serialize, Clone)]
+pub struct ImportPreviewEntry {
+    pub app: OtpApp,
+    pub warnings: Vec<String>,
+    /// Already in the vault, so the duplicate policy decides what happens to it
+    pub duplicate: bool,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct ImportPreview {
+    pub entries: Vec<ImportPreviewEntry>,
+    pub invalid: Vec<SkippedEntry>,
+}
+
+/// What to do with an imported entry that is already in the vault
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicatePolicy {
+    #[default]
+    Skip,
+    /// Keep the stored secret and id, take everything else from the import
+    Overwrite,
+    KeepBoth,
+}
+
+/// Whether Google Drive is connected, as reported by `check_google_auth`
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum GoogleAuthStatus {
+    Disconnected,
+    Connected,
+    /// Connected, but the cloud copy was written by another install and is
+    /// only merged once its master password is given
+    PasswordRequired,
+}
+
+/// Outcome of an import, listing entries by name
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct ImportReport {
+    pub added: Vec<String>,
+    pub updated: Vec<String>,
+    pub duplicates: Vec<String>,
+    pub invalid: Vec<SkippedEntry>,
+}
+
+/// A local backup of the vault file
+#[derive(Debug, Serialize, Clone)]
+pub struct BackupInfo {
+    pub id: String,
+    /// Unix time in milliseconds
+    pub created_at: i64,
+    pub size: u64,
+    /// `None` when the current key cannot open it
+    pub entry_count: Option<usize>,
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum VaultFileKind {
+    Main,
+    /// Left behind by a save that was interrupted before its rename
+    Temp,
+    Backup,
+}
+
+/// One vault file as seen by the integrity check
+#[derive(Debug, Serialize, Clone)]
+pub struct VaultFileStatus {
+    pub id: String,
+    pub kind: VaultFileKind,
+    pub entry_count: Option<usize>,
+    /// Why the file cannot be read, `None` when it can
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct IntegrityReport {
+    /// Newest contents first
+    pub files: Vec<VaultFileStatus>,
+    /// The newest readable file, when the vault file itself cannot be opened
+    pub repair_from: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum QrImageFormat {
+    Png,
+    Svg,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct QrCodeImage {
+    // Text encoded in the QR code (an otpauth:// or otpauth-migration:// URI)
+    pub content: String,
+    pub format: QrImageFormat,
+    // Base64 PNG bytes or SVG markup, depending on `format`
+    pub data: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    #[serde(rename = "2fas")]
+    TwoFas,
+    Aegis,
+    OtpauthUris,
+    /// Entries encrypted under a passphrase of their own, see `backup.rs`
+    EncryptedBackup,
 }
 
 #[derive(Debug, Error, Serialize)]
@@ -28,18 +297,39 @@ pub enum AppError {
     #[error("Invalid secret key: {0}")]
     InvalidSecret(String),
     
+    #[error("Invalid input: {0}")]
+    InvalidInput(String),
+    
     #[error("Master password not set")]
     NoMasterPassword,
     
     #[error("Invalid master password")]
     InvalidMasterPassword,
     
+    #[error("This vault also needs its keyfile")]
+    KeyfileRequired,
+    
+    #[error("Invalid recovery key")]
+    InvalidRecoveryKey,
+    
+    #[error("Too many failed attempts, try again in {retry_after_secs} seconds")]
+    TooManyAttempts { retry_after_secs: u64 },
+    
+    #[error("Too many failed attempts, the vault was erased")]
+    VaultWiped,
+    
+    #[error("The vault file was changed by another program, reload it before saving")]
+    VaultChanged,
+    
     #[error("App not found")]
     AppNotFound,
     
     #[error("Google Drive error: {0}")]
     GoogleDrive(String),
     
+    #[error("The cloud copy was encrypted on another device, enter its master password to restore it")]
+    CloudPasswordRequired,
+    
     #[error("QR code error: {0}")]
     QrCode(String),
 }
@@ -0,0 +1,520 @@
+use serde::Deserialize;
+use serde_json::Value;
+use zeroize::Zeroizing;
+
+use crate::crypto::{
+    decrypt_data, decrypt_with_aad, encrypt_with_aad, normalize_recovery_key, Kdf, KeyProtection, KeySlot,
+    Keyfile, SlotKind, VaultKey, NONCE_SIZE,
+};
+use crate::types::{AppError, OtpApp, OtpParams, Result};
+
+const MAGIC: &[u8; 4] = b"PLXV";
+
+/// Container layout written by this version:
+///
+/// ```text
+/// magic "PLXV" | format u16 | slot count u8 | slots | schema u32 | nonce (12) | ciphertext + tag
+/// slot: kind u8 | kdf id u8 | kdf params | nonce (12) | wrapped key length u8 | wrapped key
+/// ```
+///
+/// Integers are little-endian and everything before the nonce is the AES-GCM
+/// associated data, slots included. Format 0 is the original headerless base64
+/// string, format 1 the JSON envelope that first stored KDF parameters and
+/// format 2 the binary header with a single KDF in place of the slots. Only
+/// keys that have not been moved to slots yet still write format 2.
+pub const FORMAT_VERSION: u16 = 3;
+const FORMAT_HEADERLESS: u16 = 0;
+const FORMAT_JSON_ENVELOPE: u16 = 1;
+const FORMAT_DIRECT_KEY: u16 = 2;
+
+const KDF_ID_LEGACY_SHA256: u8 = 0;
+const KDF_ID_ARGON2ID: u8 = 1;
+
+const SLOT_ID_PASSWORD: u8 = 1;
+const SLOT_ID_RECOVERY: u8 = 2;
+const SLOT_ID_PASSWORD_KEYFILE: u8 = 3;
+
+/// Version of the entries JSON in `apps.enc`, upgraded by `migrate_apps`
+pub const APPS_SCHEMA_VERSION: u32 = 2;
+
+// Files from before the header carry no schema version. Migrations only fill
+// in what is missing, so treating them all as the first schema is safe.
+const LEGACY_SCHEMA_VERSION: u32 = 1;
+
+/// Each step upgrades the entries JSON by one schema version, starting at 1
+const APPS_MIGRATIONS: &[fn(Value) -> Result<Value>] = &[add_otp_params];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VaultHeader {
+    pub format_version: u16,
+    pub protection: KeyProtection,
+    pub schema_version: u32,
+}
+
+#[derive(Debug)]
+pub struct DecryptedVault {
+    pub header: VaultHeader,
+    /// The decrypted JSON, wiped when the vault is dropped
+    pub data: Zeroizing<String>,
+}
+
+impl DecryptedVault {
+    /// True when the file should be written again in the current container format
+    pub fn is_outdated(&self) -> bool {
+        self.header.format_version < FORMAT_VERSION
+    }
+}
+
+#[derive(Deserialize)]
+struct JsonEnvelope {
+    kdf: Kdf,
+    data: String,
+}
+
+pub fn encrypt(data: &str, key: &VaultKey, schema_version: u32) -> Result<Vec<u8>> {
+    let format_version = match key.protection {
+        KeyProtection::Direct(_) => FORMAT_DIRECT_KEY,
+        KeyProtection::Slots(_) => FORMAT_VERSION,
+    };
+
+    let header = VaultHeader {
+        format_version,
+        protection: key.protection.clone(),
+        schema_version,
+    };
+
+    let mut content = encode_header(&header)?;
+    let (nonce, ciphertext) = encrypt_with_aad(key.key.expose(), data.as_bytes(), &content)?;
+
+    content.extend_from_slice(&nonce);
+    content.extend_from_slice(&ciphertext);
+    Ok(content)
+}
+
+/// Reads the header of any known format without decrypting anything
+pub fn read_header(content: &[u8]) -> Result<VaultHeader> {
+    if content.starts_with(MAGIC) {
+        return decode_header(content).map(|(header, _)| header);
+    }
+
+    let text = legacy_text(content)?;
+
+    Ok(match serde_json::from_str::<JsonEnvelope>(text) {
+        Ok(envelope) => {
+            envelope.kdf.check_cost()?;
+            VaultHeader {
+                format_version: FORMAT_JSON_ENVELOPE,
+                protection: KeyProtection::Direct(envelope.kdf),
+                schema_version: LEGACY_SCHEMA_VERSION,
+            }
+        }
+        Err(_) => VaultHeader {
+            format_version: FORMAT_HEADERLESS,
+            protection: KeyProtection::Direct(Kdf::LegacySha256),
+            schema_version: LEGACY_SCHEMA_VERSION,
+        },
+    })
+}
+
+/// Decrypts with an already unlocked key. Files with key slots may have
+/// been written under other slots, only the data key has to match.
+pub fn decrypt(content: &[u8], key: &VaultKey) -> Result<DecryptedVault> {
+    let header = read_header(content)?;
+
+    match (&header.protection, &key.protection) {
+        (KeyProtection::Direct(file_kdf), KeyProtection::Direct(key_kdf)) if file_kdf == key_kdf => {}
+        (KeyProtection::Slots(_), KeyProtection::Slots(_)) => {}
+        _ => return Err(AppError::InvalidMasterPassword),
+    }
+
+    let data = Zeroizing::new(match header.format_version {
+        FORMAT_HEADERLESS => decrypt_data(legacy_text(content)?, key.key.expose())?,
+        FORMAT_JSON_ENVELOPE => {
+            let envelope: JsonEnvelope = serde_json::from_str(legacy_text(content)?)?;
+            decrypt_data(&envelope.data, key.key.expose())?
+        }
+        _ => {
+            let (_, header_len) = decode_header(content)?;
+            let (aad, rest) = content.split_at(header_len);
+
+            if rest.len() < NONCE_SIZE {
+                return Err(AppError::Encryption("Data too short".to_string()));
+            }
+
+            let (nonce, ciphertext) = rest.split_at(NONCE_SIZE);
+            let plaintext = decrypt_with_aad(key.key.expose(), nonce, ciphertext, aad)?;
+
+            String::from_utf8(plaintext)
+                .map_err(|_| AppError::Encryption("Invalid UTF-8 data".to_string()))?
+        }
+    });
+
+    Ok(DecryptedVault { header, data })
+}
+
+/// Unlocks the file's key with the password, and the keyfile if the file
+/// needs one, and decrypts it
+pub fn decrypt_with_password(content: &[u8], password: &str, keyfile: Option<&Keyfile>) -> Result<(DecryptedVault, VaultKey)> {
+    let key = match read_header(content)?.protection {
+        KeyProtection::Direct(kdf) => VaultKey::derive(password, kdf)?,
+        KeyProtection::Slots(slots) => VaultKey::unlock(&slots, SlotKind::Password, password, keyfile)?,
+    };
+
+    let vault = decrypt(content, &key)?;
+    Ok((vault, key))
+}
+
+/// Unlocks the file's key with its recovery key and decrypts it
+pub fn decrypt_with_recovery_key(content: &[u8], recovery_key: &str) -> Result<(DecryptedVault, VaultKey)> {
+    let slots = match read_header(content)?.protection {
+        KeyProtection::Slots(slots) if slots.iter().any(|s| s.kind == SlotKind::Recovery) => slots,
+        _ => return Err(AppError::Encryption("This vault has no recovery key".to_string())),
+    };
+
+    let key = VaultKey::unlock(&slots, SlotKind::Recovery, &normalize_recovery_key(recovery_key), None)?;
+    let vault = decrypt(content, &key)?;
+    Ok((vault, key))
+}
+
+/// Whether the password slot also needs a keyfile, readable before unlocking
+pub fn requires_keyfile(content: &[u8]) -> Result<bool> {
+    Ok(match read_header(content)?.protection {
+        KeyProtection::Direct(_) => false,
+        KeyProtection::Slots(slots) => slots.iter().any(|s| s.kind == SlotKind::Password && s.keyfile),
+    })
+}
+
+/// Runs the entries JSON through every migration after `schema_version`
+pub fn migrate_apps(data: &str, schema_version: u32) -> Result<Vec<OtpApp>> {
+    if schema_version == 0 || schema_version > APPS_SCHEMA_VERSION {
+        return Err(AppError::Serialization(format!(
+            "Unsupported vault schema version {}, it may have been written by a newer version",
+            schema_version
+        )));
+    }
+
+    let mut value: Value = serde_json::from_str(data)?;
+
+    for (index, migration) in APPS_MIGRATIONS.iter().enumerate().skip(schema_version as usize - 1) {
+        tracing::info!("Migrating vault schema {} -> {}", index + 1, index + 2);
+        value = migration(value)?;
+    }
+
+    Ok(serde_json::from_value(value)?)
+}
+
+// Schema 1 entries only had id, name and secret
+fn add_otp_params(mut value: Value) -> Result<Value> {
+    let Value::Object(defaults) = serde_json::to_value(OtpParams::default())? else {
+        return Err(AppError::Serialization("Invalid default OTP parameters".to_string()));
+    };
+
+    let entries = value.as_array_mut()
+        .ok_or_else(|| AppError::Serialization("Vault entries are not a list".to_string()))?;
+
+    for entry in entries.iter_mut().filter_map(|e| e.as_object_mut()) {
+        for (field, default) in &defaults {
+            entry.entry(field.clone()).or_insert_with(|| default.clone());
+        }
+    }
+
+    Ok(value)
+}
+
+fn legacy_text(content: &[u8]) -> Result<&str> {
+    std::str::from_utf8(content)
+        .map(str::trim)
+        .map_err(|_| AppError::Encryption("Unrecognized vault file format".to_string()))
+}
+
+fn encode_header(header: &VaultHeader) -> Result<Vec<u8>> {
+    let mut bytes = MAGIC.to_vec();
+    bytes.extend_from_slice(&header.format_version.to_le_bytes());
+
+    match &header.protection {
+        KeyProtection::Direct(kdf) => encode_kdf(&mut bytes, kdf)?,
+        KeyProtection::Slots(slots) => {
+            let count = u8::try_from(slots.len())
+                .map_err(|_| AppError::Encryption("Too many key slots".to_string()))?;
+            bytes.push(count);
+
+            for slot in slots {
+                bytes.push(match (slot.kind, slot.keyfile) {
+                    (SlotKind::Password, false) => SLOT_ID_PASSWORD,
+                    (SlotKind::Password, true) => SLOT_ID_PASSWORD_KEYFILE,
+                    (SlotKind::Recovery, false) => SLOT_ID_RECOVERY,
+                    (SlotKind::Recovery, true) => {
+                        return Err(AppError::Encryption("Recovery keys cannot need a keyfile".to_string()));
+                    }
+                });
+                encode_kdf(&mut bytes, &slot.kdf)?;
+                bytes.extend_from_slice(&slot.nonce);
+
+                let wrapped_len = u8::try_from(slot.wrapped_key.len())
+                    .map_err(|_| AppError::Encryption("Wrapped key is too long".to_string()))?;
+                bytes.push(wrapped_len);
+                bytes.extend_from_slice(&slot.wrapped_key);
+            }
+        }
+    }
+
+    bytes.extend_from_slice(&header.schema_version.to_le_bytes());
+    Ok(bytes)
+}
+
+fn encode_kdf(bytes: &mut Vec<u8>, kdf: &Kdf) -> Result<()> {
+    match kdf {
+        Kdf::LegacySha256 => bytes.push(KDF_ID_LEGACY_SHA256),
+        Kdf::Argon2id { m_cost, t_cost, p_cost, salt } => {
+            let salt = hex::decode(salt)
+                .map_err(|_| AppError::Encryption("Invalid key derivation salt".to_string()))?;
+            let salt_len = u8::try_from(salt.len())
+                .map_err(|_| AppError::Encryption("Key derivation salt is too long".to_string()))?;
+
+            bytes.push(KDF_ID_ARGON2ID);
+            bytes.extend_from_slice(&m_cost.to_le_bytes());
+            bytes.extend_from_slice(&t_cost.to_le_bytes());
+            bytes.extend_from_slice(&p_cost.to_le_bytes());
+            bytes.push(salt_len);
+            bytes.extend_from_slice(&salt);
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the header and its length in bytes
+fn decode_header(content: &[u8]) -> Result<(VaultHeader, usize)> {
+    let mut reader = HeaderReader { bytes: content, pos: MAGIC.len() };
+
+    let format_version = reader.u16()?;
+    if format_version > FORMAT_VERSION {
+        return Err(AppError::Serialization(format!(
+            "Unsupported vault format version {}, it may have been written by a newer version",
+            format_version
+        )));
+    }
+
+    let protection = if format_version == FORMAT_DIRECT_KEY {
+        KeyProtection::Direct(decode_kdf(&mut reader)?)
+    } else {
+        let count = reader.u8()?;
+        let mut slots = Vec::with_capacity(count as usize);
+
+        for _ in 0..count {
+            let (kind, keyfile) = match reader.u8()? {
+                SLOT_ID_PASSWORD => (SlotKind::Password, false),
+                SLOT_ID_PASSWORD_KEYFILE => (SlotKind::Password, true),
+                SLOT_ID_RECOVERY => (SlotKind::Recovery, false),
+                other => return Err(AppError::Encryption(format!("Unknown key slot type: {}", other))),
+            };
+            // Slots replace those of their kind, so more than one is a damaged
+            // or crafted file, and each would cost a full key derivation
+            if slots.iter().any(|slot: &KeySlot| slot.kind == kind) {
+                return Err(AppError::Encryption("Vault has more than one key slot of a kind".to_string()));
+            }
+            let kdf = decode_kdf(&mut reader)?;
+            let nonce = reader.take(NONCE_SIZE)?.try_into().unwrap();
+            let wrapped_len = reader.u8()? as usize;
+            let wrapped_key = reader.take(wrapped_len)?.to_vec();
+
+            slots.push(KeySlot { kind, kdf, keyfile, nonce, wrapped_key });
+        }
+
+        KeyProtection::Slots(slots)
+    };
+
+    let schema_version = reader.u32()?;
+
+    Ok((VaultHeader { format_version, protection, schema_version }, reader.pos))
+}
+
+fn decode_kdf(reader: &mut HeaderReader) -> Result<Kdf> {
+    Ok(match reader.u8()? {
+        KDF_ID_LEGACY_SHA256 => Kdf::LegacySha256,
+        KDF_ID_ARGON2ID => {
+            let m_cost = reader.u32()?;
+            let t_cost = reader.u32()?;
+            let p_cost = reader.u32()?;
+            let salt_len = reader.u8()? as usize;
+            let salt = hex::encode(reader.take(salt_len)?);
+            let kdf = Kdf::Argon2id { m_cost, t_cost, p_cost, salt };
+            kdf.check_cost()?;
+            kdf
+        }
+        other => return Err(AppError::Encryption(format!("Unknown key derivation function: {}", other))),
+    })
+}
+
+struct HeaderReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> HeaderReader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let slice = self.bytes
+            .get(self.pos..self.pos + len)
+            .ok_or_else(|| AppError::Serialization("Truncated vault header".to_string()))?;
+        self.pos += len;
+        Ok(slice)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::{derive_key, encrypt_data};
+
+    fn test_key(password: &str) -> VaultKey {
+        VaultKey::generate_with(password, None, Kdf::argon2id(1024, 1, 1)).unwrap()
+    }
+
+    #[test]
+    fn test_container_round_trip() {
+        let key = test_key("test_password");
+        let content = encrypt("[]", &key, APPS_SCHEMA_VERSION).unwrap();
+
+        assert!(content.starts_with(MAGIC));
+
+        let header = read_header(&content).unwrap();
+        assert_eq!(header.format_version, FORMAT_VERSION);
+        assert_eq!(header.protection, key.protection);
+        assert_eq!(header.schema_version, APPS_SCHEMA_VERSION);
+
+        let (vault, derived) = decrypt_with_password(&content, "test_password", None).unwrap();
+        assert_eq!(*vault.data, "[]");
+        assert!(!vault.is_outdated());
+        assert_eq!(derived, key);
+
+        assert!(decrypt_with_password(&content, "wrong", None).is_err());
+    }
+
+    #[test]
+    fn test_header_is_authenticated() {
+        let key = test_key("test_password");
+        let mut content = encrypt("[]", &key, 1).unwrap();
+
+        // Claim a different schema version without touching the ciphertext
+        let schema_offset = encode_header(&read_header(&content).unwrap()).unwrap().len() - 4;
+        content[schema_offset] = 2;
+
+        assert!(decrypt(&content, &key).is_err());
+    }
+
+    #[test]
+    fn test_excessive_kdf_cost_is_rejected() {
+        let header = VaultHeader {
+            format_version: FORMAT_DIRECT_KEY,
+            protection: KeyProtection::Direct(Kdf::argon2id(u32::MAX, 1, 1)),
+            schema_version: APPS_SCHEMA_VERSION,
+        };
+        let content = encode_header(&header).unwrap();
+
+        assert!(read_header(&content).is_err());
+        assert!(decrypt_with_password(&content, "pw", None).is_err());
+    }
+
+    #[test]
+    fn test_duplicate_key_slots_are_rejected() {
+        let mut key = VaultKey::generate_with("pw", None, Kdf::argon2id(1024, 1, 1)).unwrap();
+        let header = |protection: &KeyProtection| VaultHeader {
+            format_version: FORMAT_VERSION,
+            protection: protection.clone(),
+            schema_version: APPS_SCHEMA_VERSION,
+        };
+        assert!(read_header(&encode_header(&header(&key.protection)).unwrap()).is_ok());
+
+        if let KeyProtection::Slots(slots) = &mut key.protection {
+            slots.push(slots[0].clone());
+        }
+        assert!(read_header(&encode_header(&header(&key.protection)).unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_headerless_files_are_migrated() {
+        let legacy = encrypt_data(r#"[{"id":"1","name":"Old","secret":"JBSWY3DPEHPK3PXP"}]"#, &derive_key("pw")).unwrap();
+
+        let (vault, key) = decrypt_with_password(legacy.as_bytes(), "pw", None).unwrap();
+        assert_eq!(vault.header.format_version, FORMAT_HEADERLESS);
+        assert!(vault.is_outdated());
+        assert_eq!(key.protection, KeyProtection::Direct(Kdf::LegacySha256));
+
+        let apps = migrate_apps(&vault.data, vault.header.schema_version).unwrap();
+        assert_eq!(apps[0].name, "Old");
+        assert_eq!(apps[0].params(), OtpParams::default());
+    }
+
+    #[test]
+    fn test_json_envelope_files_are_read() {
+        let kdf = Kdf::argon2id(1024, 1, 1);
+        let key = VaultKey::derive("pw", kdf.clone()).unwrap();
+        let envelope = serde_json::json!({
+            "kdf": kdf,
+            "data": encrypt_data("[]", key.key.expose()).unwrap(),
+        })
+        .to_string();
+
+        let vault = decrypt(envelope.as_bytes(), &key).unwrap();
+        assert_eq!(vault.header.format_version, FORMAT_JSON_ENVELOPE);
+        assert_eq!(*vault.data, "[]");
+    }
+
+    #[test]
+    fn test_direct_key_files_are_read() {
+        let key = VaultKey::derive("pw", Kdf::argon2id(1024, 1, 1)).unwrap();
+        let content = encrypt("[]", &key, APPS_SCHEMA_VERSION).unwrap();
+
+        let (vault, derived) = decrypt_with_password(&content, "pw", None).unwrap();
+        assert_eq!(vault.header.format_version, FORMAT_DIRECT_KEY);
+        assert!(vault.is_outdated());
+        assert_eq!(derived, key);
+        assert!(decrypt_with_recovery_key(&content, "anything").is_err());
+    }
+
+    #[test]
+    fn test_recovery_key_opens_the_vault() {
+        let recovery_key = crate::crypto::generate_recovery_key();
+        let key = test_key("pw")
+            .with_slot(SlotKind::Recovery, &normalize_recovery_key(&recovery_key), None, Kdf::argon2id(1024, 1, 1))
+            .unwrap();
+        let content = encrypt("[]", &key, APPS_SCHEMA_VERSION).unwrap();
+
+        let (vault, recovered) = decrypt_with_recovery_key(&content, &recovery_key).unwrap();
+        assert_eq!(*vault.data, "[]");
+        assert_eq!(recovered, key);
+
+        assert!(decrypt_with_recovery_key(&content, "pw").is_err());
+        assert!(decrypt_with_password(&content, &recovery_key, None).is_err());
+    }
+
+    #[test]
+    fn test_keyfile_requirement_is_in_the_header() {
+        let keyfile = Keyfile::from_contents(b"keyfile").unwrap();
+        let key = VaultKey::generate_with("pw", Some(&keyfile), Kdf::argon2id(1024, 1, 1)).unwrap();
+        let content = encrypt("[]", &key, APPS_SCHEMA_VERSION).unwrap();
+
+        assert!(requires_keyfile(&content).unwrap());
+        assert!(!requires_keyfile(&encrypt("[]", &test_key("pw"), APPS_SCHEMA_VERSION).unwrap()).unwrap());
+        assert_eq!(read_header(&content).unwrap().protection, key.protection);
+        assert!(decrypt_with_password(&content, "pw", Some(&keyfile)).is_ok());
+    }
+
+    #[test]
+    fn test_every_schema_has_a_migration() {
+        assert_eq!(APPS_MIGRATIONS.len() as u32, APPS_SCHEMA_VERSION - 1);
+        assert!(migrate_apps("[]", APPS_SCHEMA_VERSION + 1).is_err());
+    }
+}
@@ -0,0 +1,86 @@
+use std::ffi::OsStr;
+use std::path::Path;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use tauri::{AppHandle, Manager, Runtime};
+
+use crate::state::AppState;
+use crate::storage::Storage;
+use crate::store::Blob;
+use crate::types::{AppError, Result};
+
+/// Emitted with the entry count after the vault file was reloaded
+pub const VAULT_RELOADED_EVENT: &str = "vault-reloaded";
+/// Emitted with the error when a changed vault file could not be opened
+pub const VAULT_RELOAD_FAILED_EVENT: &str = "vault-reload-failed";
+
+/// Sync clients write a file in several steps; the file is only read once
+/// the events stop
+const SETTLE_DELAY: Duration = Duration::from_millis(500);
+
+/// Watches `data_dir` for other programs replacing the vault file and
+/// reloads it into the unlocked vault. The directory is watched rather than
+/// the file, since saves replace the file by renaming another over it.
+pub fn spawn_vault_watcher<R: Runtime>(app: AppHandle<R>, data_dir: &Path) -> Result<()> {
+    let (sender, receiver) = mpsc::channel();
+
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| match event {
+        Ok(event) if touches_vault(&event) => {
+            let _ = sender.send(());
+        }
+        Ok(_) => {}
+        Err(e) => tracing::warn!("Vault watcher error: {}", e),
+    })
+    .map_err(|e| AppError::Io(e.to_string()))?;
+
+    watcher
+        .watch(data_dir, RecursiveMode::NonRecursive)
+        .map_err(|e| AppError::Io(e.to_string()))?;
+
+    thread::spawn(move || {
+        // Dropping the watcher stops it, so it lives as long as this thread
+        let _watcher = watcher;
+
+        while receiver.recv().is_ok() {
+            thread::sleep(SETTLE_DELAY);
+            while receiver.try_recv().is_ok() {}
+
+            reload(&app);
+        }
+    });
+
+    Ok(())
+}
+
+fn touches_vault(event: &Event) -> bool {
+    let vault_file = Blob::Vault.file_name();
+
+    !matches!(event.kind, EventKind::Access(_))
+        && event.paths.iter().any(|path| path.file_name() == Some(OsStr::new(&vault_file)))
+}
+
+/// Our own saves are recognised by `Storage` and leave the vault as it is
+fn reload<R: Runtime>(app: &AppHandle<R>) {
+    let state = app.state::<AppState>();
+    let storage = app.state::<Storage>();
+
+    // A locked vault reads the file afresh when it is unlocked
+    match state.reload_with(|apps, key| storage.reload_changed(apps, key)) {
+        Ok(true) => {
+            let count = state.apps.read().unwrap().len();
+            if let Err(e) = app.emit_all(VAULT_RELOADED_EVENT, count) {
+                tracing::error!("Failed to notify frontend of reload: {}", e);
+            }
+        }
+        Ok(false) => {}
+        Err(e) => {
+            tracing::warn!("Could not reload the changed vault file: {}", e);
+            if let Err(e) = app.emit_all(VAULT_RELOAD_FAILED_EVENT, e.to_string()) {
+                tracing::error!("Failed to notify frontend of reload failure: {}", e);
+            }
+        }
+    }
+}
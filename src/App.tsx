@@ -1,14 +1,20 @@
 import { useState, useEffect, useMemo, useCallback, useRef } from 'react';
 import { invoke } from '@tauri-apps/api/tauri';
-import { Shield, Lock } from 'lucide-react';
+import { listen } from '@tauri-apps/api/event';
+import { Shield, ShieldCheck, Lock, KeyRound, Settings as SettingsIcon } from 'lucide-react';
 import MasterPasswordModal from './components/MasterPasswordModal';
 import VirtualizedOtpList from './components/VirtualizedOtpList';
 import SearchBar from './components/SearchBar';
-import AddAppModal from './components/AddAppModal';
+import AddAppModal, { OtpParams } from './components/AddAppModal';
 import ImportModal from './components/ImportModal';
+import ExportModal from './components/ExportModal';
 import ConfirmModal from './components/ConfirmModal';
+import RecoveryKeyModal from './components/RecoveryKeyModal';
+import SettingsModal from './components/SettingsModal';
+import SecurityModal from './components/SecurityModal';
 import { useDebounce } from './hooks/useDebounce';
 import { useMemoryManager } from './hooks/useMemoryManager';
+import { useSettings } from './hooks/useSettings';
 
 import { GoogleDriveSync } from './components/GoogleDriveSync';
 
@@ -16,19 +22,39 @@ interface OtpApp {
   id: string;
   name: string;
   secret: string;
+  algorithm: 'SHA1' | 'SHA256' | 'SHA512';
+  digits: number;
+  period: number;
+  otp_type: 'totp' | 'hotp' | 'steam';
+  counter: number;
+  issuer: string | null;
+  account: string | null;
 }
 
+const VAULT_CHANGED_MESSAGE =
+  'O cofre foi alterado por outro programa e não pôde ser recarregado. Bloqueie e desbloqueie o cofre para carregar a versão mais recente; até lá, nenhuma alteração será salva.';
+
 function App() {
   const [isAuthenticated, setIsAuthenticated] = useState(false);
   const [apps, setApps] = useState<OtpApp[]>([]);
   const [searchTerm, setSearchTerm] = useState('');
   const [showAddModal, setShowAddModal] = useState(false);
   const [showImportModal, setShowImportModal] = useState(false);
+  const [showExportModal, setShowExportModal] = useState(false);
   const [showResetModal, setShowResetModal] = useState(false);
+  const [showSettingsModal, setShowSettingsModal] = useState(false);
+  const [showSecurityModal, setShowSecurityModal] = useState(false);
   const [hasMasterPassword, setHasMasterPassword] = useState(false);
+  const [recoveryKey, setRecoveryKey] = useState<string | null>(null);
+  // Asks before creating a recovery key, or replacing the one the vault has
+  const [recoveryOffer, setRecoveryOffer] = useState<'missing' | 'replace' | null>(null);
+  const [keyfileRequired, setKeyfileRequired] = useState(false);
+  const [vaultNotice, setVaultNotice] = useState<string | null>(null);
   const [isVisible, setIsVisible] = useState(true);
   const cleanupRef = useRef<NodeJS.Timeout>();
   const { forceCleanup } = useMemoryManager();
+  const settings = useSettings();
+  const sortOrder = settings?.sort_order;
 
   useEffect(() => {
     const handleVisibilityChange = () => {
@@ -61,6 +87,7 @@ function App() {
       try {
         const exists = await invoke<boolean>('has_master_password');
         setHasMasterPassword(exists);
+        setKeyfileRequired(await invoke<boolean>('is_keyfile_required'));
       } catch (error) {
         console.error('Failed to check master password:', error);
       }
@@ -68,6 +95,39 @@ function App() {
     checkMasterPassword();
   }, []);
 
+  useEffect(() => {
+    const unlisten = listen('vault-locked', () => {
+      setIsAuthenticated(false);
+      setApps([]);
+      setSearchTerm('');
+      setHasMasterPassword(true);
+      setVaultNotice(null);
+    });
+    return () => {
+      unlisten.then((fn) => fn());
+    };
+  }, []);
+
+  useEffect(() => {
+    if (!isAuthenticated) return;
+
+    // Restart the backend idle timer, at most every 30 seconds
+    let lastReport = 0;
+    const reportActivity = () => {
+      const now = Date.now();
+      if (now - lastReport > 30000) {
+        lastReport = now;
+        invoke('record_activity').catch(() => {});
+      }
+    };
+
+    const events = ['mousemove', 'mousedown', 'keydown', 'wheel'];
+    events.forEach((name) => window.addEventListener(name, reportActivity, { passive: true }));
+    return () => {
+      events.forEach((name) => window.removeEventListener(name, reportActivity));
+    };
+  }, [isAuthenticated]);
+
   const loadApps = useCallback(async () => {
     try {
       const loadedApps = await invoke<OtpApp[]>('get_apps');
@@ -77,19 +137,106 @@ function App() {
     }
   }, []);
 
-  const handleMasterPassword = useCallback(async (password: string) => {
+  useEffect(() => {
+    if (settings) {
+      document.documentElement.lang = settings.language;
+    }
+  }, [settings]);
+
+  // get_apps returns the entries in the configured order
+  useEffect(() => {
+    if (isAuthenticated && sortOrder) {
+      loadApps();
+    }
+  }, [sortOrder]);
+
+  // The vault file was replaced by another program, e.g. a sync client
+  useEffect(() => {
+    const unlistenReloaded = listen('vault-reloaded', () => {
+      setVaultNotice(null);
+      loadApps();
+    });
+    const unlistenFailed = listen('vault-reload-failed', () => {
+      setVaultNotice(VAULT_CHANGED_MESSAGE);
+    });
+    return () => {
+      unlistenReloaded.then((fn) => fn());
+      unlistenFailed.then((fn) => fn());
+    };
+  }, [loadApps]);
+
+  // Turns throttling and wipe errors into a message for the unlock screen
+  const unlockErrorMessage = useCallback((error: unknown): string | false => {
+    if (error === 'VaultWiped') {
+      setHasMasterPassword(false);
+      return 'Muitas tentativas inválidas. O cofre foi apagado.';
+    }
+    if (error === 'KeyfileRequired') {
+      setKeyfileRequired(true);
+      return 'Este cofre também exige o arquivo-chave.';
+    }
+    const throttled = (error as { TooManyAttempts?: { retry_after_secs: number } })?.TooManyAttempts;
+    if (throttled) {
+      return `Muitas tentativas inválidas. Tente novamente em ${throttled.retry_after_secs}s.`;
+    }
+    return false;
+  }, []);
+
+  const handleMasterPassword = useCallback(async (password: string, keyfile?: number[]) => {
     try {
-      const isValid = await invoke<boolean>('verify_master_password', { password });
+      const isValid = await invoke<boolean>('verify_master_password', { password, keyfile });
       if (isValid) {
         setIsAuthenticated(true);
         await loadApps();
+        if (!hasMasterPassword) {
+          // First run: the recovery key is shown once, right after setup
+          setRecoveryKey(await invoke<string>('create_recovery_key'));
+          setHasMasterPassword(true);
+          setKeyfileRequired(!!keyfile);
+        } else if (!(await invoke<boolean>('has_recovery_key'))) {
+          // Vaults from older versions are moved to key slots on unlock, without one
+          setRecoveryOffer('missing');
+        }
       }
       return isValid;
     } catch (error) {
       console.error('Failed to verify password:', error);
-      return false;
+      return unlockErrorMessage(error);
     }
-  }, [loadApps]);
+  }, [loadApps, hasMasterPassword, unlockErrorMessage]);
+
+  const handleRecover = useCallback(async (recoveryKey: string, newPassword: string, keyfile?: number[]) => {
+    try {
+      const isValid = await invoke<boolean>('unlock_with_recovery_key', { recoveryKey, newPassword, keyfile });
+      if (isValid) {
+        setKeyfileRequired(!!keyfile);
+        setIsAuthenticated(true);
+        await loadApps();
+      }
+      return isValid;
+    } catch (error) {
+      console.error('Failed to recover vault:', error);
+      if (error === 'InvalidRecoveryKey') return false;
+      return unlockErrorMessage(error) || 'Este cofre não possui chave de recuperação.';
+    }
+  }, [loadApps, unlockErrorMessage]);
+
+  const openRecoveryOffer = useCallback(async () => {
+    const hasRecoveryKey = await invoke<boolean>('has_recovery_key');
+    setRecoveryOffer(hasRecoveryKey ? 'replace' : 'missing');
+  }, []);
+
+  const handleCreateRecoveryKey = useCallback(async () => {
+    setRecoveryOffer(null);
+    try {
+      setRecoveryKey(await invoke<string>('create_recovery_key'));
+    } catch (error) {
+      console.error('Failed to create recovery key:', error);
+      if (error === 'VaultChanged') {
+        setVaultNotice(VAULT_CHANGED_MESSAGE);
+      }
+    }
+  }, []);
 
   const handleReset = useCallback(async () => {
     try {
@@ -103,14 +250,19 @@ function App() {
     }
   }, []);
 
-  const handleAddApp = useCallback(async (name: string, secret: string) => {
+  const handleAddApp = useCallback(async (name: string, secret: string, params?: OtpParams) => {
     try {
-      await invoke('add_app', { name, secret });
+      await invoke('add_app', { name, secret, params });
       await loadApps();
     } catch (error) {
       console.error('Failed to add app:', error);
+      if (error === 'VaultChanged') {
+        setVaultNotice(VAULT_CHANGED_MESSAGE);
+      }
       // Re-throw with better error message
-      const errorMsg = typeof error === 'string' ? error : 'Erro ao adicionar aplicativo';
+      const errorMsg = error === 'VaultChanged'
+        ? VAULT_CHANGED_MESSAGE
+        : typeof error === 'string' ? error : 'Erro ao adicionar aplicativo';
       throw new Error(errorMsg);
     }
   }, [loadApps]);
@@ -121,6 +273,9 @@ function App() {
       await loadApps();
     } catch (error) {
       console.error('Failed to delete app:', error);
+      if (error === 'VaultChanged') {
+        setVaultNotice(VAULT_CHANGED_MESSAGE);
+      }
     }
   }, [loadApps]);
 
@@ -148,6 +303,8 @@ function App() {
         <MasterPasswordModal
           onSubmit={handleMasterPassword}
           onReset={() => setShowResetModal(true)}
+          onRecover={handleRecover}
+          keyfileRequired={keyfileRequired}
           isFirstTime={!hasMasterPassword}
         />
 
@@ -178,14 +335,51 @@ function App() {
                 Plaxo OTP
               </h1>
             </div>
-            <GoogleDriveSync />
+            <div className="flex items-center gap-2">
+              <GoogleDriveSync />
+              <button
+                onClick={() => setShowSettingsModal(true)}
+                disabled={!settings}
+                className="text-plaxo-text-secondary hover:text-plaxo-text p-2 rounded-lg hover:bg-plaxo-background/50 transition-colors"
+                title="Configurações"
+              >
+                <SettingsIcon className="w-5 h-5" />
+              </button>
+              <button
+                onClick={() => setShowSecurityModal(true)}
+                className="text-plaxo-text-secondary hover:text-plaxo-text p-2 rounded-lg hover:bg-plaxo-background/50 transition-colors"
+                title="Segurança"
+              >
+                <ShieldCheck className="w-5 h-5" />
+              </button>
+              <button
+                onClick={openRecoveryOffer}
+                className="text-plaxo-text-secondary hover:text-plaxo-text p-2 rounded-lg hover:bg-plaxo-background/50 transition-colors"
+                title="Chave de recuperação"
+              >
+                <KeyRound className="w-5 h-5" />
+              </button>
+              <button
+                onClick={() => invoke('lock_vault')}
+                className="text-plaxo-text-secondary hover:text-plaxo-text p-2 rounded-lg hover:bg-plaxo-background/50 transition-colors"
+                title="Bloquear"
+              >
+                <Lock className="w-5 h-5" />
+              </button>
+            </div>
           </div>
           <SearchBar
             value={searchTerm}
             onChange={setSearchTerm}
             onAddClick={() => setShowAddModal(true)}
             onImportClick={() => setShowImportModal(true)}
+            onExportClick={() => setShowExportModal(true)}
           />
+          {vaultNotice && (
+            <div className="mt-3 p-3 bg-red-500/10 border border-red-500/20 rounded-lg text-sm text-red-500">
+              {vaultNotice}
+            </div>
+          )}
         </div>
         <div className="content-scrollable">
           {displayApps.length === 0 ? (
@@ -221,6 +415,46 @@ function App() {
             onImportComplete={loadApps}
           />
         )}
+
+        {showExportModal && (
+          <ExportModal
+            appIds={apps.map((app) => app.id)}
+            onClose={() => setShowExportModal(false)}
+          />
+        )}
+
+        {showSettingsModal && settings && (
+          <SettingsModal
+            settings={settings}
+            onClose={() => setShowSettingsModal(false)}
+          />
+        )}
+
+        {showSecurityModal && (
+          <SecurityModal
+            keyfileRequired={keyfileRequired}
+            onKeyfileChange={setKeyfileRequired}
+            onVaultRepaired={loadApps}
+            onClose={() => setShowSecurityModal(false)}
+          />
+        )}
+
+        <ConfirmModal
+          isOpen={recoveryOffer !== null}
+          title="Chave de recuperação"
+          message={recoveryOffer === 'replace'
+            ? 'Gerar uma nova chave de recuperação? A chave atual deixará de funcionar.'
+            : 'Este cofre não possui chave de recuperação. Sem ela, esquecer a senha mestre significa perder o acesso aos seus códigos. Deseja criar uma agora?'}
+          confirmText={recoveryOffer === 'replace' ? 'Gerar nova chave' : 'Criar chave'}
+          cancelText="Agora não"
+          onConfirm={handleCreateRecoveryKey}
+          onCancel={() => setRecoveryOffer(null)}
+        />
+
+        <RecoveryKeyModal
+          recoveryKey={recoveryKey}
+          onClose={() => setRecoveryKey(null)}
+        />
       </div>
     </div>
   );
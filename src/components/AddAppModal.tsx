@@ -2,8 +2,23 @@ import { useState } from 'react';
 import { Plus, X, Loader2, AlertCircle, Image } from 'lucide-react';
 import { invoke } from '@tauri-apps/api/tauri';
 
+export interface OtpParams {
+  otp_type: 'totp' | 'hotp';
+  algorithm: 'SHA1' | 'SHA256' | 'SHA512';
+  digits: number;
+  period: number;
+  counter: number;
+  issuer: string | null;
+  account: string | null;
+}
+
+interface QrData extends OtpParams {
+  name: string;
+  secret: string;
+}
+
 interface Props {
-  onSubmit: (name: string, secret: string) => Promise<void>;
+  onSubmit: (name: string, secret: string, params?: OtpParams) => Promise<void>;
   onClose: () => void;
 }
 
@@ -13,6 +28,7 @@ export default function AddAppModal({ onSubmit, onClose }: Props) {
   const [loading, setLoading] = useState(false);
   const [error, setError] = useState('');
   const [processingQR, setProcessingQR] = useState(false);
+  const [qrParams, setQrParams] = useState<OtpParams | undefined>();
 
   const handlePaste = async (e: React.ClipboardEvent) => {
     e.preventDefault();
@@ -20,13 +36,14 @@ export default function AddAppModal({ onSubmit, onClose }: Props) {
     setError('');
     
     try {
-      const qrData = await invoke<{ name: string; secret: string }>('decode_qr_from_clipboard');
+      const qrData = await invoke<QrData>('decode_qr_from_clipboard');
       
       if (!qrData.secret || qrData.secret.trim().length === 0) {
         throw new Error('QR code não contém dados válidos');
       }
       
       setSecret(qrData.secret.trim());
+      setQrParams(qrData);
       if (qrData.name && qrData.name !== 'Unknown') {
         setName(qrData.name);
       }
@@ -61,7 +78,7 @@ export default function AddAppModal({ onSubmit, onClose }: Props) {
     setError('');
 
     try {
-      await onSubmit(trimmedName, trimmedSecret);
+      await onSubmit(trimmedName, trimmedSecret, qrParams);
       onClose();
     } catch (err) {
       const errorMsg = err instanceof Error ? err.message : String(err);
@@ -114,7 +131,10 @@ export default function AddAppModal({ onSubmit, onClose }: Props) {
               <input
                 type="text"
                 value={secret}
-                onChange={(e) => setSecret(e.target.value)}
+                onChange={(e) => {
+                setSecret(e.target.value);
+                setQrParams(undefined);
+              }}
                 onPaste={handlePaste}
                 placeholder="Cole aqui o código secreto ou Ctrl+V uma imagem QR..."
                 className="w-full px-4 py-3 bg-plaxo-background/50 border border-plaxo-border rounded-xl text-plaxo-text placeholder-plaxo-text-secondary focus:outline-none focus:border-plaxo-primary focus:ring-2 focus:ring-plaxo-primary/20 transition-all font-mono text-sm"
@@ -0,0 +1,203 @@
+import { useState } from 'react';
+import { Download, X, Loader2, AlertCircle, CheckCircle, QrCode } from 'lucide-react';
+import { invoke } from '@tauri-apps/api/tauri';
+import QrCodeModal, { QrCodeImage } from './QrCodeModal';
+
+interface Props {
+  // Entries offered to the Google Authenticator export
+  appIds: string[];
+  onClose: () => void;
+}
+
+type Format = 'encrypted_backup' | '2fas' | 'aegis' | 'otpauth_uris';
+
+const FORMATS: Record<Format, { label: string; extension: string }> = {
+  encrypted_backup: { label: 'Backup criptografado (Plaxo OTP)', extension: '.json' },
+  '2fas': { label: '2FAS', extension: '.2fas' },
+  aegis: { label: 'Aegis (JSON)', extension: '.json' },
+  otpauth_uris: { label: 'Lista de URIs otpauth://', extension: '.txt' },
+};
+
+const inputClass =
+  'w-full px-4 py-3 bg-plaxo-background/50 border border-plaxo-border rounded-xl text-plaxo-text placeholder-plaxo-text-secondary focus:outline-none focus:border-plaxo-primary focus:ring-2 focus:ring-plaxo-primary/20 transition-all text-sm';
+
+export default function ExportModal({ appIds, onClose }: Props) {
+  const [format, setFormat] = useState<Format>('encrypted_backup');
+  const [path, setPath] = useState('');
+  const [passphrase, setPassphrase] = useState('');
+  const [confirmPassphrase, setConfirmPassphrase] = useState('');
+  const [loading, setLoading] = useState(false);
+  const [error, setError] = useState('');
+  const [success, setSuccess] = useState('');
+  const [migrationCodes, setMigrationCodes] = useState<QrCodeImage[] | null>(null);
+
+  const encrypted = format === 'encrypted_backup';
+
+  const handleExport = async () => {
+    setError('');
+    setSuccess('');
+
+    if (!path.trim()) {
+      setError('Informe o caminho do arquivo');
+      return;
+    }
+    if (encrypted && !passphrase) {
+      setError('Informe a senha do backup');
+      return;
+    }
+    if (encrypted && passphrase !== confirmPassphrase) {
+      setError('As senhas não coincidem');
+      return;
+    }
+
+    setLoading(true);
+
+    try {
+      const count = await invoke<number>('export_vault', {
+        format,
+        path: path.trim(),
+        passphrase: encrypted ? passphrase : null,
+      });
+      setPassphrase('');
+      setConfirmPassphrase('');
+      setSuccess(`${count} aplicativo(s) exportado(s) para ${path.trim()}`);
+    } catch (err) {
+      setError(typeof err === 'string' ? err : 'Erro ao exportar');
+    } finally {
+      setLoading(false);
+    }
+  };
+
+  const handleMigrationExport = async () => {
+    setLoading(true);
+    setError('');
+    setSuccess('');
+
+    try {
+      setMigrationCodes(await invoke<QrCodeImage[]>('export_migration_qr_codes', { appIds, format: 'png' }));
+    } catch (err) {
+      setError(typeof err === 'string' ? err : 'Erro ao gerar os códigos QR');
+    } finally {
+      setLoading(false);
+    }
+  };
+
+  if (migrationCodes) {
+    return (
+      <QrCodeModal
+        title="Google Authenticator"
+        images={migrationCodes}
+        onClose={() => setMigrationCodes(null)}
+      />
+    );
+  }
+
+  return (
+    <div className="fixed inset-0 bg-black/50 backdrop-blur-sm flex items-center justify-center p-4 z-50" onClick={onClose}>
+      <div className="bg-plaxo-surface border border-plaxo-border rounded-2xl p-6 w-full max-w-md shadow-2xl max-h-full overflow-y-auto" onClick={(e) => e.stopPropagation()}>
+        <div className="flex items-center justify-between mb-6">
+          <div className="flex items-center gap-3">
+            <div className="flex items-center justify-center w-10 h-10 bg-plaxo-primary/10 rounded-xl">
+              <Download className="w-5 h-5 text-plaxo-primary" />
+            </div>
+            <h2 className="text-lg font-heading font-semibold text-plaxo-text">
+              Exportar
+            </h2>
+          </div>
+          <button
+            onClick={onClose}
+            className="text-plaxo-text-secondary hover:text-plaxo-text p-1.5 rounded-lg hover:bg-plaxo-background/50 transition-colors"
+            disabled={loading}
+          >
+            <X className="w-5 h-5" />
+          </button>
+        </div>
+
+        <div className="space-y-4">
+          <select
+            value={format}
+            onChange={(e) => setFormat(e.target.value as Format)}
+            className={inputClass}
+            disabled={loading}
+          >
+            {(Object.keys(FORMATS) as Format[]).map((key) => (
+              <option key={key} value={key}>{FORMATS[key].label}</option>
+            ))}
+          </select>
+
+          <input
+            type="text"
+            value={path}
+            onChange={(e) => setPath(e.target.value)}
+            placeholder={`Caminho do arquivo, ex.: /home/voce/plaxo-otp${FORMATS[format].extension}`}
+            className={inputClass}
+            disabled={loading}
+          />
+
+          {encrypted ? (
+            <>
+              <input
+                type="password"
+                value={passphrase}
+                onChange={(e) => setPassphrase(e.target.value)}
+                placeholder="Senha do backup"
+                className={inputClass}
+                disabled={loading}
+              />
+              <input
+                type="password"
+                value={confirmPassphrase}
+                onChange={(e) => setConfirmPassphrase(e.target.value)}
+                placeholder="Confirme a senha do backup"
+                className={inputClass}
+                disabled={loading}
+              />
+            </>
+          ) : (
+            <div className="flex items-start gap-2 text-plaxo-error text-sm bg-plaxo-error/10 px-3 py-2 rounded-lg border border-plaxo-error/20">
+              <AlertCircle className="w-4 h-4 mt-0.5 shrink-0" />
+              Este formato guarda os segredos sem criptografia. Apague o arquivo assim que terminar de usá-lo.
+            </div>
+          )}
+
+          {error && (
+            <div className="flex items-center gap-2 text-plaxo-error text-sm bg-plaxo-error/10 px-3 py-2 rounded-lg border border-plaxo-error/20">
+              <AlertCircle className="w-4 h-4" />
+              {error}
+            </div>
+          )}
+
+          {success && (
+            <div className="flex items-center gap-2 text-plaxo-success text-sm bg-plaxo-success/10 px-3 py-2 rounded-lg border border-plaxo-success/20">
+              <CheckCircle className="w-4 h-4" />
+              {success}
+            </div>
+          )}
+
+          <button
+            onClick={handleExport}
+            disabled={loading}
+            className="w-full flex items-center justify-center gap-2 py-3 px-4 bg-plaxo-primary hover:bg-plaxo-primary-hover text-plaxo-background font-semibold rounded-xl transition-colors disabled:opacity-50 disabled:cursor-not-allowed"
+          >
+            {loading && <Loader2 className="w-4 h-4 animate-spin" />}
+            Exportar
+          </button>
+
+          <div className="border-t border-plaxo-border pt-4 space-y-2">
+            <p className="text-xs text-plaxo-text-secondary">
+              Para levar os códigos ao Google Authenticator, abra o app, toque em ⋮ → Transferir contas → Importar contas e escaneie os códigos mostrados.
+            </p>
+            <button
+              onClick={handleMigrationExport}
+              disabled={loading || appIds.length === 0}
+              className="w-full flex items-center justify-center gap-2 py-2.5 px-4 bg-plaxo-background/30 hover:bg-plaxo-background/50 text-plaxo-text border border-plaxo-border rounded-xl transition-colors text-sm font-medium disabled:opacity-50 disabled:cursor-not-allowed"
+            >
+              <QrCode className="w-4 h-4" />
+              Mostrar códigos QR do Google Authenticator
+            </button>
+          </div>
+        </div>
+      </div>
+    </div>
+  );
+}
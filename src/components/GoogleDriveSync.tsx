@@ -1,27 +1,58 @@
 import React, { useState, useEffect } from 'react';
 import { invoke } from '@tauri-apps/api/tauri';
-import { Cloud, CloudOff } from 'lucide-react';
+import { Cloud, CloudOff, FileKey, Loader2, X } from 'lucide-react';
 
 export const GoogleDriveSync: React.FC = () => {
   const [isAuthenticated, setIsAuthenticated] = useState(false);
   const [isLoading, setIsLoading] = useState(false);
+  // The cloud copy was written by another install and needs its password
+  const [passwordRequired, setPasswordRequired] = useState(false);
+  const [cloudPassword, setCloudPassword] = useState('');
+  const [cloudKeyfile, setCloudKeyfile] = useState<{ name: string; bytes: number[] } | null>(null);
+  const [cloudError, setCloudError] = useState('');
 
   useEffect(() => {
     checkAuth();
   }, []);
 
-  const checkAuth = async () => {
+  const checkAuth = async (password?: string, keyfile?: number[]) => {
     try {
-      const authenticated = await invoke<boolean>('check_google_auth');
-      setIsAuthenticated(authenticated);
-      if (authenticated) {
+      const status = await invoke<'disconnected' | 'connected' | 'password_required'>('check_google_auth', {
+        password: password ?? null,
+        keyfile: keyfile ?? null,
+      });
+      setIsAuthenticated(status !== 'disconnected');
+      setPasswordRequired(status === 'password_required');
+      if (status === 'connected') {
+        setCloudPassword('');
+        setCloudKeyfile(null);
         console.log('✅ Google Drive conectado - Sincronização automática ativa!');
       }
     } catch (error) {
-      console.log('Google Drive não conectado');
+      if (password) {
+        setCloudError(error as string);
+      } else {
+        console.log('Google Drive não conectado');
+      }
     }
   };
 
+  const handleCloudKeyfileSelect = async (e: React.ChangeEvent<HTMLInputElement>) => {
+    const file = e.target.files?.[0];
+    if (!file) return;
+    setCloudKeyfile({ name: file.name, bytes: Array.from(new Uint8Array(await file.arrayBuffer())) });
+  };
+
+  const handleCloudPassword = async (e: React.FormEvent) => {
+    e.preventDefault();
+    if (!cloudPassword.trim()) return;
+
+    setIsLoading(true);
+    setCloudError('');
+    await checkAuth(cloudPassword, cloudKeyfile?.bytes);
+    setIsLoading(false);
+  };
+
   const handleAuth = async () => {
     try {
       setIsLoading(true);
@@ -56,6 +87,59 @@ export const GoogleDriveSync: React.FC = () => {
           <Cloud size={18} className="text-plaxo-primary" />
         </div>
       )}
+
+      {passwordRequired && (
+        <div className="fixed inset-0 bg-black/50 backdrop-blur-sm flex items-center justify-center p-4 z-50">
+          <form
+            onSubmit={handleCloudPassword}
+            className="bg-plaxo-surface border border-plaxo-border rounded-2xl p-6 w-full max-w-md shadow-2xl space-y-4"
+          >
+            <div className="flex items-center justify-between">
+              <h2 className="text-lg font-heading font-semibold text-plaxo-text">
+                Cópia do Google Drive
+              </h2>
+              <button
+                type="button"
+                onClick={() => setPasswordRequired(false)}
+                className="text-plaxo-text-secondary hover:text-plaxo-text p-1.5 rounded-lg hover:bg-plaxo-background/50 transition-colors"
+                disabled={isLoading}
+              >
+                <X className="w-5 h-5" />
+              </button>
+            </div>
+            <p className="text-plaxo-text-secondary text-sm">
+              A cópia na nuvem foi criptografada em outro dispositivo. Digite a senha mestre usada nele para mesclá-la com este cofre.
+            </p>
+            <input
+              type="password"
+              value={cloudPassword}
+              onChange={(e) => setCloudPassword(e.target.value)}
+              placeholder="Senha mestre do outro dispositivo"
+              className="w-full px-4 py-3 bg-plaxo-background/50 border border-plaxo-border rounded-xl text-plaxo-text placeholder-plaxo-text-secondary focus:outline-none focus:border-plaxo-primary focus:ring-2 focus:ring-plaxo-primary/20 transition-all"
+              disabled={isLoading}
+              autoFocus
+            />
+            <label className="w-full flex items-center gap-2 px-4 py-3 bg-plaxo-background/50 border border-plaxo-border rounded-xl text-plaxo-text-secondary hover:border-plaxo-primary cursor-pointer transition-all text-sm">
+              <FileKey className="w-4 h-4" />
+              <span className="truncate">{cloudKeyfile ? cloudKeyfile.name : 'Arquivo-chave (opcional)'}</span>
+              <input type="file" onChange={handleCloudKeyfileSelect} className="hidden" disabled={isLoading} />
+            </label>
+            {cloudError && (
+              <div className="text-plaxo-error text-sm bg-plaxo-error/10 px-3 py-2 rounded-lg border border-plaxo-error/20">
+                {cloudError}
+              </div>
+            )}
+            <button
+              type="submit"
+              disabled={isLoading || !cloudPassword.trim()}
+              className="w-full flex items-center justify-center gap-2 py-3 bg-plaxo-primary hover:bg-plaxo-primary-hover text-plaxo-background font-semibold rounded-xl transition-all duration-200 disabled:opacity-50 disabled:cursor-not-allowed"
+            >
+              {isLoading && <Loader2 className="w-4 h-4 animate-spin" />}
+              Mesclar cópia da nuvem
+            </button>
+          </form>
+        </div>
+      )}
     </div>
   );
 };
@@ -2,32 +2,191 @@ import { useState } from 'react';
 import { Upload, X, Loader2, AlertCircle, CheckCircle, FileText } from 'lucide-react';
 import { invoke } from '@tauri-apps/api/tauri';
 
+interface ImportReport {
+  added: string[];
+  updated: string[];
+  duplicates: string[];
+  invalid: { name: string; reason: string }[];
+}
+
+interface ImportPreview {
+  entries: { app: { name: string; account?: string }; warnings: string[]; duplicate: boolean }[];
+  invalid: { name: string; reason: string }[];
+}
+
 interface Props {
   onClose: () => void;
   onImportComplete: () => void;
 }
 
+type Source = '2fas' | 'aegis' | 'google' | 'backup';
+
+interface SourceInfo {
+  label: string;
+  accept: string;
+  // Several files make up one import, e.g. the QR codes of a large export
+  multiple: boolean;
+  // Placeholder of the password field, null when the source has none
+  password: string | null;
+  dropText: string;
+  help: string[];
+}
+
+const SOURCES: Record<Source, SourceInfo> = {
+  '2fas': {
+    label: '2FAS',
+    accept: '.2fas',
+    multiple: false,
+    password: 'Senha do backup (se estiver protegido)',
+    dropText: 'Arraste seu arquivo .2fas aqui',
+    help: [
+      'Como exportar do 2FAS:',
+      '1. Abra o app 2FAS',
+      '2. Vá em Configurações → Backup',
+      '3. Toque em "Exportar" e salve o arquivo .2fas',
+    ],
+  },
+  aegis: {
+    label: 'Aegis',
+    accept: '.json',
+    multiple: false,
+    password: 'Senha do cofre (se estiver criptografado)',
+    dropText: 'Arraste seu arquivo de exportação do Aegis aqui',
+    help: [
+      'Como exportar do Aegis:',
+      '1. Abra o app Aegis',
+      '2. Vá em Configurações → Importar e exportar → Exportar',
+      '3. Escolha o formato JSON do Aegis e salve o arquivo',
+    ],
+  },
+  google: {
+    label: 'Google Authenticator',
+    accept: 'image/*',
+    multiple: true,
+    password: null,
+    dropText: 'Arraste as capturas de tela dos códigos QR aqui',
+    help: [
+      'Como exportar do Google Authenticator:',
+      '1. Abra o app e toque em ⋮ → Transferir contas → Exportar contas',
+      '2. Faça uma captura de tela de cada código QR mostrado',
+      '3. Selecione todas as capturas de uma vez',
+    ],
+  },
+  backup: {
+    label: 'Backup',
+    accept: '.json',
+    multiple: false,
+    password: 'Senha do backup',
+    dropText: 'Arraste seu backup criptografado do Plaxo OTP aqui',
+    help: [
+      'Como criar um backup:',
+      '1. Toque em "Exportar" na tela principal',
+      '2. Escolha "Backup criptografado" e defina uma senha',
+      '3. Guarde o arquivo e a senha em locais separados',
+    ],
+  },
+};
+
+const readBytes = async (file: File) => Array.from(new Uint8Array(await file.arrayBuffer()));
+
+// Reads the chosen files into the preview of what would be imported
+const previewFiles = async (source: Source, files: File[], password: string): Promise<ImportPreview> => {
+  switch (source) {
+    case '2fas':
+      if (!files[0].name.endsWith('.2fas')) {
+        throw 'Por favor, selecione um arquivo .2fas válido';
+      }
+      return invoke<ImportPreview>('preview_2fas_file', {
+        fileContent: await files[0].text(),
+        password: password || null,
+      });
+    case 'aegis':
+      if (!files[0].name.endsWith('.json')) {
+        throw 'Por favor, selecione um arquivo .json válido';
+      }
+      return invoke<ImportPreview>('preview_aegis_file', {
+        fileContent: await files[0].text(),
+        password: password || null,
+      });
+    case 'google':
+      return invoke<ImportPreview>('preview_google_migration_images', {
+        images: await Promise.all(files.map(readBytes)),
+      });
+    case 'backup':
+      if (!password) {
+        throw 'Informe a senha do backup antes de selecionar o arquivo';
+      }
+      return invoke<ImportPreview>('preview_backup_file', {
+        fileContent: await files[0].text(),
+        passphrase: password,
+      });
+  }
+};
+
 export default function ImportModal({ onClose, onImportComplete }: Props) {
   const [loading, setLoading] = useState(false);
   const [error, setError] = useState('');
   const [success, setSuccess] = useState('');
   const [dragOver, setDragOver] = useState(false);
+  const [source, setSource] = useState<Source>('2fas');
+  const [password, setPassword] = useState('');
+  const [preview, setPreview] = useState<ImportPreview | null>(null);
+  const [selected, setSelected] = useState<Set<number>>(new Set());
 
-  const handleFileSelect = async (file: File) => {
-    if (!file.name.endsWith('.2fas')) {
-      setError('Por favor, selecione um arquivo .2fas válido');
-      return;
-    }
+  const sourceInfo = SOURCES[source];
 
+  const handleFileSelect = async (files: File[]) => {
     setLoading(true);
     setError('');
     setSuccess('');
 
     try {
-      const content = await file.text();
-      const importedCount = await invoke<number>('import_2fas_file', { fileContent: content });
+      const result = await previewFiles(source, files, password);
+      
+      setPreview(result);
+      setSelected(new Set(
+        result.entries
+          .map((entry, index) => (entry.warnings.length === 0 && !entry.duplicate ? index : -1))
+          .filter((index) => index >= 0)
+      ));
+    } catch (err) {
+      setError(err as string || 'Erro ao importar arquivo');
+    } finally {
+      setLoading(false);
+    }
+  };
+
+  const toggleEntry = (index: number) => {
+    const next = new Set(selected);
+    if (next.has(index)) {
+      next.delete(index);
+    } else {
+      next.add(index);
+    }
+    setSelected(next);
+  };
+
+  const handleCommit = async () => {
+    if (!preview) return;
+
+    setLoading(true);
+    setError('');
+
+    try {
+      const report = await invoke<ImportReport>('commit_import', {
+        apps: preview.entries.filter((_, index) => selected.has(index)).map((entry) => entry.app),
+        policy: 'skip',
+      });
       
-      setSuccess(`${importedCount} aplicativo(s) importado(s) com sucesso!`);
+      const details = [
+        report.duplicates.length > 0 ? `${report.duplicates.length} duplicado(s) ignorado(s)` : '',
+        report.invalid.length > 0 ? `${report.invalid.length} inválido(s)` : '',
+      ].filter(Boolean).join(', ');
+      
+      setPreview(null);
+      setSuccess(
+        `${report.added.length} aplicativo(s) importado(s) com sucesso!` + (details ? ` (${details})` : '')
+      );
       setTimeout(() => {
         onImportComplete();
         onClose();
@@ -45,27 +204,27 @@ export default function ImportModal({ onClose, onImportComplete }: Props) {
     
     const files = Array.from(e.dataTransfer.files);
     if (files.length > 0) {
-      handleFileSelect(files[0]);
+      handleFileSelect(sourceInfo.multiple ? files : files.slice(0, 1));
     }
   };
 
   const handleFileInput = (e: React.ChangeEvent<HTMLInputElement>) => {
     const files = e.target.files;
     if (files && files.length > 0) {
-      handleFileSelect(files[0]);
+      handleFileSelect(Array.from(files));
     }
   };
 
   return (
     <div className="fixed inset-0 bg-black/50 backdrop-blur-sm flex items-center justify-center p-4 z-50" onClick={onClose}>
-      <div className="bg-plaxo-surface border border-plaxo-border rounded-2xl p-6 w-full max-w-md shadow-2xl" onClick={(e) => e.stopPropagation()}>
+      <div className="bg-plaxo-surface border border-plaxo-border rounded-2xl p-6 w-full max-w-md shadow-2xl max-h-full overflow-y-auto" onClick={(e) => e.stopPropagation()}>
         <div className="flex items-center justify-between mb-6">
           <div className="flex items-center gap-3">
             <div className="flex items-center justify-center w-10 h-10 bg-plaxo-primary/10 rounded-xl">
               <Upload className="w-5 h-5 text-plaxo-primary" />
             </div>
             <h2 className="text-lg font-heading font-semibold text-plaxo-text">
-              Importar do 2FAS
+              Importar
             </h2>
           </div>
           <button
@@ -78,6 +237,38 @@ export default function ImportModal({ onClose, onImportComplete }: Props) {
         </div>
 
         <div className="space-y-4">
+          <div className="grid grid-cols-2 gap-2">
+            {(Object.keys(SOURCES) as Source[]).map((key) => (
+              <button
+                key={key}
+                onClick={() => {
+                  setSource(key);
+                  setPreview(null);
+                  setError('');
+                }}
+                disabled={loading}
+                className={`px-3 py-2 text-sm rounded-lg border transition-colors ${
+                  source === key
+                    ? 'border-plaxo-primary bg-plaxo-primary/10 text-plaxo-text'
+                    : 'border-plaxo-border text-plaxo-text-secondary hover:text-plaxo-text'
+                }`}
+              >
+                {SOURCES[key].label}
+              </button>
+            ))}
+          </div>
+
+          {sourceInfo.password && (
+            <input
+              type="password"
+              value={password}
+              onChange={(e) => setPassword(e.target.value)}
+              placeholder={sourceInfo.password}
+              className="w-full px-4 py-3 bg-plaxo-background/50 border border-plaxo-border rounded-xl text-plaxo-text placeholder-plaxo-text-secondary focus:outline-none focus:border-plaxo-primary focus:ring-2 focus:ring-plaxo-primary/20 transition-all"
+              disabled={loading}
+            />
+          )}
+
           <div
             className={`relative border-2 border-dashed rounded-xl p-8 text-center transition-colors ${
               dragOver 
@@ -98,7 +289,7 @@ export default function ImportModal({ onClose, onImportComplete }: Props) {
                 <FileText className="w-8 h-8 text-plaxo-text-secondary mx-auto" />
                 <div>
                   <p className="text-plaxo-text font-medium mb-1">
-                    Arraste seu arquivo .2fas aqui
+                    {sourceInfo.dropText}
                   </p>
                   <p className="text-sm text-plaxo-text-secondary">
                     ou clique para selecionar
@@ -106,7 +297,8 @@ export default function ImportModal({ onClose, onImportComplete }: Props) {
                 </div>
                 <input
                   type="file"
-                  accept=".2fas"
+                  accept={sourceInfo.accept}
+                  multiple={sourceInfo.multiple}
                   onChange={handleFileInput}
                   className="absolute inset-0 w-full h-full opacity-0 cursor-pointer"
                   disabled={loading}
@@ -115,6 +307,54 @@ export default function ImportModal({ onClose, onImportComplete }: Props) {
             )}
           </div>
 
+          {preview && (
+            <div className="space-y-3">
+              <div className="max-h-60 overflow-y-auto space-y-2">
+                {preview.entries.map((entry, index) => (
+                  <label
+                    key={index}
+                    className="flex items-start gap-3 p-3 bg-plaxo-background/30 rounded-lg cursor-pointer"
+                  >
+                    <input
+                      type="checkbox"
+                      checked={selected.has(index)}
+                      onChange={() => toggleEntry(index)}
+                      disabled={loading}
+                      className="mt-1"
+                    />
+                    <div className="text-sm">
+                      <p className="text-plaxo-text font-medium">
+                        {entry.app.name}
+                        {entry.app.account && (
+                          <span className="text-plaxo-text-secondary font-normal"> · {entry.app.account}</span>
+                        )}
+                      </p>
+                      {entry.duplicate && (
+                        <p className="text-plaxo-text-secondary">Já existe no cofre</p>
+                      )}
+                      {entry.warnings.map((warning) => (
+                        <p key={warning} className="text-plaxo-error">{warning}</p>
+                      ))}
+                    </div>
+                  </label>
+                ))}
+                {preview.invalid.map((entry, index) => (
+                  <div key={`invalid-${index}`} className="p-3 text-sm bg-plaxo-error/10 rounded-lg">
+                    <p className="text-plaxo-text font-medium">{entry.name}</p>
+                    <p className="text-plaxo-error">{entry.reason}</p>
+                  </div>
+                ))}
+              </div>
+              <button
+                onClick={handleCommit}
+                disabled={loading || selected.size === 0}
+                className="w-full py-3 px-4 bg-plaxo-primary hover:bg-plaxo-primary-hover text-plaxo-background font-semibold rounded-xl transition-colors disabled:opacity-50 disabled:cursor-not-allowed"
+              >
+                Importar {selected.size} selecionado(s)
+              </button>
+            </div>
+          )}
+
           {error && (
             <div className="flex items-center gap-2 text-plaxo-error text-sm bg-plaxo-error/10 px-3 py-2 rounded-lg border border-plaxo-error/20">
               <AlertCircle className="w-4 h-4" />
@@ -130,10 +370,10 @@ export default function ImportModal({ onClose, onImportComplete }: Props) {
           )}
 
           <div className="text-xs text-plaxo-text-secondary bg-plaxo-background/30 p-3 rounded-lg">
-            <p className="font-medium mb-1">Como exportar do 2FAS:</p>
-            <p>1. Abra o app 2FAS</p>
-            <p>2. Vá em Configurações → Backup</p>
-            <p>3. Toque em "Exportar" e salve o arquivo .2fas</p>
+            <p className="font-medium mb-1">{sourceInfo.help[0]}</p>
+            {sourceInfo.help.slice(1).map((line) => (
+              <p key={line}>{line}</p>
+            ))}
           </div>
         </div>
       </div>
@@ -1,14 +1,30 @@
 import { useState } from 'react';
-import { Shield, Eye, EyeOff, Loader2, RotateCcw } from 'lucide-react';
+import { Shield, Eye, EyeOff, Loader2, RotateCcw, KeyRound, FileKey } from 'lucide-react';
 
 interface Props {
-  onSubmit: (password: string) => Promise<boolean>;
+  // Resolves to an error message when the attempt was refused
+  onSubmit: (password: string, keyfile?: number[]) => Promise<boolean | string>;
   onReset?: () => void;
+  // Same contract as onSubmit, with the recovery key and the new password
+  onRecover?: (recoveryKey: string, newPassword: string, keyfile?: number[]) => Promise<boolean | string>;
   isFirstTime: boolean;
+  keyfileRequired?: boolean;
 }
 
-export default function MasterPasswordModal({ onSubmit, onReset, isFirstTime }: Props) {
+export default function MasterPasswordModal({ onSubmit, onReset, onRecover, isFirstTime, keyfileRequired = false }: Props) {
   const [password, setPassword] = useState('');
+  const [recovering, setRecovering] = useState(false);
+  const [recoveryKey, setRecoveryKey] = useState('');
+  const [keyfile, setKeyfile] = useState<{ name: string; bytes: number[] } | null>(null);
+
+  const handleKeyfileSelect = async (e: React.ChangeEvent<HTMLInputElement>) => {
+    const file = e.target.files?.[0];
+    if (!file) return;
+    setKeyfile({ name: file.name, bytes: Array.from(new Uint8Array(await file.arrayBuffer())) });
+  };
+
+  // Optional when creating the vault or choosing the new password during recovery
+  const keyfileOptional = isFirstTime || recovering;
   const [error, setError] = useState('');
   const [loading, setLoading] = useState(false);
   const [showPassword, setShowPassword] = useState(false);
@@ -20,9 +36,13 @@ export default function MasterPasswordModal({ onSubmit, onReset, isFirstTime }:
     setLoading(true);
     setError('');
 
-    const isValid = await onSubmit(password);
-    if (!isValid) {
-      setError('Senha inválida');
+    const result = recovering && onRecover
+      ? await onRecover(recoveryKey, password, keyfile?.bytes)
+      : await onSubmit(password, keyfile?.bytes);
+    if (typeof result === 'string') {
+      setError(result);
+    } else if (!result) {
+      setError(recovering ? 'Chave de recuperação inválida' : 'Senha inválida');
     }
 
     setLoading(false);
@@ -38,14 +58,36 @@ export default function MasterPasswordModal({ onSubmit, onReset, isFirstTime }:
           Plaxo OTP
         </h1>
         <p className="text-plaxo-text-secondary text-sm">
-          {isFirstTime ? 'Configure sua senha mestre para proteger seus dados' : 'Digite sua senha mestre para continuar'}
+          {isFirstTime
+            ? 'Configure sua senha mestre para proteger seus dados'
+            : recovering
+              ? 'Digite sua chave de recuperação e uma nova senha mestre'
+              : 'Digite sua senha mestre para continuar'}
         </p>
       </div>
 
       <form onSubmit={handleSubmit} className="space-y-6">
+        {recovering && (
+          <div className="space-y-2">
+            <label className="text-sm font-medium text-plaxo-text">
+              Chave de Recuperação
+            </label>
+            <input
+              type="text"
+              value={recoveryKey}
+              onChange={(e) => setRecoveryKey(e.target.value)}
+              placeholder="XXXX-XXXX-XXXX-XXXX-XXXX-XXXX-XXXX-XXXX"
+              className="w-full px-4 py-3 bg-plaxo-background/50 border border-plaxo-border rounded-xl text-plaxo-text placeholder-plaxo-text-secondary font-mono focus:outline-none focus:border-plaxo-primary focus:ring-2 focus:ring-plaxo-primary/20 transition-all"
+              disabled={loading}
+              autoComplete="off"
+              spellCheck={false}
+            />
+          </div>
+        )}
+
         <div className="space-y-2">
           <label className="text-sm font-medium text-plaxo-text">
-            Senha Mestre
+            {recovering ? 'Nova Senha Mestre' : 'Senha Mestre'}
           </label>
           <div className="relative">
             <input
@@ -75,9 +117,32 @@ export default function MasterPasswordModal({ onSubmit, onReset, isFirstTime }:
           )}
         </div>
 
+        {(keyfileRequired || keyfileOptional) && (
+          <div className="space-y-2">
+            <label className="text-sm font-medium text-plaxo-text">
+              {keyfileOptional ? 'Arquivo-chave (opcional)' : 'Arquivo-chave'}
+            </label>
+            <label className="w-full flex items-center gap-2 px-4 py-3 bg-plaxo-background/50 border border-plaxo-border rounded-xl text-plaxo-text-secondary hover:border-plaxo-primary cursor-pointer transition-all text-sm">
+              <FileKey className="w-4 h-4" />
+              <span className="truncate">{keyfile ? keyfile.name : 'Selecionar arquivo-chave'}</span>
+              <input
+                type="file"
+                onChange={handleKeyfileSelect}
+                className="hidden"
+                disabled={loading}
+              />
+            </label>
+          </div>
+        )}
+
         <button
           type="submit"
-          disabled={loading || !password.trim()}
+          disabled={
+            loading ||
+            !password.trim() ||
+            (recovering && !recoveryKey.trim()) ||
+            (keyfileRequired && !keyfileOptional && !keyfile)
+          }
           className="w-full flex items-center justify-center gap-2 py-3 bg-plaxo-primary hover:bg-plaxo-primary-hover text-plaxo-background font-semibold rounded-xl transition-all duration-200 disabled:opacity-50 disabled:cursor-not-allowed"
         >
           {loading ? (
@@ -88,11 +153,26 @@ export default function MasterPasswordModal({ onSubmit, onReset, isFirstTime }:
           ) : (
             <>
               <Shield className="w-4 h-4" />
-              {isFirstTime ? 'Criar Senha Mestre' : 'Acessar Aplicação'}
+              {isFirstTime ? 'Criar Senha Mestre' : recovering ? 'Recuperar Acesso' : 'Acessar Aplicação'}
             </>
           )}
         </button>
 
+        {!isFirstTime && onRecover && (
+          <button
+            type="button"
+            onClick={() => {
+              setRecovering(!recovering);
+              setError('');
+            }}
+            className="w-full flex items-center justify-center gap-2 py-2 text-plaxo-text-secondary hover:text-plaxo-text transition-colors text-sm"
+            disabled={loading}
+          >
+            <KeyRound className="w-4 h-4" />
+            {recovering ? 'Voltar para a senha mestre' : 'Esqueci a senha, usar chave de recuperação'}
+          </button>
+        )}
+
         {!isFirstTime && onReset && (
           <button
             type="button"
@@ -1,14 +1,20 @@
 import { useState, useEffect, useCallback } from 'react';
 import { invoke } from '@tauri-apps/api/tauri';
-import { Copy, Trash2, Check, Clock, Edit3, X } from 'lucide-react';
+import { Copy, Trash2, Check, Clock, Edit3, X, RefreshCw, Hash, QrCode } from 'lucide-react';
 import { getPlatformIcon, getPlatformColor } from '../utils/platformIcons';
 import { useOtpTimer } from '../hooks/useOtpTimer';
+import QrCodeModal, { QrCodeImage } from './QrCodeModal';
 
 interface Props {
   app: {
     id: string;
     name: string;
     secret: string;
+    algorithm?: 'SHA1' | 'SHA256' | 'SHA512';
+    digits?: number;
+    period?: number;
+    otp_type?: 'totp' | 'hotp' | 'steam';
+    counter?: number;
   };
   onDelete: (id: string) => void;
   onEdit?: () => void;
@@ -21,8 +27,14 @@ export default function OtpItem({ app, onDelete, onEdit, isVisible = true }: Pro
   const [isEditing, setIsEditing] = useState(false);
   const [editName, setEditName] = useState(app.name);
   const [isGenerating, setIsGenerating] = useState(false);
+  // HOTP codes are only generated on request, each one uses up a counter value
+  const isHotp = app.otp_type === 'hotp';
+  const [isResyncing, setIsResyncing] = useState(false);
+  const [resyncCode, setResyncCode] = useState('');
+  const [resyncError, setResyncError] = useState('');
+  const [qrCode, setQrCode] = useState<QrCodeImage | null>(null);
 
-  const { timeLeft, shouldRefresh } = useOtpTimer();
+  const { timeLeft, shouldRefresh } = useOtpTimer(app.period ?? 30);
 
   useEffect(() => {
     if (!isVisible) {
@@ -32,7 +44,7 @@ export default function OtpItem({ app, onDelete, onEdit, isVisible = true }: Pro
   }, [isVisible]);
 
   const generateOtp = useCallback(async () => {
-    if (isGenerating || !isVisible) return;
+    if (isGenerating || !isVisible || isHotp) return;
 
     setIsGenerating(true);
     try {
@@ -44,7 +56,48 @@ export default function OtpItem({ app, onDelete, onEdit, isVisible = true }: Pro
     } finally {
       setIsGenerating(false);
     }
-  }, [app.id, isGenerating, isVisible]);
+  }, [app.id, isGenerating, isVisible, isHotp]);
+
+  const showQrCode = async () => {
+    try {
+      setQrCode(await invoke<QrCodeImage>('generate_qr_code', { appId: app.id, format: 'png' }));
+    } catch (error) {
+      console.error('Failed to generate QR code:', error);
+    }
+  };
+
+  const nextHotpCode = async () => {
+    if (isGenerating) return;
+
+    setIsGenerating(true);
+    try {
+      setOtp(await invoke<string>('next_hotp_code', { appId: app.id }));
+      onEdit?.();
+    } catch (error) {
+      console.error('Failed to generate HOTP code:', error);
+      setOtp('ERROR');
+    } finally {
+      setIsGenerating(false);
+    }
+  };
+
+  // Moves the counter past a code the server accepted, after codes were
+  // generated here without being used or on another device
+  const handleResync = async () => {
+    if (!resyncCode.trim()) return;
+
+    setResyncError('');
+    try {
+      await invoke<number>('resync_hotp_counter', { appId: app.id, code: resyncCode.trim() });
+      setResyncCode('');
+      setIsResyncing(false);
+      setOtp('------');
+      onEdit?.();
+    } catch (error) {
+      console.error('Failed to resync HOTP counter:', error);
+      setResyncError('Código não corresponde aos próximos valores do contador');
+    }
+  };
 
   useEffect(() => {
     if (isVisible) {
@@ -87,7 +140,7 @@ export default function OtpItem({ app, onDelete, onEdit, isVisible = true }: Pro
     }
   };
 
-  const progressPercentage = (timeLeft / 30) * 100;
+  const progressPercentage = (timeLeft / (app.period ?? 30)) * 100;
   const isExpiring = timeLeft <= 10;
 
   const PlatformIcon = getPlatformIcon(app.name);
@@ -143,6 +196,13 @@ export default function OtpItem({ app, onDelete, onEdit, isVisible = true }: Pro
               <Edit3 className="w-4 h-4" />
             </button>
           )}
+          <button
+            onClick={showQrCode}
+            className="text-plaxo-text-secondary hover:text-plaxo-text p-1.5 rounded-lg hover:bg-plaxo-hover transition-colors"
+            title="Mostrar código QR"
+          >
+            <QrCode className="w-4 h-4" />
+          </button>
           <button
             onClick={() => onDelete(app.id)}
             className="text-plaxo-text-secondary hover:text-plaxo-error p-1.5 rounded-lg hover:bg-plaxo-error/10 transition-colors"
@@ -166,38 +226,105 @@ export default function OtpItem({ app, onDelete, onEdit, isVisible = true }: Pro
           )}
         </div>
 
-        <div className="space-y-2">
-          <div className="otp-progress">
-            <div
-              className={`otp-progress-bar ${isExpiring ? 'bg-plaxo-warning' : 'bg-plaxo-primary'}`}
-              style={{ width: `${progressPercentage}%` }}
-            />
+        {isHotp ? (
+          <div className="space-y-2">
+            {isResyncing && (
+              <div className="space-y-1">
+                <div className="flex items-center gap-2">
+                  <input
+                    type="text"
+                    inputMode="numeric"
+                    value={resyncCode}
+                    onChange={(e) => setResyncCode(e.target.value)}
+                    onKeyDown={(e) => {
+                      if (e.key === 'Enter') handleResync();
+                      if (e.key === 'Escape') setIsResyncing(false);
+                    }}
+                    placeholder="Último código aceito pelo serviço"
+                    className="flex-1 px-3 py-1.5 bg-plaxo-background/50 border border-plaxo-border rounded-lg text-sm text-plaxo-text placeholder-plaxo-text-secondary focus:outline-none focus:border-plaxo-primary"
+                    autoFocus
+                  />
+                  <button
+                    onClick={handleResync}
+                    className="text-green-500 hover:text-green-600 p-1"
+                    title="Sincronizar"
+                  >
+                    <Check className="w-4 h-4" />
+                  </button>
+                </div>
+                {resyncError && <div className="text-xs text-red-500">{resyncError}</div>}
+              </div>
+            )}
+            <div className="flex items-center justify-between">
+              <button
+                onClick={() => {
+                  setIsResyncing(!isResyncing);
+                  setResyncError('');
+                }}
+                className="flex items-center gap-2 text-xs text-plaxo-text-secondary hover:text-plaxo-text transition-colors"
+                title="Sincronizar contador"
+              >
+                <Hash className="w-3 h-3" />
+                <span>Contador {app.counter ?? 0}</span>
+              </button>
+              <div className="flex items-center gap-2">
+                <button
+                  onClick={nextHotpCode}
+                  disabled={isGenerating}
+                  className="flex items-center gap-2 px-3 py-1.5 bg-plaxo-surface hover:bg-plaxo-surface-hover text-plaxo-text border border-plaxo-border text-sm font-medium rounded-lg transition-colors disabled:opacity-50"
+                >
+                  <RefreshCw className="w-3 h-3" />
+                  Próximo código
+                </button>
+                <button
+                  onClick={copyToClipboard}
+                  disabled={otp === 'ERROR' || otp === '------'}
+                  className="flex items-center gap-2 px-3 py-1.5 bg-plaxo-primary hover:bg-plaxo-primary-hover text-plaxo-background text-sm font-medium rounded-lg transition-colors disabled:opacity-50 disabled:cursor-not-allowed"
+                >
+                  {copied ? <Check className="w-3 h-3" /> : <Copy className="w-3 h-3" />}
+                  {copied ? 'Copiado!' : 'Copiar'}
+                </button>
+              </div>
+            </div>
           </div>
-          <div className="flex items-center justify-between">
-            <div className="flex items-center gap-2 text-xs text-plaxo-text-secondary">
-              <Clock className="w-3 h-3" />
-              <span>{timeLeft}s restantes</span>
+        ) : (
+          <div className="space-y-2">
+            <div className="otp-progress">
+              <div
+                className={`otp-progress-bar ${isExpiring ? 'bg-plaxo-warning' : 'bg-plaxo-primary'}`}
+                style={{ width: `${progressPercentage}%` }}
+              />
             </div>
-            <button
-              onClick={copyToClipboard}
-              disabled={otp === 'ERROR'}
-              className="flex items-center gap-2 px-3 py-1.5 bg-plaxo-primary hover:bg-plaxo-primary-hover text-plaxo-background text-sm font-medium rounded-lg transition-colors disabled:opacity-50 disabled:cursor-not-allowed"
-            >
-              {copied ? (
-                <>
-                  <Check className="w-3 h-3" />
-                  Copiado!
-                </>
+            <div className="flex items-center justify-between">
+              <div className="flex items-center gap-2 text-xs text-plaxo-text-secondary">
+                <Clock className="w-3 h-3" />
+                <span>{timeLeft}s restantes</span>
+              </div>
+              <button
+                onClick={copyToClipboard}
+                disabled={otp === 'ERROR'}
+                className="flex items-center gap-2 px-3 py-1.5 bg-plaxo-primary hover:bg-plaxo-primary-hover text-plaxo-background text-sm font-medium rounded-lg transition-colors disabled:opacity-50 disabled:cursor-not-allowed"
+              >
+                {copied ? (
+                  <>
+                    <Check className="w-3 h-3" />
+                    Copiado!
+                  </>
               ) : (
-                <>
-                  <Copy className="w-3 h-3" />
-                  Copiar
-                </>
-              )}
-            </button>
+                  <>
+                    <Copy className="w-3 h-3" />
+                    Copiar
+                  </>
+                )}
+              </button>
+            </div>
           </div>
-        </div>
+        )}
       </div>
+
+      {qrCode && (
+        <QrCodeModal title={app.name} images={[qrCode]} onClose={() => setQrCode(null)} />
+      )}
     </div>
   );
 }
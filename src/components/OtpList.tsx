@@ -4,6 +4,9 @@ interface OtpApp {
   id: string;
   name: string;
   secret: string;
+  period?: number;
+  otp_type?: 'totp' | 'hotp' | 'steam';
+  counter?: number;
 }
 
 interface Props {
@@ -0,0 +1,77 @@
+import { useState } from 'react';
+import { QrCode, X, ChevronLeft, ChevronRight } from 'lucide-react';
+
+export interface QrCodeImage {
+  content: string;
+  format: 'png' | 'svg';
+  data: string;
+}
+
+interface Props {
+  title: string;
+  // Several images when one QR code cannot hold every entry
+  images: QrCodeImage[];
+  onClose: () => void;
+}
+
+export default function QrCodeModal({ title, images, onClose }: Props) {
+  const [index, setIndex] = useState(0);
+  const image = images[index];
+
+  return (
+    <div className="fixed inset-0 bg-black/50 backdrop-blur-sm flex items-center justify-center p-4 z-50" onClick={onClose}>
+      <div className="bg-plaxo-surface border border-plaxo-border rounded-2xl p-6 w-full max-w-md shadow-2xl max-h-full overflow-y-auto" onClick={(e) => e.stopPropagation()}>
+        <div className="flex items-center justify-between mb-6">
+          <div className="flex items-center gap-3">
+            <div className="flex items-center justify-center w-10 h-10 bg-plaxo-primary/10 rounded-xl">
+              <QrCode className="w-5 h-5 text-plaxo-primary" />
+            </div>
+            <h2 className="text-lg font-heading font-semibold text-plaxo-text truncate">
+              {title}
+            </h2>
+          </div>
+          <button
+            onClick={onClose}
+            className="text-plaxo-text-secondary hover:text-plaxo-text p-1.5 rounded-lg hover:bg-plaxo-background/50 transition-colors"
+          >
+            <X className="w-5 h-5" />
+          </button>
+        </div>
+
+        <div className="space-y-4">
+          {image && (
+            <img
+              src={`data:image/png;base64,${image.data}`}
+              alt={title}
+              className="w-full bg-white rounded-xl p-2"
+            />
+          )}
+
+          {images.length > 1 && (
+            <div className="flex items-center justify-between text-sm text-plaxo-text">
+              <button
+                onClick={() => setIndex(index - 1)}
+                disabled={index === 0}
+                className="p-2 rounded-lg hover:bg-plaxo-background/50 disabled:opacity-30"
+              >
+                <ChevronLeft className="w-5 h-5" />
+              </button>
+              {index + 1} de {images.length}
+              <button
+                onClick={() => setIndex(index + 1)}
+                disabled={index === images.length - 1}
+                className="p-2 rounded-lg hover:bg-plaxo-background/50 disabled:opacity-30"
+              >
+                <ChevronRight className="w-5 h-5" />
+              </button>
+            </div>
+          )}
+
+          <p className="text-xs text-plaxo-error bg-plaxo-error/10 p-3 rounded-lg">
+            Qualquer pessoa que veja este código pode gerar seus códigos de acesso. Feche esta janela assim que terminar.
+          </p>
+        </div>
+      </div>
+    </div>
+  );
+}
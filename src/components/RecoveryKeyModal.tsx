@@ -0,0 +1,62 @@
+import { useState } from 'react';
+import { KeyRound, Copy, Check } from 'lucide-react';
+import { invoke } from '@tauri-apps/api/tauri';
+
+interface Props {
+  recoveryKey: string | null;
+  onClose: () => void;
+}
+
+export default function RecoveryKeyModal({ recoveryKey, onClose }: Props) {
+  const [copied, setCopied] = useState(false);
+
+  if (!recoveryKey) return null;
+
+  const handleCopy = async () => {
+    try {
+      await invoke('copy_to_clipboard', { text: recoveryKey });
+      setCopied(true);
+    } catch (error) {
+      console.error('Failed to copy recovery key:', error);
+    }
+  };
+
+  return (
+    <div className="fixed inset-0 bg-black bg-opacity-50 flex items-center justify-center z-50">
+      <div className="bg-plaxo-surface border border-plaxo-border rounded-lg p-6 max-w-md w-full mx-4">
+        <div className="flex items-center gap-3 mb-4">
+          <KeyRound className="w-6 h-6 text-plaxo-primary" />
+          <h3 className="text-lg font-semibold text-plaxo-text-primary">
+            Chave de recuperação
+          </h3>
+        </div>
+
+        <p className="text-plaxo-text-secondary mb-4">
+          Guarde esta chave em um lugar seguro. Ela permite acessar o cofre e definir uma nova senha caso você esqueça a senha mestre. Ela não será mostrada novamente.
+        </p>
+
+        <div className="flex items-center gap-2 mb-6">
+          <code className="flex-1 px-3 py-2 bg-plaxo-background/50 border border-plaxo-border rounded-md text-plaxo-text font-mono text-sm break-all select-all">
+            {recoveryKey}
+          </code>
+          <button
+            onClick={handleCopy}
+            className="p-2 text-plaxo-text-secondary hover:text-plaxo-text-primary border border-plaxo-border rounded-md hover:bg-plaxo-hover transition-colors"
+            title="Copiar"
+          >
+            {copied ? <Check className="w-4 h-4" /> : <Copy className="w-4 h-4" />}
+          </button>
+        </div>
+
+        <div className="flex justify-end">
+          <button
+            onClick={onClose}
+            className="px-4 py-2 text-plaxo-background bg-plaxo-primary hover:bg-plaxo-primary-hover rounded-md transition-colors"
+          >
+            Já guardei a chave
+          </button>
+        </div>
+      </div>
+    </div>
+  );
+}
@@ -1,13 +1,14 @@
-import { Search, Plus, Upload } from 'lucide-react';
+import { Search, Plus, Upload, Download } from 'lucide-react';
 
 interface Props {
   value: string;
   onChange: (value: string) => void;
   onAddClick: () => void;
   onImportClick: () => void;
+  onExportClick: () => void;
 }
 
-export default function SearchBar({ value, onChange, onAddClick, onImportClick }: Props) {
+export default function SearchBar({ value, onChange, onAddClick, onImportClick, onExportClick }: Props) {
   return (
     <div className="space-y-3">
       <div className="flex gap-3">
@@ -30,13 +31,22 @@ export default function SearchBar({ value, onChange, onAddClick, onImportClick }
         </button>
       </div>
       
-      <button
-        onClick={onImportClick}
-        className="w-full flex items-center justify-center gap-2 py-2.5 bg-plaxo-background/30 hover:bg-plaxo-background/50 text-plaxo-text border border-plaxo-border rounded-xl transition-colors text-sm font-medium"
-      >
-        <Upload className="w-4 h-4" />
-        Importar do 2FAS
-      </button>
+      <div className="flex gap-3">
+        <button
+          onClick={onImportClick}
+          className="flex-1 flex items-center justify-center gap-2 py-2.5 bg-plaxo-background/30 hover:bg-plaxo-background/50 text-plaxo-text border border-plaxo-border rounded-xl transition-colors text-sm font-medium"
+        >
+          <Upload className="w-4 h-4" />
+          Importar
+        </button>
+        <button
+          onClick={onExportClick}
+          className="flex-1 flex items-center justify-center gap-2 py-2.5 bg-plaxo-background/30 hover:bg-plaxo-background/50 text-plaxo-text border border-plaxo-border rounded-xl transition-colors text-sm font-medium"
+        >
+          <Download className="w-4 h-4" />
+          Exportar
+        </button>
+      </div>
     </div>
   );
 }
@@ -0,0 +1,424 @@
+import { useEffect, useState } from 'react';
+import { ShieldCheck, X, Loader2, AlertCircle, CheckCircle, FileKey } from 'lucide-react';
+import { invoke } from '@tauri-apps/api/tauri';
+
+interface Props {
+  keyfileRequired: boolean;
+  onKeyfileChange: (required: boolean) => void;
+  onVaultRepaired: () => void;
+  onClose: () => void;
+}
+
+interface IntegrityReport {
+  files: { id: string; kind: 'main' | 'temp' | 'backup'; entry_count: number | null; error: string | null }[];
+  repair_from: string | null;
+}
+
+const FILE_KINDS: Record<IntegrityReport['files'][number]['kind'], string> = {
+  main: 'Cofre',
+  temp: 'Gravação interrompida',
+  backup: 'Backup',
+};
+
+type Keyfile = { name: string; bytes: number[] } | null;
+
+// Lowest threshold the backend accepts, see throttle::MIN_WIPE_AFTER
+const MIN_WIPE_AFTER = 5;
+
+const inputClass =
+  'w-full px-4 py-3 bg-plaxo-background/50 border border-plaxo-border rounded-xl text-plaxo-text placeholder-plaxo-text-secondary focus:outline-none focus:border-plaxo-primary focus:ring-2 focus:ring-plaxo-primary/20 transition-all text-sm';
+
+const buttonClass =
+  'w-full flex items-center justify-center gap-2 py-2.5 px-4 bg-plaxo-primary hover:bg-plaxo-primary-hover text-plaxo-background font-semibold rounded-xl transition-colors disabled:opacity-50 disabled:cursor-not-allowed text-sm';
+
+const errorText = (err: unknown, fallback: string) => {
+  switch (err) {
+    case 'InvalidMasterPassword':
+      return 'Senha atual incorreta';
+    case 'KeyfileRequired':
+      return 'Selecione o arquivo-chave atual';
+    default:
+      return typeof err === 'string' ? err : fallback;
+  }
+};
+
+function KeyfileInput({ label, value, onChange, disabled }: {
+  label: string;
+  value: Keyfile;
+  onChange: (keyfile: Keyfile) => void;
+  disabled: boolean;
+}) {
+  const handleSelect = async (e: React.ChangeEvent<HTMLInputElement>) => {
+    const file = e.target.files?.[0];
+    if (!file) return;
+    onChange({ name: file.name, bytes: Array.from(new Uint8Array(await file.arrayBuffer())) });
+  };
+
+  return (
+    <label className="w-full flex items-center gap-2 px-4 py-3 bg-plaxo-background/50 border border-plaxo-border rounded-xl text-plaxo-text-secondary hover:border-plaxo-primary cursor-pointer transition-all text-sm">
+      <FileKey className="w-4 h-4" />
+      <span className="truncate">{value ? value.name : label}</span>
+      <input type="file" onChange={handleSelect} className="hidden" disabled={disabled} />
+    </label>
+  );
+}
+
+function Message({ error, success }: { error: string; success: string }) {
+  if (error) {
+    return (
+      <div className="flex items-center gap-2 text-plaxo-error text-sm bg-plaxo-error/10 px-3 py-2 rounded-lg border border-plaxo-error/20">
+        <AlertCircle className="w-4 h-4" />
+        {error}
+      </div>
+    );
+  }
+  if (success) {
+    return (
+      <div className="flex items-center gap-2 text-plaxo-success text-sm bg-plaxo-success/10 px-3 py-2 rounded-lg border border-plaxo-success/20">
+        <CheckCircle className="w-4 h-4" />
+        {success}
+      </div>
+    );
+  }
+  return null;
+}
+
+function ChangePasswordSection({ keyfileRequired }: { keyfileRequired: boolean }) {
+  const [currentPassword, setCurrentPassword] = useState('');
+  const [keyfile, setKeyfile] = useState<Keyfile>(null);
+  const [newPassword, setNewPassword] = useState('');
+  const [confirmPassword, setConfirmPassword] = useState('');
+  const [loading, setLoading] = useState(false);
+  const [error, setError] = useState('');
+  const [success, setSuccess] = useState('');
+
+  const handleSubmit = async () => {
+    setError('');
+    setSuccess('');
+
+    if (newPassword !== confirmPassword) {
+      setError('As senhas não coincidem');
+      return;
+    }
+
+    setLoading(true);
+
+    try {
+      await invoke('change_master_password', {
+        currentPassword,
+        newPassword,
+        keyfile: keyfile?.bytes ?? null,
+      });
+      setCurrentPassword('');
+      setNewPassword('');
+      setConfirmPassword('');
+      setKeyfile(null);
+      setSuccess('Senha mestre alterada');
+    } catch (err) {
+      setError(errorText(err, 'Erro ao alterar a senha'));
+    } finally {
+      setLoading(false);
+    }
+  };
+
+  return (
+    <section className="space-y-3">
+      <h3 className="text-sm font-semibold text-plaxo-text">Alterar senha mestre</h3>
+      <input
+        type="password"
+        value={currentPassword}
+        onChange={(e) => setCurrentPassword(e.target.value)}
+        placeholder="Senha atual"
+        className={inputClass}
+        disabled={loading}
+      />
+      {keyfileRequired && (
+        <KeyfileInput label="Arquivo-chave atual" value={keyfile} onChange={setKeyfile} disabled={loading} />
+      )}
+      <input
+        type="password"
+        value={newPassword}
+        onChange={(e) => setNewPassword(e.target.value)}
+        placeholder="Nova senha"
+        className={inputClass}
+        disabled={loading}
+      />
+      <input
+        type="password"
+        value={confirmPassword}
+        onChange={(e) => setConfirmPassword(e.target.value)}
+        placeholder="Confirme a nova senha"
+        className={inputClass}
+        disabled={loading}
+      />
+      <Message error={error} success={success} />
+      <button
+        onClick={handleSubmit}
+        disabled={loading || !currentPassword || !newPassword.trim() || (keyfileRequired && !keyfile)}
+        className={buttonClass}
+      >
+        {loading && <Loader2 className="w-4 h-4 animate-spin" />}
+        Alterar senha
+      </button>
+    </section>
+  );
+}
+
+function KeyfileSection({ keyfileRequired, onKeyfileChange }: Pick<Props, 'keyfileRequired' | 'onKeyfileChange'>) {
+  const [currentPassword, setCurrentPassword] = useState('');
+  const [currentKeyfile, setCurrentKeyfile] = useState<Keyfile>(null);
+  const [newKeyfile, setNewKeyfile] = useState<Keyfile>(null);
+  const [loading, setLoading] = useState(false);
+  const [error, setError] = useState('');
+  const [success, setSuccess] = useState('');
+
+  // No new keyfile removes the current one
+  const handleSubmit = async (keyfile: Keyfile) => {
+    setLoading(true);
+    setError('');
+    setSuccess('');
+
+    try {
+      await invoke('set_keyfile', {
+        currentPassword,
+        currentKeyfile: currentKeyfile?.bytes ?? null,
+        newKeyfile: keyfile?.bytes ?? null,
+      });
+      setCurrentPassword('');
+      setCurrentKeyfile(null);
+      setNewKeyfile(null);
+      onKeyfileChange(keyfile !== null);
+      setSuccess(keyfile ? 'Arquivo-chave definido' : 'Arquivo-chave removido');
+    } catch (err) {
+      setError(errorText(err, 'Erro ao alterar o arquivo-chave'));
+    } finally {
+      setLoading(false);
+    }
+  };
+
+  const ready = !!currentPassword && (!keyfileRequired || !!currentKeyfile);
+
+  return (
+    <section className="space-y-3">
+      <h3 className="text-sm font-semibold text-plaxo-text">Arquivo-chave</h3>
+      <p className="text-xs text-plaxo-text-secondary">
+        {keyfileRequired
+          ? 'Este cofre exige um arquivo-chave além da senha mestre.'
+          : 'Exija um arquivo além da senha mestre para desbloquear o cofre. Guarde uma cópia dele: sem o arquivo, só a chave de recuperação abre o cofre.'}
+      </p>
+      <input
+        type="password"
+        value={currentPassword}
+        onChange={(e) => setCurrentPassword(e.target.value)}
+        placeholder="Senha atual"
+        className={inputClass}
+        disabled={loading}
+      />
+      {keyfileRequired && (
+        <KeyfileInput label="Arquivo-chave atual" value={currentKeyfile} onChange={setCurrentKeyfile} disabled={loading} />
+      )}
+      <KeyfileInput label="Novo arquivo-chave" value={newKeyfile} onChange={setNewKeyfile} disabled={loading} />
+      <Message error={error} success={success} />
+      <div className="flex gap-3">
+        <button
+          onClick={() => handleSubmit(newKeyfile)}
+          disabled={loading || !ready || !newKeyfile}
+          className={buttonClass}
+        >
+          {loading && <Loader2 className="w-4 h-4 animate-spin" />}
+          {keyfileRequired ? 'Trocar arquivo-chave' : 'Definir arquivo-chave'}
+        </button>
+        {keyfileRequired && (
+          <button
+            onClick={() => handleSubmit(null)}
+            disabled={loading || !ready}
+            className="w-full py-2.5 px-4 bg-plaxo-background/30 hover:bg-plaxo-background/50 text-plaxo-text border border-plaxo-border rounded-xl transition-colors text-sm font-medium disabled:opacity-50 disabled:cursor-not-allowed"
+          >
+            Remover
+          </button>
+        )}
+      </div>
+    </section>
+  );
+}
+
+function WipeSection() {
+  const [enabled, setEnabled] = useState(false);
+  const [attempts, setAttempts] = useState(10);
+  const [loading, setLoading] = useState(false);
+  const [error, setError] = useState('');
+  const [success, setSuccess] = useState('');
+
+  useEffect(() => {
+    invoke<number | null>('get_wipe_after_failures').then((current) => {
+      setEnabled(current !== null);
+      if (current !== null) setAttempts(current);
+    });
+  }, []);
+
+  const handleSubmit = async () => {
+    setLoading(true);
+    setError('');
+    setSuccess('');
+
+    try {
+      await invoke('set_wipe_after_failures', { attempts: enabled ? attempts : null });
+      setSuccess(enabled ? `O cofre será apagado após ${attempts} tentativas inválidas` : 'Apagamento automático desativado');
+    } catch (err) {
+      setError(errorText(err, 'Erro ao salvar'));
+    } finally {
+      setLoading(false);
+    }
+  };
+
+  return (
+    <section className="space-y-3">
+      <h3 className="text-sm font-semibold text-plaxo-text">Apagar após tentativas inválidas</h3>
+      <label className="flex items-center gap-3 text-sm text-plaxo-text">
+        <input
+          type="checkbox"
+          checked={enabled}
+          onChange={(e) => setEnabled(e.target.checked)}
+          disabled={loading}
+        />
+        Apagar o cofre após várias senhas erradas seguidas
+      </label>
+      {enabled && (
+        <>
+          <input
+            type="number"
+            min={MIN_WIPE_AFTER}
+            value={attempts}
+            onChange={(e) => setAttempts(Math.floor(Number(e.target.value) || 0))}
+            className={inputClass}
+            disabled={loading}
+          />
+          <p className="text-xs text-plaxo-error">
+            Sem um backup ou a sincronização com o Google Drive, os códigos apagados não podem ser recuperados.
+          </p>
+        </>
+      )}
+      <Message error={error} success={success} />
+      <button
+        onClick={handleSubmit}
+        disabled={loading || (enabled && attempts < MIN_WIPE_AFTER)}
+        className={buttonClass}
+      >
+        {loading && <Loader2 className="w-4 h-4 animate-spin" />}
+        Salvar
+      </button>
+    </section>
+  );
+}
+
+function IntegritySection({ onVaultRepaired }: Pick<Props, 'onVaultRepaired'>) {
+  const [report, setReport] = useState<IntegrityReport | null>(null);
+  const [loading, setLoading] = useState(false);
+  const [error, setError] = useState('');
+  const [success, setSuccess] = useState('');
+
+  const handleCheck = async () => {
+    setLoading(true);
+    setError('');
+    setSuccess('');
+
+    try {
+      setReport(await invoke<IntegrityReport>('check_vault_integrity'));
+    } catch (err) {
+      setError(errorText(err, 'Erro ao verificar o cofre'));
+    } finally {
+      setLoading(false);
+    }
+  };
+
+  const handleRepair = async (id: string) => {
+    setLoading(true);
+    setError('');
+
+    try {
+      const count = await invoke<number>('repair_vault', { id });
+      setReport(null);
+      setSuccess(`Cofre restaurado com ${count} aplicativo(s)`);
+      onVaultRepaired();
+    } catch (err) {
+      setError(errorText(err, 'Erro ao reparar o cofre'));
+    } finally {
+      setLoading(false);
+    }
+  };
+
+  return (
+    <section className="space-y-3">
+      <h3 className="text-sm font-semibold text-plaxo-text">Integridade do cofre</h3>
+      {report && (
+        <div className="space-y-2">
+          {report.files.map((file) => (
+            <div key={file.id} className="p-3 text-sm bg-plaxo-background/30 rounded-lg">
+              <p className="text-plaxo-text font-medium">{FILE_KINDS[file.kind]}</p>
+              <p className="text-plaxo-text-secondary truncate">{file.id}</p>
+              {file.error === null ? (
+                <p className="text-plaxo-success">{file.entry_count} aplicativo(s)</p>
+              ) : (
+                <p className="text-plaxo-error">{file.error}</p>
+              )}
+            </div>
+          ))}
+          {report.repair_from === null ? (
+            report.files.some((file) => file.kind === 'main' && file.error === null) && (
+              <p className="text-sm text-plaxo-success">O cofre está íntegro</p>
+            )
+          ) : (
+            <button
+              onClick={() => handleRepair(report.repair_from!)}
+              disabled={loading}
+              className={buttonClass}
+            >
+              Restaurar a partir de {report.repair_from}
+            </button>
+          )}
+        </div>
+      )}
+      <Message error={error} success={success} />
+      <button
+        onClick={handleCheck}
+        disabled={loading}
+        className="w-full flex items-center justify-center gap-2 py-2.5 px-4 bg-plaxo-background/30 hover:bg-plaxo-background/50 text-plaxo-text border border-plaxo-border rounded-xl transition-colors text-sm font-medium disabled:opacity-50 disabled:cursor-not-allowed"
+      >
+        {loading && <Loader2 className="w-4 h-4 animate-spin" />}
+        Verificar integridade
+      </button>
+    </section>
+  );
+}
+
+export default function SecurityModal({ keyfileRequired, onKeyfileChange, onVaultRepaired, onClose }: Props) {
+  return (
+    <div className="fixed inset-0 bg-black/50 backdrop-blur-sm flex items-center justify-center p-4 z-50" onClick={onClose}>
+      <div className="bg-plaxo-surface border border-plaxo-border rounded-2xl p-6 w-full max-w-md shadow-2xl max-h-full overflow-y-auto" onClick={(e) => e.stopPropagation()}>
+        <div className="flex items-center justify-between mb-6">
+          <div className="flex items-center gap-3">
+            <div className="flex items-center justify-center w-10 h-10 bg-plaxo-primary/10 rounded-xl">
+              <ShieldCheck className="w-5 h-5 text-plaxo-primary" />
+            </div>
+            <h2 className="text-lg font-heading font-semibold text-plaxo-text">
+              Segurança
+            </h2>
+          </div>
+          <button
+            onClick={onClose}
+            className="text-plaxo-text-secondary hover:text-plaxo-text p-1.5 rounded-lg hover:bg-plaxo-background/50 transition-colors"
+          >
+            <X className="w-5 h-5" />
+          </button>
+        </div>
+
+        <div className="space-y-6">
+          <ChangePasswordSection keyfileRequired={keyfileRequired} />
+          <KeyfileSection keyfileRequired={keyfileRequired} onKeyfileChange={onKeyfileChange} />
+          <WipeSection />
+          <IntegritySection onVaultRepaired={onVaultRepaired} />
+        </div>
+      </div>
+    </div>
+  );
+}
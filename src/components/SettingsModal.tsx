@@ -0,0 +1,155 @@
+import { useState } from 'react';
+import { Settings as SettingsIcon, X, Loader2, AlertCircle } from 'lucide-react';
+import { invoke } from '@tauri-apps/api/tauri';
+import { Settings } from '../hooks/useSettings';
+
+interface Props {
+  settings: Settings;
+  onClose: () => void;
+}
+
+const inputClass =
+  'w-full px-3 py-2 bg-plaxo-background/50 border border-plaxo-border rounded-lg text-plaxo-text text-sm focus:outline-none focus:border-plaxo-primary';
+
+export default function SettingsModal({ settings, onClose }: Props) {
+  const [draft, setDraft] = useState<Settings>(settings);
+  const [loading, setLoading] = useState(false);
+  const [error, setError] = useState('');
+
+  const update = <K extends keyof Settings>(key: K, value: Settings[K]) => {
+    setDraft({ ...draft, [key]: value });
+  };
+
+  // Empty or invalid number fields count as 0, which turns the feature off
+  const number = (value: string) => Math.max(0, Math.floor(Number(value) || 0));
+
+  const handleSave = async () => {
+    setLoading(true);
+    setError('');
+
+    try {
+      // Applied everywhere through the 'settings-changed' event
+      await invoke('set_settings', { settings: draft });
+      onClose();
+    } catch (err) {
+      setError(typeof err === 'string' ? err : 'Erro ao salvar as configurações');
+    } finally {
+      setLoading(false);
+    }
+  };
+
+  return (
+    <div className="fixed inset-0 bg-black/50 backdrop-blur-sm flex items-center justify-center p-4 z-50" onClick={onClose}>
+      <div className="bg-plaxo-surface border border-plaxo-border rounded-2xl p-6 w-full max-w-md shadow-2xl max-h-full overflow-y-auto" onClick={(e) => e.stopPropagation()}>
+        <div className="flex items-center justify-between mb-6">
+          <div className="flex items-center gap-3">
+            <div className="flex items-center justify-center w-10 h-10 bg-plaxo-primary/10 rounded-xl">
+              <SettingsIcon className="w-5 h-5 text-plaxo-primary" />
+            </div>
+            <h2 className="text-lg font-heading font-semibold text-plaxo-text">
+              Configurações
+            </h2>
+          </div>
+          <button
+            onClick={onClose}
+            className="text-plaxo-text-secondary hover:text-plaxo-text p-1.5 rounded-lg hover:bg-plaxo-background/50 transition-colors"
+            disabled={loading}
+          >
+            <X className="w-5 h-5" />
+          </button>
+        </div>
+
+        <div className="space-y-4">
+          <label className="block space-y-1">
+            <span className="text-sm font-medium text-plaxo-text">Bloqueio automático (minutos, 0 desativa)</span>
+            <input
+              type="number"
+              min={0}
+              value={draft.auto_lock_minutes}
+              onChange={(e) => update('auto_lock_minutes', number(e.target.value))}
+              className={inputClass}
+              disabled={loading}
+            />
+          </label>
+
+          <label className="block space-y-1">
+            <span className="text-sm font-medium text-plaxo-text">Limpar área de transferência após (segundos, 0 mantém)</span>
+            <input
+              type="number"
+              min={0}
+              value={draft.clipboard_clear_seconds}
+              onChange={(e) => update('clipboard_clear_seconds', number(e.target.value))}
+              className={inputClass}
+              disabled={loading}
+            />
+          </label>
+
+          <label className="block space-y-1">
+            <span className="text-sm font-medium text-plaxo-text">Sincronizar com o Google Drive a cada (minutos, 0 só manual)</span>
+            <input
+              type="number"
+              min={0}
+              value={draft.sync_interval_minutes}
+              onChange={(e) => update('sync_interval_minutes', number(e.target.value))}
+              className={inputClass}
+              disabled={loading}
+            />
+          </label>
+
+          <label className="block space-y-1">
+            <span className="text-sm font-medium text-plaxo-text">Ordenar aplicativos por</span>
+            <select
+              value={draft.sort_order}
+              onChange={(e) => update('sort_order', e.target.value as Settings['sort_order'])}
+              className={inputClass}
+              disabled={loading}
+            >
+              <option value="added">Ordem de cadastro</option>
+              <option value="name">Nome</option>
+              <option value="issuer">Emissor</option>
+            </select>
+          </label>
+
+          <label className="block space-y-1">
+            <span className="text-sm font-medium text-plaxo-text">Idioma</span>
+            <select
+              value={draft.language}
+              onChange={(e) => update('language', e.target.value as Settings['language'])}
+              className={inputClass}
+              disabled={loading}
+            >
+              <option value="pt-BR">Português (Brasil)</option>
+              <option value="en">English</option>
+            </select>
+          </label>
+
+          <label className="flex items-center gap-3 text-sm text-plaxo-text">
+            <input
+              type="checkbox"
+              checked={draft.close_to_tray}
+              onChange={(e) => update('close_to_tray', e.target.checked)}
+              disabled={loading}
+            />
+            Fechar a janela minimiza para a bandeja
+          </label>
+
+          {error && (
+            <div className="flex items-center gap-2 text-plaxo-error text-sm bg-plaxo-error/10 px-3 py-2 rounded-lg border border-plaxo-error/20">
+              <AlertCircle className="w-4 h-4" />
+              {error}
+            </div>
+          )}
+
+          <button
+            onClick={handleSave}
+            disabled={loading}
+            className="w-full flex items-center justify-center gap-2 py-3 px-4 bg-plaxo-primary hover:bg-plaxo-primary-hover text-plaxo-background font-semibold rounded-xl transition-colors disabled:opacity-50 disabled:cursor-not-allowed"
+          >
+            {loading && <Loader2 className="w-4 h-4 animate-spin" />}
+            Salvar
+          </button>
+        </div>
+      </div>
+    </div>
+  );
+}
@@ -5,8 +5,8 @@ interface UseOtpTimerReturn {
   shouldRefresh: boolean;
 }
 
-export function useOtpTimer(): UseOtpTimerReturn {
-  const [timeLeft, setTimeLeft] = useState(30);
+export function useOtpTimer(period: number = 30): UseOtpTimerReturn {
+  const [timeLeft, setTimeLeft] = useState(period);
   const [shouldRefresh, setShouldRefresh] = useState(false);
   const [isVisible, setIsVisible] = useState(true);
   const intervalRef = useRef<NodeJS.Timeout | null>(null);
@@ -25,10 +25,10 @@ export function useOtpTimer(): UseOtpTimerReturn {
       if (!isVisible) return;
       
       const now = Math.floor(Date.now() / 1000);
-      const remaining = 30 - (now % 30);
+      const remaining = period - (now % period);
       setTimeLeft(remaining);
 
-      if (remaining === 30) {
+      if (remaining === period) {
         setShouldRefresh(true);
         setTimeout(() => setShouldRefresh(false), 100);
       }
@@ -49,7 +49,7 @@ export function useOtpTimer(): UseOtpTimerReturn {
         clearInterval(intervalRef.current);
       }
     };
-  }, [isVisible]);
+  }, [isVisible, period]);
 
   return { timeLeft, shouldRefresh };
 }
@@ -0,0 +1,32 @@
+import { useState, useEffect } from 'react';
+import { invoke } from '@tauri-apps/api/tauri';
+import { listen } from '@tauri-apps/api/event';
+
+export interface Settings {
+  auto_lock_minutes: number;
+  clipboard_clear_seconds: number;
+  sort_order: 'added' | 'name' | 'issuer';
+  sync_interval_minutes: number;
+  close_to_tray: boolean;
+  language: 'pt-BR' | 'en';
+}
+
+// Current settings, kept up to date with the 'settings-changed' event
+export function useSettings(): Settings | null {
+  const [settings, setSettings] = useState<Settings | null>(null);
+
+  useEffect(() => {
+    invoke<Settings>('get_settings')
+      .then(setSettings)
+      .catch((error) => console.error('Failed to load settings:', error));
+
+    const unlisten = listen<Settings>('settings-changed', (event) => {
+      setSettings(event.payload);
+    });
+    return () => {
+      unlisten.then((fn) => fn());
+    };
+  }, []);
+
+  return settings;
+}